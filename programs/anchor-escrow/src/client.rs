@@ -0,0 +1,318 @@
+use anchor_lang::{error::ErrorCode, prelude::Pubkey, solana_program::instruction::{AccountMeta, Instruction}, system_program, Discriminator, InstructionData, ToAccountMetas};
+use anchor_spl::{associated_token::{self, get_associated_token_address}, token::{self, spl_token::native_mint}};
+use solana_compute_budget_interface::ComputeBudgetInstruction;
+use std::hash::{BuildHasher, RandomState};
+
+pub use crate::{instructions::MakeArgs, pricing::{effective_receive, protocol_fee}};
+
+use crate::state::{Escrow, EscrowStatus};
+
+//Always the maker that made the escrow, TransferMaker does not move the PDA
+pub fn escrow_pda(maker: &Pubkey, seed: u64) -> (Pubkey, u8) {
+    escrow_pda_with_nonce(maker, seed, 0)
+}
+
+pub fn escrow_pda_with_nonce(maker: &Pubkey, seed: u64, nonce: u64) -> (Pubkey, u8) {
+    escrow_pda_in_namespace(maker, seed, nonce, &[0; 8])
+}
+
+//Seed order is [b"escrow", namespace, maker, seed, nonce], a zero namespace or nonce adds no seed bytes
+pub fn escrow_pda_in_namespace(maker: &Pubkey, seed: u64, nonce: u64, namespace: &[u8; 8]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"escrow", Escrow::namespace_seed(namespace), maker.as_ref(), &seed.to_le_bytes(), Escrow::nonce_seed(&nonce)],
+        &crate::ID,
+    )
+}
+
+//The escrow and vault addresses a Make with this seed would create
+pub fn derive_for_seed(maker: &Pubkey, mint_a: &Pubkey, seed: u64) -> (Pubkey, Pubkey) {
+    let escrow = escrow_pda(maker, seed).0;
+
+    (escrow, vault_address(&escrow, mint_a))
+}
+
+//Not for anything that has to be unpredictable, it only spreads seeds apart
+pub fn random_seed() -> u64 {
+    RandomState::new().hash_one(std::time::SystemTime::now())
+}
+
+//Keyed by the maker that made the escrows, like escrow_pda
+pub fn maker_index_pda(maker: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"index", maker.as_ref()], &crate::ID)
+}
+
+//Created by the taker's first fill of the escrow and never closed
+pub fn receipt_pda(escrow: &Pubkey, taker: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"receipt", escrow.as_ref(), taker.as_ref()], &crate::ID)
+}
+
+//Only exists while a take is staged on the escrow
+pub fn staged_take_pda(escrow: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &crate::ID)
+}
+
+//Only exists once the admin has paused the mint
+pub fn paused_mint_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"paused", mint.as_ref()], &crate::ID)
+}
+
+pub fn config_pda() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config"], &crate::ID)
+}
+
+pub fn vault_address(escrow: &Pubkey, mint_a: &Pubkey) -> Pubkey {
+    get_associated_token_address(escrow, mint_a)
+}
+
+//First 8 bytes of every escrow account, usable as a getProgramAccounts memcmp filter at offset 0
+pub const ESCROW_DISCRIMINATOR: &[u8] = Escrow::DISCRIMINATOR;
+
+//Only checks the discriminator, load_escrow also checks the length
+pub fn is_escrow(data: &[u8]) -> bool {
+    data.starts_with(ESCROW_DISCRIMINATOR)
+}
+
+//None for a closed account, a different account type or data too short to hold an Escrow
+pub fn load_escrow(data: &[u8]) -> Option<Escrow> {
+    let body = data.strip_prefix(ESCROW_DISCRIMINATOR)?;
+    let body = body.get(..std::mem::size_of::<Escrow>())?;
+
+    Some(bytemuck::pod_read_unaligned(body))
+}
+
+//An escrow account that is gone or emptied reads as Closed
+pub fn escrow_status(data: &[u8]) -> EscrowStatus {
+    load_escrow(data).map_or(EscrowStatus::Closed, |escrow| escrow.status())
+}
+
+//A Take, Refund or any other instruction on an escrow that is already gone fails while Anchor loads the accounts,
+//before program code could return its own error, so it surfaces as AccountOwnedByWrongProgram
+//True when that error means the escrow was closed, e.g. by a taker that got there first
+pub fn is_already_closed_error(error_code: u32, escrow_data: Option<&[u8]>) -> bool {
+    error_code == u32::from(ErrorCode::AccountOwnedByWrongProgram)
+        && escrow_data.is_none_or(|data| escrow_status(data) == EscrowStatus::Closed)
+}
+
+//Whole-token amounts to base units, rounded to the nearest unit; negative amounts become 0
+pub fn to_base_units(amount: f64, decimals: u8) -> u64 {
+    (amount * 10f64.powi(decimals as i32)).round() as u64
+}
+
+//Lossy above 2^53 base units, for display only
+pub fn from_base_units(amount: u64, decimals: u8) -> f64 {
+    amount as f64 / 10f64.powi(decimals as i32)
+}
+
+//deposit and receive are stored in base units of their own mints, so comparing them raw is only meaningful
+//when both mints share decimals; this is the display rate in whole mint_b per whole mint_a
+//A zero deposit has no rate and gives infinity or NaN, for display only like from_base_units
+pub fn price_ratio(deposit: u64, deposit_decimals: u8, receive: u64, receive_decimals: u8) -> f64 {
+    from_base_units(receive, receive_decimals) / from_base_units(deposit, deposit_decimals)
+}
+
+//Make's accounts in order; a program maker signs its CPI with invoke_signed, so the metas don't change
+pub fn make_account_metas(maker: &Pubkey, mint_a: &Pubkey, mint_b: &Pubkey, seed: u64, nonce: u64, namespace: &[u8; 8]) -> Vec<AccountMeta> {
+    let escrow = escrow_pda_in_namespace(maker, seed, nonce, namespace).0;
+
+    crate::accounts::Make {
+        maker: *maker,
+        mint_a: *mint_a,
+        mint_b: *mint_b,
+        maker_ata_a: get_associated_token_address(maker, mint_a),
+        escrow,
+        vault: vault_address(&escrow, mint_a),
+        maker_index: maker_index_pda(maker).0,
+        config: config_pda().0,
+        paused_mint_a: paused_mint_pda(mint_a).0,
+        paused_mint_b: paused_mint_pda(mint_b).0,
+        associated_token_program: associated_token::ID,
+        token_program: token::ID,
+        system_program: system_program::ID,
+    }.to_account_metas(None)
+}
+
+pub fn make_ix(maker: &Pubkey, mint_a: &Pubkey, mint_b: &Pubkey, args: MakeArgs) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: make_account_metas(maker, mint_a, mint_b, args.seed, args.nonce, &args.namespace),
+        data: crate::instruction::Make { args }.data(),
+    }
+}
+
+//MakeWhole scales deposit_whole and receive_whole by the mints' decimals on chain
+pub fn make_whole_ix(maker: &Pubkey, mint_a: &Pubkey, mint_b: &Pubkey, args: MakeArgs, deposit_whole: u64, receive_whole: u64) -> Instruction {
+    Instruction {
+        program_id: crate::ID,
+        accounts: make_account_metas(maker, mint_a, mint_b, args.seed, args.nonce, &args.namespace),
+        data: crate::instruction::MakeWhole { args, deposit_whole, receive_whole }.data(),
+    }
+}
+
+//make_ix with deposit and receive in whole tokens; receive_decimals is pinned to decimals_b so a mismatch fails on chain
+#[allow(clippy::too_many_arguments)]
+pub fn make_ix_ui(maker: &Pubkey, mint_a: &Pubkey, decimals_a: u8, mint_b: &Pubkey, decimals_b: u8, deposit: f64, receive: f64, args: MakeArgs) -> Instruction {
+    make_ix(maker, mint_a, mint_b, MakeArgs {
+        deposit: to_base_units(deposit, decimals_a),
+        receive: to_base_units(receive, decimals_b),
+        receive_decimals: Some(decimals_b),
+        ..args
+    })
+}
+
+//Builds a Make on the first seed from `seed` (random_seed() if unset) that has no escrow yet
+//`exists` is asked about each candidate escrow address, e.g. |escrow| svm.get_account(escrow).is_some()
+pub struct MakeBuilder {
+    maker: Pubkey,
+    mint_a: Pubkey,
+    mint_b: Pubkey,
+    seed: Option<u64>,
+    args: MakeArgs,
+}
+
+impl MakeBuilder {
+    pub fn new(maker: &Pubkey, mint_a: &Pubkey, mint_b: &Pubkey) -> Self {
+        Self { maker: *maker, mint_a: *mint_a, mint_b: *mint_b, seed: None, args: MakeArgs::default() }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn deposit(mut self, deposit: u64) -> Self {
+        self.args.deposit = deposit;
+        self
+    }
+
+    pub fn receive(mut self, receive: u64) -> Self {
+        self.args.receive = receive;
+        self
+    }
+
+    //Every other MakeArgs field, so call it before deposit and receive; args.seed is ignored
+    pub fn args(mut self, args: MakeArgs) -> Self {
+        self.args = args;
+        self
+    }
+
+    //Returns the picked seed with the instruction so callers can take or refund it later
+    pub fn build(self, exists: impl Fn(&Pubkey) -> bool) -> (u64, Instruction) {
+        let mut seed = self.seed.unwrap_or_else(random_seed);
+        while exists(&escrow_pda_in_namespace(&self.maker, seed, self.args.nonce, &self.args.namespace).0) {
+            seed = seed.wrapping_add(1);
+        }
+
+        (seed, make_ix(&self.maker, &self.mint_a, &self.mint_b, MakeArgs { seed, ..self.args }))
+    }
+}
+
+//fee_authority is the config authority, it owns fee_vault and fee_vault_a
+//max_receive = u64::MAX opts out of the slippage check
+#[allow(clippy::too_many_arguments)]
+pub fn take_ix(taker: &Pubkey, maker: &Pubkey, mint_a: &Pubkey, mint_b: &Pubkey, seed: u64, fee_authority: &Pubkey, take_amount: u64, max_receive: u64) -> Instruction {
+    let escrow = escrow_pda(maker, seed).0;
+
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::Take {
+            taker: *taker,
+            maker: *maker,
+            mint_a: *mint_a,
+            mint_b: *mint_b,
+            taker_ata_a: get_associated_token_address(taker, mint_a),
+            taker_ata_b: get_associated_token_address(taker, mint_b),
+            maker_ata_b: get_associated_token_address(maker, mint_b),
+            escrow,
+            vault: vault_address(&escrow, mint_a),
+            mint_a2: None,
+            taker_ata_a2: None,
+            vault_a2: None,
+            royalty_ata_b: None,
+            maker_receive_account: None,
+            //Only used when the escrow unwraps SOL, harmless otherwise
+            sol_unwrap: (*mint_b == native_mint::ID).then(|| get_associated_token_address(&escrow, mint_b)),
+            //Oracle escrows need their feed here, build the instruction by hand for those
+            oracle: None,
+            maker_index: maker_index_pda(maker).0,
+            config: config_pda().0,
+            paused_mint_a: paused_mint_pda(mint_a).0,
+            paused_mint_b: paused_mint_pda(mint_b).0,
+            fee_authority: *fee_authority,
+            fee_vault: get_associated_token_address(fee_authority, mint_b),
+            fee_vault_a: get_associated_token_address(fee_authority, mint_a),
+            receipt: receipt_pda(&escrow, taker).0,
+            associated_token_program: associated_token::ID,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        }.to_account_metas(None),
+        data: crate::instruction::Take { take_amount, max_receive }.data(),
+    }
+}
+
+pub fn refund_ix(maker: &Pubkey, mint_a: &Pubkey, seed: u64) -> Instruction {
+    let mut ix = refund_ix_with_authority(maker, None, mint_a, seed);
+    //Refund declares maker as a plain account since the refund authority may sign instead
+    ix.accounts[0].is_signer = true;
+    ix
+}
+
+//Refund signed by the escrow's refund_authority rather than the maker
+pub fn refund_ix_with_authority(maker: &Pubkey, refund_authority: Option<&Pubkey>, mint_a: &Pubkey, seed: u64) -> Instruction {
+    let escrow = escrow_pda(maker, seed).0;
+
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::Refund {
+            maker: *maker,
+            refund_authority: refund_authority.copied(),
+            mint_a: *mint_a,
+            maker_ata_a: get_associated_token_address(maker, mint_a),
+            refund_destination: None,
+            escrow,
+            vault: vault_address(&escrow, mint_a),
+            mint_a2: None,
+            maker_ata_a2: None,
+            vault_a2: None,
+            staged_take: staged_take_pda(&escrow).0,
+            maker_index: maker_index_pda(maker).0,
+            config: config_pda().0,
+            associated_token_program: associated_token::ID,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        }.to_account_metas(None),
+        data: crate::instruction::Refund { close_maker_ata: false }.data(),
+    }
+}
+
+pub fn quote_ix(maker: &Pubkey, mint_a: &Pubkey, seed: u64, take_amount: u64) -> Instruction {
+    let escrow = escrow_pda(maker, seed).0;
+
+    Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::Quote {
+            mint_a: *mint_a,
+            escrow,
+            vault: vault_address(&escrow, mint_a),
+            config: config_pda().0,
+            token_program: token::ID,
+        }.to_account_metas(None),
+        data: crate::instruction::Quote { take_amount }.data(),
+    }
+}
+
+//Runtime ceiling for a whole transaction
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+//Leaves room for a partial Token-2022 take paying protocol, taker and royalty fees
+pub const DEFAULT_COMPUTE_UNITS_PER_IX: u32 = 300_000;
+
+//Prefixes `instructions` with a compute unit limit of `units_per_ix` each, capped at the transaction maximum
+pub fn with_compute_budget(instructions: &[Instruction], units_per_ix: u32) -> Vec<Instruction> {
+    let units = units_per_ix
+        .saturating_mul(instructions.len() as u32)
+        .min(MAX_COMPUTE_UNIT_LIMIT);
+
+    std::iter::once(ComputeBudgetInstruction::set_compute_unit_limit(units))
+        .chain(instructions.iter().cloned())
+        .collect()
+}
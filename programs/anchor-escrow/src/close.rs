@@ -0,0 +1,19 @@
+use anchor_lang::{prelude::*, system_program};
+
+use crate::error::EscrowError;
+
+//Shared by every instruction that closes an escrow, so they all leave the account in the same state
+//Same steps as the close constraint: move the rent, wipe the data and hand the account back to the system program
+pub fn close_escrow<'info>(escrow: &AccountInfo<'info>, rent_receiver: &AccountInfo<'info>) -> Result<()> {
+    let rent = escrow.lamports();
+    let received = rent_receiver.lamports().checked_add(rent).ok_or(EscrowError::MathOverflow)?;
+    **rent_receiver.try_borrow_mut_lamports()? = received;
+    **escrow.try_borrow_mut_lamports()? = 0;
+
+    escrow.try_borrow_mut_data()?.fill(0);
+    escrow.assign(&system_program::ID);
+
+    escrow.realloc(0, false)?;
+
+    Ok(())
+}
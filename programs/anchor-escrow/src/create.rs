@@ -0,0 +1,45 @@
+use anchor_lang::{prelude::*, system_program::{self, Allocate, Assign, CreateAccount, Transfer}};
+
+use crate::error::EscrowError;
+
+//Shared by the batch instructions that create PDAs from remaining_accounts, where no init constraint can run
+//Same steps as the init constraint, including an address someone already sent lamports to
+pub fn create_program_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    space: usize,
+    signer_seeds: &[&[u8]],
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let required = Rent::get()?.minimum_balance(space);
+    require_keys_eq!(*account.owner, system_program::ID, EscrowError::InvalidRemainingAccounts);
+
+    let signer_seeds = [signer_seeds];
+
+    if account.lamports() == 0 {
+        let cpi_accounts = CreateAccount {
+            from: payer.clone(),
+            to: account.clone(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, &signer_seeds);
+
+        return system_program::create_account(cpi_context, required, space as u64, &crate::ID);
+    }
+
+    let shortfall = required.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        let cpi_accounts = Transfer {
+            from: payer.clone(),
+            to: account.clone(),
+        };
+
+        system_program::transfer(CpiContext::new(system_program.clone(), cpi_accounts), shortfall)?;
+    }
+
+    let cpi_accounts = Allocate { account_to_allocate: account.clone() };
+    system_program::allocate(CpiContext::new_with_signer(system_program.clone(), cpi_accounts, &signer_seeds), space as u64)?;
+
+    let cpi_accounts = Assign { account_to_assign: account.clone() };
+    system_program::assign(CpiContext::new_with_signer(system_program.clone(), cpi_accounts, &signer_seeds), &crate::ID)
+}
@@ -0,0 +1,127 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum EscrowError {
+    #[msg("The escrow has expired")]
+    EscrowExpired,
+    #[msg("The escrow has not expired yet")]
+    EscrowNotExpired,
+    #[msg("Amount must be greater than zero")]
+    InvalidAmount,
+    #[msg("Take amount exceeds the remaining vault balance")]
+    PartialFillTooLarge,
+    #[msg("This escrow can only be taken by its allowed taker")]
+    UnauthorizedTaker,
+    #[msg("Fee exceeds the maximum of 1000 bps")]
+    FeeTooHigh,
+    #[msg("This escrow does not receive native SOL")]
+    NotNativeMint,
+    #[msg("mint_a and mint_b must be different")]
+    SameMint,
+    #[msg("The offer changed since the take was staged")]
+    StagedAmountMismatch,
+    #[msg("This escrow has no arbiter")]
+    NoArbiter,
+    #[msg("Signer is not the arbiter of this escrow")]
+    UnauthorizedArbiter,
+    #[msg("The program is paused")]
+    ProgramPaused,
+    #[msg("Dutch auction needs a future decay_end and a floor between 1 and receive")]
+    InvalidAuction,
+    #[msg("remaining_accounts must hold whole groups of accounts for each escrow")]
+    InvalidRemainingAccounts,
+    #[msg("The vault holds less than the escrow's recorded deposit")]
+    InsufficientVaultBalance,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Maker does not match the escrow")]
+    InvalidMaker,
+    #[msg("Mint does not match the escrow")]
+    InvalidMint,
+    #[msg("mint_b decimals do not match the escrow's receive_decimals")]
+    DecimalsMismatch,
+    #[msg("A staged take exists, use CancelEscrow with its accounts to refund both sides")]
+    StagedTakeActive,
+    #[msg("Basket escrows need mint_a2, the second vault and its token account")]
+    BasketAccountsMissing,
+    #[msg("Basket escrows can only be taken in full")]
+    BasketPartialFill,
+    #[msg("This instruction does not support basket escrows")]
+    BasketNotSupported,
+    #[msg("Partial take is smaller than the escrow's min_fill")]
+    FillTooSmall,
+    #[msg("This escrow can only be taken in full")]
+    PartialFillNotAllowed,
+    #[msg("mint_b is not on the config's receive mint allowlist")]
+    MintNotAllowed,
+    #[msg("The receive mint allowlist is full")]
+    AllowlistFull,
+    #[msg("Mint is already on the receive mint allowlist")]
+    MintAlreadyAllowed,
+    #[msg("Escrows can only be merged with the same maker, mints, pricing and terms")]
+    MergeMismatch,
+    #[msg("Token account has the wrong owner or mint")]
+    InvalidTokenAccount,
+    #[msg("Escrow account is not a layout Migrate can upgrade")]
+    UnsupportedVersion,
+    #[msg("Take would cost more than the taker's max_receive")]
+    SlippageExceeded,
+    #[msg("Maker already has the maximum number of open escrows")]
+    TooManyOpenEscrows,
+    #[msg("Token account did not receive the transferred amount")]
+    TransferVerificationFailed,
+    #[msg("Escrow can't be refunded until its refund cooldown has passed")]
+    CooldownActive,
+    #[msg("Royalty needs a recipient and can't exceed 100%")]
+    InvalidRoyalty,
+    #[msg("Escrows with a royalty can only be taken through Take")]
+    RoyaltyNotSupported,
+    #[msg("Escrow has a royalty but no royalty_ata_b was passed")]
+    RoyaltyAccountMissing,
+    #[msg("Staged take is past its take deadline")]
+    TakeDeadlinePassed,
+    #[msg("Only the taker can cancel a stage before its take deadline")]
+    TakeDeadlineActive,
+    #[msg("Deposit mint has a freeze authority")]
+    FreezableMint,
+    #[msg("Mint is paused")]
+    MintPaused,
+    #[msg("The fee tier list is full")]
+    FeeTiersFull,
+    #[msg("Mint has no fee tier")]
+    FeeTierNotFound,
+    #[msg("Tokens of the escrow's deposit mints can't be rescued")]
+    RescueDepositMint,
+    #[msg("Maker can't cover the rent for the escrow, vault and index")]
+    InsufficientRent,
+    #[msg("Config authority can't be the default pubkey")]
+    InvalidAuthority,
+    #[msg("Escrows with a taker tip can only be taken with Take")]
+    TipNotSupported,
+    #[msg("Expiry is further out than the config's max lifetime")]
+    LifetimeTooLong,
+    #[msg("Max lifetime can't be negative")]
+    InvalidLifetime,
+    #[msg("Escrow unwraps SOL but the sol_unwrap account is missing or wrong")]
+    UnwrapAccountMissing,
+    #[msg("Escrows with a settlement hook can only be taken with Take")]
+    SettlementHookNotSupported,
+    #[msg("The settlement hook program must be the first remaining account")]
+    SettlementHookMissing,
+    #[msg("max_open_per_maker can't exceed what a maker index holds")]
+    InvalidOpenEscrowLimit,
+    #[msg("Oracle escrows need a max staleness, bounds in order and no auction")]
+    InvalidOracle,
+    #[msg("The oracle price is older than the escrow allows")]
+    OracleStale,
+    #[msg("The oracle price is outside the escrow's bounds")]
+    OraclePriceOutOfBounds,
+    #[msg("Escrows priced by an oracle can only be taken with Take")]
+    OracleNotSupported,
+    #[msg("Escrow is priced by an oracle but the oracle account is missing")]
+    OracleAccountMissing,
+    #[msg("Refund must be signed by the maker or the escrow's refund authority")]
+    RefundNotAuthorized,
+    #[msg("Escrow has a staged take waiting to settle")]
+    EscrowStaged,
+}
@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct EscrowMade {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub deposit: u64,
+    pub receive: u64,
+    pub seed: u64,
+}
+
+#[event]
+pub struct EscrowTaken {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub deposit: u64, //mint_a released to the taker
+    pub receive: u64, //mint_b paid by the taker, including the fee
+    pub fee: u64, //portion of receive routed to the fee vault
+    pub taker_fee: u64, //portion of deposit withheld from the taker, only charged by Take
+    pub seed: u64,
+}
+
+//Emitted alongside EscrowTaken when a take leaves the escrow open
+#[event]
+pub struct EscrowPartiallyFilled {
+    pub escrow: Pubkey,
+    pub taker: Pubkey,
+    pub filled: u64, //mint_a taken out of the vault by this take
+    pub remaining_deposit: u64, //vault balance left for later takes
+    pub remaining_receive: u64, //mint_b the maker still expects for the rest
+    pub seed: u64,
+}
+
+#[event]
+pub struct EscrowRefunded {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub deposit: u64, //mint_a returned to the maker
+    pub receive: u64,
+    pub seed: u64,
+}
+
+//Same amounts a Take of `deposit` would settle at the quoted slot
+#[event]
+pub struct EscrowQuote {
+    pub escrow: Pubkey,
+    pub deposit: u64, //mint_a the take would release, before the taker fee
+    pub receive: u64, //mint_b the taker would pay, including the fee
+    pub fee: u64,
+    pub taker_fee: u64,
+}
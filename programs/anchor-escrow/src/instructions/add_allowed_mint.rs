@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Config};
+
+#[derive(Accounts)]
+pub struct AddAllowedMint<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> AddAllowedMint<'info> {
+    pub fn add_allowed_mint(&mut self, mint: Pubkey) -> Result<()> {
+        let allowed = &mut self.config.allowed_receive_mints;
+
+        require!(!allowed.contains(&mint), EscrowError::MintAlreadyAllowed);
+        require!(allowed.len() < Config::MAX_ALLOWED_MINTS, EscrowError::AllowlistFull);
+
+        allowed.push(mint);
+
+        Ok(())
+    }
+}
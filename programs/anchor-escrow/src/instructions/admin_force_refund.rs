@@ -0,0 +1,134 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked, CloseAccount, close_account};
+
+use crate::{close::close_escrow, error::EscrowError, events::EscrowRefunded, state::{Config, Escrow, MakerIndex}};
+
+//Compliance takedown: the config authority sends the vault back to the maker regardless of expiry or cooldown
+//The vault and escrow rent go to the maker as in a normal refund
+#[derive(Accounts)]
+pub struct AdminForceRefund<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    maker: SystemAccount<'info>,
+    #[account(mint::token_program = token_program)]
+    mint_a: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        has_one = mint_a @ EscrowError::InvalidMint,
+        has_one = maker @ EscrowError::InvalidMaker,
+        constraint = !escrow.load()?.is_basket() @ EscrowError::BasketNotSupported,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    vault: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: address is pinned by seeds, it only has to be empty
+    #[account(
+        seeds = [b"stage", escrow.key().as_ref()],
+        bump,
+    )]
+    staged_take: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"index", escrow.load()?.maker.as_ref()],
+        bump = maker_index.bump,
+    )]
+    maker_index: Account<'info, MakerIndex>,
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    config: Account<'info, Config>,
+    token_program: Interface<'info, TokenInterface>,
+    system_program: Program<'info, System>,
+}
+
+impl<'info> AdminForceRefund<'info> {
+    //A staged taker's mint_b would be stranded, CancelEscrow is the way out for those
+    pub fn check_no_stage(&self) -> Result<()> {
+        require!(self.staged_take.data_is_empty(), EscrowError::StagedTakeActive);
+
+        Ok(())
+    }
+
+    pub fn refund_and_close_vault(&mut self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            to: self.maker_ata_a.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, self.vault.amount, self.mint_a.decimals)?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.maker.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)
+    }
+
+    pub fn emit_refunded(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        emit!(EscrowRefunded {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: escrow.mint_b,
+            deposit: self.vault.amount,
+            receive: escrow.receive,
+            seed: escrow.seed,
+        });
+
+        Ok(())
+    }
+
+    pub fn unindex(&mut self) {
+        self.maker_index.remove(&self.escrow.key());
+    }
+
+    pub fn record_refund(&mut self) -> Result<()> {
+        self.config.record_refund()
+    }
+
+    pub fn close_escrow(&self) -> Result<()> {
+        close_escrow(&self.escrow.to_account_info(), &self.maker.to_account_info())
+    }
+}
@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{error::EscrowError, state::{Config, Escrow}};
+
+//Sweeps tokens someone sent to the escrow PDA by mistake
+//Accounts holding the escrow's own deposit mints can't be touched, so the vaults stay with the maker
+#[derive(Accounts)]
+pub struct AdminRescue<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        mint::token_program = token_program,
+        constraint = stray_mint.key() != escrow.load()?.mint_a @ EscrowError::RescueDepositMint,
+        constraint = !escrow.load()?.is_basket() || stray_mint.key() != escrow.load()?.mint_a2 @ EscrowError::RescueDepositMint,
+    )]
+    pub stray_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        token::mint = stray_mint,
+        token::authority = escrow,
+        token::token_program = token_program,
+    )]
+    pub stray_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = stray_mint,
+        token::token_program = token_program,
+    )]
+    pub recipient: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> AdminRescue<'info> {
+    pub fn rescue(&mut self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.stray_account.to_account_info(),
+            to: self.recipient.to_account_info(),
+            mint: self.stray_mint.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, self.stray_account.amount, self.stray_mint.decimals)
+    }
+}
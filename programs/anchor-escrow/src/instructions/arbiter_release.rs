@@ -0,0 +1,253 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, CloseAccount, close_account}};
+
+use crate::{close::close_escrow, error::EscrowError, events::{EscrowRefunded, EscrowTaken}, state::{Config, Escrow, MakerIndex, StagedTake}};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArbiterDecision {
+    Settle, //swap as if the taker had settled
+    Refund, //return mint_a to the maker and the staged mint_b to the taker
+}
+
+//Disputes only exist once a taker has committed funds, so the arbiter acts on a staged take
+#[derive(Accounts)]
+pub struct ArbiterRelease<'info> {
+    #[account(mut)]
+    pub arbiter: Signer<'info>,
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+    #[account(mut)]
+    pub taker: SystemAccount<'info>,
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+    )]
+    pub maker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = arbiter,
+        associated_token::mint = mint_b,
+        associated_token::authority = maker,
+    )]
+    pub maker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = arbiter,
+        associated_token::mint = mint_a,
+        associated_token::authority = taker,
+    )]
+    pub taker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = taker,
+    )]
+    pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = mint_a @ EscrowError::InvalidMint,
+        has_one = mint_b @ EscrowError::InvalidMint,
+        constraint = escrow.load()?.arbiter != Pubkey::default() @ EscrowError::NoArbiter,
+        constraint = escrow.load()?.arbiter == arbiter.key() @ EscrowError::UnauthorizedArbiter,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"index", maker.key().as_ref()],
+        bump = maker_index.bump,
+    )]
+    pub maker_index: Box<Account<'info, MakerIndex>>,
+    #[account(
+        mut,
+        close = taker,
+        has_one = taker @ EscrowError::UnauthorizedTaker,
+        has_one = escrow,
+        seeds = [b"stage", escrow.key().as_ref()],
+        bump = staged_take.bump,
+    )]
+    pub staged_take: Box<Account<'info, StagedTake>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = staged_take,
+    )]
+    pub take_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    #[account(address = config.authority)]
+    pub fee_authority: SystemAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = arbiter,
+        associated_token::mint = mint_b,
+        associated_token::authority = fee_authority,
+    )]
+    pub fee_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ArbiterRelease<'info> {
+    pub fn force_settle(&mut self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        require!(!self.config.paused, EscrowError::ProgramPaused);
+
+        let fee = self.config.fee_for(&self.mint_b.key(), self.staged_take.amount)?;
+
+        self.transfer_from_take_vault(self.maker_ata_b.to_account_info(), self.staged_take.amount.checked_sub(fee).ok_or(EscrowError::MathOverflow)?)?;
+        if fee > 0 {
+            self.transfer_from_take_vault(self.fee_vault.to_account_info(), fee)?;
+        }
+        self.close_take_vault()?;
+
+        self.transfer_from_vault(self.taker_ata_a.to_account_info(), self.vault.amount)?;
+        self.close_vault()?;
+
+        emit!(EscrowTaken {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            taker: self.taker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: self.mint_b.key(),
+            deposit: self.vault.amount,
+            receive: self.staged_take.amount,
+            fee,
+            taker_fee: 0,
+            seed: escrow.seed,
+        });
+
+        Ok(())
+    }
+
+    pub fn force_refund(&mut self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        self.transfer_from_take_vault(self.taker_ata_b.to_account_info(), self.take_vault.amount)?;
+        self.close_take_vault()?;
+
+        self.transfer_from_vault(self.maker_ata_a.to_account_info(), self.vault.amount)?;
+        self.close_vault()?;
+
+        emit!(EscrowRefunded {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: self.mint_b.key(),
+            deposit: self.vault.amount,
+            receive: escrow.receive,
+            seed: escrow.seed,
+        });
+
+        Ok(())
+    }
+
+    pub fn unindex(&mut self) {
+        self.maker_index.remove(&self.escrow.key());
+    }
+
+    pub fn close_escrow(&self) -> Result<()> {
+        close_escrow(&self.escrow.to_account_info(), &self.maker.to_account_info())
+    }
+
+    fn transfer_from_vault(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            to,
+            authority: self.escrow.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, amount, self.mint_a.decimals)
+    }
+
+    fn close_vault(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.maker.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)
+    }
+
+    fn transfer_from_take_vault(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"stage",
+            self.staged_take.escrow.as_ref(),
+            &[self.staged_take.bump]
+        ]];
+
+        let cpi_accounts = TransferChecked {
+            from: self.take_vault.to_account_info(),
+            to,
+            authority: self.staged_take.to_account_info(),
+            mint: self.mint_b.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, amount, self.mint_b.decimals)
+    }
+
+    fn close_take_vault(&self) -> Result<()> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"stage",
+            self.staged_take.escrow.as_ref(),
+            &[self.staged_take.bump]
+        ]];
+
+        let cpi_accounts = CloseAccount {
+            account: self.take_vault.to_account_info(),
+            destination: self.taker.to_account_info(),
+            authority: self.staged_take.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,211 @@
+use anchor_lang::{prelude::*, system_program};
+use anchor_spl::{associated_token::get_associated_token_address_with_program_id, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, CloseAccount, close_account}};
+
+use crate::{close::close_escrow, error::EscrowError, events::EscrowRefunded, state::{Config, Escrow, MakerIndex, StagedTake}};
+
+//staged_take is always passed so a pending stage can't be skipped by leaving it out
+//The taker side accounts are only needed when that stage exists
+#[derive(Accounts)]
+pub struct CancelEscrow<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(mint::token_program = token_program)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        has_one = mint_a @ EscrowError::InvalidMint,
+        has_one = maker @ EscrowError::InvalidMaker,
+        constraint = !escrow.load()?.is_basket() @ EscrowError::BasketNotSupported,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"index", maker.key().as_ref()],
+        bump = maker_index.bump,
+    )]
+    pub maker_index: Box<Account<'info, MakerIndex>>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    /// CHECK: address is pinned by seeds, the contents are only trusted after deserializing
+    #[account(
+        mut,
+        seeds = [b"stage", escrow.key().as_ref()],
+        bump,
+    )]
+    pub staged_take: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub taker: Option<SystemAccount<'info>>,
+    pub mint_b: Option<Box<InterfaceAccount<'info, Mint>>>,
+    #[account(mut)]
+    pub taker_ata_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(mut)]
+    pub take_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+//Return the staged mint_b and both stage rents to the taker
+//Return the vault to the maker, then close the escrow
+impl<'info> CancelEscrow<'info> {
+    //Cancelling is a refund too, so it waits out the same cooldown
+    pub fn check_cooldown(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(escrow.refund_unlocked(now), EscrowError::CooldownActive);
+
+        Ok(())
+    }
+
+    pub fn refund_staged_take(&mut self) -> Result<()> {
+        if self.staged_take.data_is_empty() {
+            return Ok(());
+        }
+
+        let (Some(taker), Some(mint_b), Some(taker_ata_b), Some(take_vault)) =
+            (&self.taker, &self.mint_b, &self.taker_ata_b, &self.take_vault) else {
+            return err!(EscrowError::StagedTakeActive);
+        };
+
+        require_keys_eq!(*self.staged_take.owner, crate::ID, EscrowError::StagedTakeActive);
+        let staged_take = StagedTake::try_deserialize(&mut &self.staged_take.try_borrow_data()?[..])?;
+
+        let escrow = *self.escrow.load()?;
+        require_keys_eq!(staged_take.escrow, self.escrow.key(), EscrowError::StagedTakeActive);
+        require_keys_eq!(staged_take.taker, taker.key(), EscrowError::StagedTakeActive);
+        require_keys_eq!(escrow.mint_b, mint_b.key(), EscrowError::InvalidMint);
+        require_keys_eq!(taker_ata_b.owner, taker.key(), EscrowError::StagedTakeActive);
+        require_keys_eq!(taker_ata_b.mint, mint_b.key(), EscrowError::InvalidMint);
+        require_keys_eq!(
+            take_vault.key(),
+            get_associated_token_address_with_program_id(&self.staged_take.key(), &mint_b.key(), &self.token_program.key()),
+            EscrowError::StagedTakeActive
+        );
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"stage",
+            staged_take.escrow.as_ref(),
+            &[staged_take.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: take_vault.to_account_info(),
+            to: taker_ata_b.to_account_info(),
+            authority: self.staged_take.to_account_info(),
+            mint: mint_b.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, take_vault.amount, mint_b.decimals)?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: take_vault.to_account_info(),
+            destination: taker.to_account_info(),
+            authority: self.staged_take.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)?;
+
+        //Same steps as the close constraint, done by hand since staged_take is unchecked
+        let stage_info = self.staged_take.to_account_info();
+        **taker.to_account_info().try_borrow_mut_lamports()? += stage_info.lamports();
+        **stage_info.try_borrow_mut_lamports()? = 0;
+        stage_info.assign(&system_program::ID);
+        stage_info.realloc(0, false)?;
+
+        Ok(())
+    }
+
+    pub fn refund_and_close_vault(&mut self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            to: self.maker_ata_a.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, self.vault.amount, self.mint_a.decimals)?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.maker.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)
+    }
+
+    pub fn emit_refunded(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        emit!(EscrowRefunded {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: escrow.mint_b,
+            deposit: self.vault.amount,
+            receive: escrow.receive,
+            seed: escrow.seed,
+        });
+
+        Ok(())
+    }
+
+    pub fn unindex(&mut self) {
+        self.maker_index.remove(&self.escrow.key());
+    }
+
+    pub fn record_refund(&mut self) -> Result<()> {
+        self.config.record_refund()
+    }
+
+    pub fn close_escrow(&self) -> Result<()> {
+        close_escrow(&self.escrow.to_account_info(), &self.maker.to_account_info())
+    }
+}
@@ -0,0 +1,106 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked, CloseAccount, close_account};
+
+use crate::{error::EscrowError, state::{Escrow, StagedTake}};
+
+//The escrow may already be gone (refunded or taken), so the stage is
+//located through the escrow key it stored rather than the escrow account
+//Anyone can cancel once the take deadline has passed; funds and rent still go back to the taker
+#[derive(Accounts)]
+pub struct CancelStage<'info> {
+    pub caller: Signer<'info>,
+    #[account(mut)]
+    pub taker: SystemAccount<'info>,
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = taker,
+    )]
+    pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        close = taker,
+        has_one = taker @ EscrowError::UnauthorizedTaker,
+        seeds = [b"stage", staged_take.escrow.as_ref()],
+        bump = staged_take.bump,
+    )]
+    pub staged_take: Box<Account<'info, StagedTake>>,
+    /// CHECK: pinned to the stage's escrow, only written back while it is still an Escrow
+    #[account(
+        mut,
+        address = staged_take.escrow,
+    )]
+    pub escrow: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = staged_take,
+    )]
+    pub take_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CancelStage<'info> {
+    pub fn check_caller(&self) -> Result<()> {
+        if self.caller.key() == self.taker.key() {
+            return Ok(());
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(self.staged_take.is_past_deadline(now), EscrowError::TakeDeadlineActive);
+
+        Ok(())
+    }
+
+    //Puts the escrow's status back to what it was before the stage, if the escrow is still open
+    pub fn unstage_escrow(&mut self) -> Result<()> {
+        if *self.escrow.owner != crate::ID {
+            return Ok(());
+        }
+
+        let mut data = self.escrow.try_borrow_mut_data()?;
+        let len = 8 + std::mem::size_of::<Escrow>();
+        if data.len() < len || !data.starts_with(Escrow::DISCRIMINATOR) {
+            return Ok(());
+        }
+
+        bytemuck::from_bytes_mut::<Escrow>(&mut data[8..len]).record_unstage();
+
+        Ok(())
+    }
+
+    pub fn return_and_close_take_vault(&mut self) -> Result<()> {
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"stage",
+            self.staged_take.escrow.as_ref(),
+            &[self.staged_take.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.take_vault.to_account_info(),
+            to: self.taker_ata_b.to_account_info(),
+            mint: self.mint_b.to_account_info(),
+            authority: self.staged_take.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, self.take_vault.amount, self.mint_b.decimals)?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: self.take_vault.to_account_info(),
+            destination: self.taker.to_account_info(),
+            authority: self.staged_take.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,27 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Config};
+
+//The mint goes back to paying config.fee_bps
+#[derive(Accounts)]
+pub struct ClearFeeTier<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> ClearFeeTier<'info> {
+    pub fn clear_fee_tier(&mut self, mint: Pubkey) -> Result<()> {
+        let tiers = &mut self.config.fee_tiers;
+
+        let index = tiers.iter().position(|tier| tier.mint == mint).ok_or(EscrowError::FeeTierNotFound)?;
+        tiers.remove(index);
+
+        Ok(())
+    }
+}
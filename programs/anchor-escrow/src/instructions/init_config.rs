@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Config};
+
+#[derive(Accounts)]
+pub struct InitConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"config"],
+        bump,
+        space = 8 + Config::INIT_SPACE,
+    )]
+    pub config: Account<'info, Config>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitConfig<'info> {
+    pub fn init_config(&mut self, fee_bps: u16, bumps: &InitConfigBumps) -> Result<()> {
+        require!(fee_bps <= Config::MAX_FEE_BPS, EscrowError::FeeTooHigh);
+
+        self.config.set_inner(Config {
+            authority: self.authority.key(),
+            fee_bps,
+            taker_fee_bps: 0,
+            cranker_bps: 0,
+            paused: false,
+            reject_freezable_mints: false,
+            max_lifetime: 0,
+            max_open_per_maker: 0,
+            total_makes: 0,
+            total_takes: 0,
+            total_refunds: 0,
+            allowed_receive_mints: Vec::new(),
+            fee_tiers: Vec::new(),
+            bump: bumps.config,
+        });
+
+        Ok(())
+    }
+}
\ No newline at end of file
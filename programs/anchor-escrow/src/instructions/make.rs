@@ -1,58 +1,267 @@
 use anchor_lang::prelude::*;
-use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}};
+use anchor_spl::{associated_token::AssociatedToken, token::spl_token::native_mint, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}};
 
-use crate::state::Escrow;
+use crate::{error::EscrowError, events::EscrowMade, pricing::whole_to_base_units, state::{Config, Escrow, EscrowStatus, MakerIndex, PausedMint, RoundingMode}, transfer::received_since};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct MakeArgs {
+    pub seed: u64,
+    pub deposit: u64,
+    pub receive: u64, //starting price when the escrow is a dutch auction
+    pub expiry: i64, //unix timestamp, 0 = never expires
+    pub allowed_taker: Pubkey, //Pubkey::default() = anyone can take
+    pub arbiter: Pubkey, //Pubkey::default() = no arbiter
+    pub floor_receive: u64, //dutch auction floor, ignored when decay_end is 0
+    pub decay_end: i64, //unix timestamp, 0 = fixed price
+    pub receive_decimals: Option<u8>, //decimals receive is quoted in, None = trust mint_b
+    pub memo: [u8; 32], //order id or other maker reference, zero padded
+    pub min_fill: u64, //smallest partial take, 0 = any size
+    pub all_or_nothing: bool, //reject partial takes entirely
+    pub refund_cooldown: i64, //seconds before Refund is allowed, 0 = any time
+    pub nonce: u64, //extra PDA seed after seed, 0 = the address from seed alone
+    pub royalty_recipient: Pubkey, //gets royalty_bps of the maker's proceeds, even after TransferMaker
+    pub royalty_bps: u16, //0 = no royalty
+    pub namespace: [u8; 8], //escrow book this escrow belongs to, all zero = the default book
+    pub accepted_receive_mints: [Pubkey; 4], //other mints a taker may pay in instead of mint_b, Pubkey::default() = unused
+    pub taker_tip: u64, //extra mint_a paid to takers on top of deposit, 0 = no tip
+    pub unwrap_sol: bool, //wSOL mint_b only: Take pays the maker native lamports instead
+    pub settlement_hook: Pubkey, //program Take calls with the fill details, Pubkey::default() = no hook
+    pub oracle: Pubkey, //OraclePrice feed Take charges from instead of receive, Pubkey::default() = fixed terms
+    pub oracle_max_staleness: i64, //seconds a feed update stays usable, must be set with an oracle
+    pub oracle_min_price: u64, //bounds on the feed price, in its own units
+    pub oracle_max_price: u64, //0 = no ceiling
+    pub refund_authority: Pubkey, //may refund in the maker's place, e.g. a treasury, Pubkey::default() = the maker alone
+    pub rounding: RoundingMode, //which way partial fills round the receive
+}
+
+impl MakeArgs {
+    //The tip is funded up front, so the vault starts with both
+    pub fn vault_amount(&self) -> Result<u64> {
+        self.deposit.checked_add(self.taker_tip).ok_or(error!(EscrowError::MathOverflow))
+    }
+
+    //Takers are owed the tip in full, so a transfer fee on the way in comes out of the deposit
+    pub fn with_received(self, received: u64) -> Result<MakeArgs> {
+        let deposit = received.checked_sub(self.taker_tip).filter(|deposit| *deposit > 0).ok_or(EscrowError::InvalidAmount)?;
+
+        Ok(MakeArgs {
+            deposit,
+            min_fill: self.min_fill.min(deposit),
+            ..self
+        })
+    }
+}
+
+//Anchor evaluates the escrow's space before it creates any account, so the rent check lives there
+//A maker short on lamports gets InsufficientRent instead of a system program error from create_account
+//Token-2022 vaults with extensions are larger than TokenAccount::LEN, so that part is a lower bound
+fn funded_escrow_space(maker: &AccountInfo, escrow: &AccountInfo, vault: &AccountInfo, maker_index: &AccountInfo) -> Result<usize> {
+    let rent = Rent::get()?;
+    let space = 8 + Escrow::INIT_SPACE;
+
+    //Accounts that already exist only need topping up to rent exemption
+    let shortfall = |account: &AccountInfo, space: usize| rent.minimum_balance(space).saturating_sub(account.lamports());
+
+    let required = shortfall(escrow, space)
+        .checked_add(shortfall(vault, anchor_spl::token::TokenAccount::LEN))
+        .and_then(|total| total.checked_add(shortfall(maker_index, 8 + MakerIndex::INIT_SPACE)))
+        .ok_or(EscrowError::MathOverflow)?;
+    require!(maker.lamports() >= required, EscrowError::InsufficientRent);
+
+    Ok(space)
+}
 
 #[derive(Accounts)]
-#[instruction(seed: u64)]
+#[instruction(args: MakeArgs)]
 pub struct Make<'info> {
     #[account(mut)]
     pub maker: Signer<'info>,
+    #[account(mint::token_program = token_program)]
     pub mint_a: InterfaceAccount<'info, Mint>,
+    #[account(
+        mint::token_program = token_program,
+        constraint = mint_b.key() != mint_a.key() @ EscrowError::SameMint,
+    )]
     pub mint_b: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
         associated_token::mint = mint_a,
         associated_token::authority = maker,
+        associated_token::token_program = token_program,
     )]
     pub maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    //A bare `bump` makes Anchor find the canonical bump itself, so an address derived with any other bump
+    //fails the seeds check and each (namespace, maker, seed, nonce) has exactly one escrow address
     #[account(
         init,
         payer = maker,
-        seeds = [b"escrow", maker.key().as_ref(), seed.to_le_bytes().as_ref()],
+        seeds = [b"escrow", Escrow::namespace_seed(&args.namespace), maker.key().as_ref(), args.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&args.nonce)],
         bump,
-        space = 8 + Escrow::INIT_SPACE,
+        space = funded_escrow_space(&maker, escrow, vault, maker_index)?,
     )]
-    pub escrow: Account<'info, Escrow>,
+    pub escrow: AccountLoader<'info, Escrow>,
+    //Anyone can create the escrow's ATA ahead of time, so the vault may already exist
     #[account(
-        init,
+        init_if_needed,
         payer = maker,
         associated_token::mint = mint_a,
         associated_token::authority = escrow,
+        associated_token::token_program = token_program,
     )]
     pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = maker,
+        seeds = [b"index", maker.key().as_ref()],
+        bump,
+        space = 8 + MakerIndex::INIT_SPACE,
+    )]
+    pub maker_index: Account<'info, MakerIndex>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ EscrowError::ProgramPaused,
+        constraint = config.allows_receive_mint(&mint_b.key()) @ EscrowError::MintNotAllowed,
+        constraint = config.allows_deposit_mint(&mint_a.freeze_authority) @ EscrowError::FreezableMint,
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: address is pinned by seeds, only read through PausedMint::is_paused
+    #[account(
+        seeds = [b"paused", mint_a.key().as_ref()],
+        bump,
+        constraint = !PausedMint::is_paused(&paused_mint_a) @ EscrowError::MintPaused,
+    )]
+    pub paused_mint_a: UncheckedAccount<'info>,
+    /// CHECK: address is pinned by seeds, only read through PausedMint::is_paused
+    #[account(
+        seeds = [b"paused", mint_b.key().as_ref()],
+        bump,
+        constraint = !PausedMint::is_paused(&paused_mint_b) @ EscrowError::MintPaused,
+    )]
+    pub paused_mint_b: UncheckedAccount<'info>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Make<'info> {
-    pub fn init_escrow(&mut self, seed: u64, receive: u64, bumps: &MakeBumps) -> Result<()> {
+    #[cfg(feature = "debug")]
+    pub fn log_pdas(&self) {
+        msg!("escrow: {}, vault: {}", self.escrow.key(), self.vault.key());
+    }
+
+    pub fn validate(&self, args: &MakeArgs) -> Result<()> {
+        require!(args.deposit > 0, EscrowError::InvalidAmount);
+        require!(args.receive > 0, EscrowError::InvalidAmount);
+        require!(args.min_fill <= args.deposit, EscrowError::InvalidAmount);
+        require!(args.refund_cooldown >= 0, EscrowError::InvalidAmount);
+        require!(args.royalty_bps <= 10_000, EscrowError::InvalidRoyalty);
+        require!(args.royalty_bps == 0 || args.royalty_recipient != Pubkey::default(), EscrowError::InvalidRoyalty);
+        args.vault_amount()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(self.config.allows_expiry(args.expiry, now), EscrowError::LifetimeTooLong);
+
+        //Same rules as mint_b itself
+        for mint in args.accepted_receive_mints.iter().filter(|mint| **mint != Pubkey::default()) {
+            require_keys_neq!(*mint, self.mint_a.key(), EscrowError::SameMint);
+            require!(self.config.allows_receive_mint(mint), EscrowError::MintNotAllowed);
+        }
+
+        require!(!args.unwrap_sol || self.mint_b.key() == native_mint::ID, EscrowError::NotNativeMint);
+
+        //The feed replaces the price curve, so an oracle escrow can't also be an auction
+        if args.oracle != Pubkey::default() {
+            require!(args.oracle_max_staleness > 0, EscrowError::InvalidOracle);
+            require!(args.decay_end == 0, EscrowError::InvalidOracle);
+            require!(args.oracle_max_price == 0 || args.oracle_min_price <= args.oracle_max_price, EscrowError::InvalidOracle);
+        }
+
+        if let Some(receive_decimals) = args.receive_decimals {
+            require!(receive_decimals == self.mint_b.decimals, EscrowError::DecimalsMismatch);
+        }
+
+        if args.decay_end != 0 {
+            let now = Clock::get()?.unix_timestamp;
+            require!(args.decay_end > now, EscrowError::InvalidAuction);
+            require!(args.floor_receive > 0, EscrowError::InvalidAuction);
+            require!(args.floor_receive <= args.receive, EscrowError::InvalidAuction);
+        }
+
+        Ok(())
+    }
+
+    //MakeWhole's amounts are whole tokens, the escrow still stores base units
+    //Only deposit and receive are scaled, the rest of args is taken as given
+    pub fn scale_whole(&self, args: MakeArgs, deposit_whole: u64, receive_whole: u64) -> Result<MakeArgs> {
+        Ok(MakeArgs {
+            deposit: whole_to_base_units(deposit_whole, self.mint_a.decimals)?,
+            receive: whole_to_base_units(receive_whole, self.mint_b.decimals)?,
+            ..args
+        })
+    }
+
+    pub fn init_escrow(&mut self, args: &MakeArgs, bumps: &MakeBumps) -> Result<()> {
         let clock = Clock::get()?;
-        self.escrow.set_inner(Escrow {
-            seed,
+        *self.escrow.load_init()? = Escrow {
+            version: Escrow::CURRENT_VERSION,
+            _version_padding: [0; 7],
+            seed: args.seed,
             maker: self.maker.key(),
+            seed_maker: self.maker.key(),
             mint_a: self.mint_a.key(),
             mint_b: self.mint_b.key(),
-            receive,
+            receive: args.receive,
+            deposit: args.deposit,
             created_at: clock.unix_timestamp, 
+            expiry: args.expiry,
+            allowed_taker: args.allowed_taker,
+            arbiter: args.arbiter,
+            start_receive: args.receive,
+            floor_receive: args.floor_receive,
+            decay_end: args.decay_end,
+            mint_a2: Pubkey::default(),
+            deposit_a2: 0,
+            min_fill: args.min_fill,
+            memo: args.memo,
             bump: bumps.escrow,
-        });
+            receive_decimals: self.mint_b.decimals,
+            all_or_nothing: args.all_or_nothing as u8,
+            _padding: [0; 5],
+            refund_cooldown: args.refund_cooldown,
+            nonce: args.nonce,
+            royalty_recipient: args.royalty_recipient,
+            royalty_bps: args.royalty_bps,
+            _royalty_padding: [0; 6],
+            namespace: args.namespace,
+            accepted_receive_mints: args.accepted_receive_mints,
+            taker_tip: args.taker_tip,
+            status: EscrowStatus::Open as u8,
+            status_before_stage: EscrowStatus::Open as u8,
+            unwrap_sol: args.unwrap_sol as u8,
+            rounding: args.rounding as u8,
+            _status_padding: [0; 4],
+            settlement_hook: args.settlement_hook,
+            oracle: args.oracle,
+            oracle_max_staleness: args.oracle_max_staleness,
+            oracle_min_price: args.oracle_min_price,
+            oracle_max_price: args.oracle_max_price,
+            refund_authority: args.refund_authority,
+        };
 
         Ok(())
     }
 
-    pub fn deposit(&mut self, deposit: u64) -> Result<()> {
+    pub fn index_escrow(&mut self, bumps: &MakeBumps) -> Result<()> {
+        self.maker_index.maker = self.maker.key();
+        self.maker_index.bump = bumps.maker_index;
+
+        self.maker_index.push(self.escrow.key(), self.config.open_escrow_limit())
+    }
+
+    //Returns what the vault gained, which is less than `deposit` under a Token-2022 transfer fee
+    pub fn deposit(&mut self, deposit: u64) -> Result<u64> {
         let cpi_program = self.token_program.to_account_info();
 
         let cpi_accounts = TransferChecked {
@@ -64,8 +273,22 @@ impl<'info> Make<'info> {
 
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
 
+        let vault_before = self.vault.amount;
         transfer_checked(cpi_ctx, deposit, self.mint_a.decimals)?;
 
-        Ok(())
+        received_since(&mut self.vault, vault_before)
+    }
+
+    //The escrow discriminator is only written on exit, so read from args instead of load()
+    pub fn emit_made(&self, args: &MakeArgs) {
+        emit!(EscrowMade {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: self.mint_b.key(),
+            deposit: args.deposit,
+            receive: args.receive,
+            seed: args.seed,
+        });
     }
 }
\ No newline at end of file
@@ -0,0 +1,241 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}};
+
+use crate::{error::EscrowError, events::EscrowMade, state::{Config, Escrow, EscrowStatus, MakerIndex, PausedMint, RoundingMode}, transfer::received_since};
+
+//Baskets are fixed price and taken in full, so there is no auction or arbiter here
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct MakeBasketArgs {
+    pub seed: u64,
+    pub deposit: u64, //mint_a into the vault
+    pub deposit_a2: u64, //mint_a2 into the vault_a2
+    pub receive: u64,
+    pub expiry: i64, //unix timestamp, 0 = never expires
+    pub allowed_taker: Pubkey, //Pubkey::default() = anyone can take
+    pub memo: [u8; 32], //order id or other maker reference, zero padded
+}
+
+#[derive(Accounts)]
+#[instruction(args: MakeBasketArgs)]
+pub struct MakeBasket<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(mint::token_program = token_program)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mint::token_program = token_program,
+        constraint = mint_a2.key() != mint_a.key() @ EscrowError::SameMint,
+    )]
+    pub mint_a2: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mint::token_program = token_program,
+        constraint = mint_b.key() != mint_a.key() @ EscrowError::SameMint,
+        constraint = mint_b.key() != mint_a2.key() @ EscrowError::SameMint,
+    )]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a2,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_ata_a2: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = maker,
+        seeds = [b"escrow", maker.key().as_ref(), args.seed.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + Escrow::INIT_SPACE,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_a2,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_a2: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = maker,
+        seeds = [b"index", maker.key().as_ref()],
+        bump,
+        space = 8 + MakerIndex::INIT_SPACE,
+    )]
+    pub maker_index: Box<Account<'info, MakerIndex>>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ EscrowError::ProgramPaused,
+        constraint = config.allows_receive_mint(&mint_b.key()) @ EscrowError::MintNotAllowed,
+        constraint = config.allows_deposit_mint(&mint_a.freeze_authority) @ EscrowError::FreezableMint,
+        constraint = config.allows_deposit_mint(&mint_a2.freeze_authority) @ EscrowError::FreezableMint,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    /// CHECK: address is pinned by seeds, only read through PausedMint::is_paused
+    #[account(
+        seeds = [b"paused", mint_a.key().as_ref()],
+        bump,
+        constraint = !PausedMint::is_paused(&paused_mint_a) @ EscrowError::MintPaused,
+    )]
+    pub paused_mint_a: UncheckedAccount<'info>,
+    /// CHECK: address is pinned by seeds, only read through PausedMint::is_paused
+    #[account(
+        seeds = [b"paused", mint_a2.key().as_ref()],
+        bump,
+        constraint = !PausedMint::is_paused(&paused_mint_a2) @ EscrowError::MintPaused,
+    )]
+    pub paused_mint_a2: UncheckedAccount<'info>,
+    /// CHECK: address is pinned by seeds, only read through PausedMint::is_paused
+    #[account(
+        seeds = [b"paused", mint_b.key().as_ref()],
+        bump,
+        constraint = !PausedMint::is_paused(&paused_mint_b) @ EscrowError::MintPaused,
+    )]
+    pub paused_mint_b: UncheckedAccount<'info>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MakeBasket<'info> {
+    pub fn validate(&self, args: &MakeBasketArgs) -> Result<()> {
+        require!(args.deposit > 0, EscrowError::InvalidAmount);
+        require!(args.deposit_a2 > 0, EscrowError::InvalidAmount);
+        require!(args.receive > 0, EscrowError::InvalidAmount);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(self.config.allows_expiry(args.expiry, now), EscrowError::LifetimeTooLong);
+
+        Ok(())
+    }
+
+    pub fn init_escrow(&mut self, args: &MakeBasketArgs, bumps: &MakeBasketBumps) -> Result<()> {
+        let clock = Clock::get()?;
+        *self.escrow.load_init()? = Escrow {
+            version: Escrow::CURRENT_VERSION,
+            _version_padding: [0; 7],
+            seed: args.seed,
+            maker: self.maker.key(),
+            seed_maker: self.maker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: self.mint_b.key(),
+            receive: args.receive,
+            deposit: args.deposit,
+            created_at: clock.unix_timestamp,
+            expiry: args.expiry,
+            allowed_taker: args.allowed_taker,
+            arbiter: Pubkey::default(),
+            start_receive: args.receive,
+            floor_receive: 0,
+            decay_end: 0,
+            mint_a2: self.mint_a2.key(),
+            deposit_a2: args.deposit_a2,
+            min_fill: 0,
+            memo: args.memo,
+            bump: bumps.escrow,
+            receive_decimals: self.mint_b.decimals,
+            all_or_nothing: 1,
+            _padding: [0; 5],
+            refund_cooldown: 0,
+            nonce: 0,
+            royalty_recipient: Pubkey::default(),
+            royalty_bps: 0,
+            _royalty_padding: [0; 6],
+            namespace: [0; 8],
+            accepted_receive_mints: [Pubkey::default(); 4],
+            taker_tip: 0,
+            status: EscrowStatus::Open as u8,
+            status_before_stage: EscrowStatus::Open as u8,
+            unwrap_sol: 0,
+            rounding: RoundingMode::MakerFavored as u8,
+            _status_padding: [0; 4],
+            settlement_hook: Pubkey::default(),
+            oracle: Pubkey::default(),
+            oracle_max_staleness: 0,
+            oracle_min_price: 0,
+            oracle_max_price: 0,
+            refund_authority: Pubkey::default(),
+        };
+
+        Ok(())
+    }
+
+    pub fn index_escrow(&mut self, bumps: &MakeBasketBumps) -> Result<()> {
+        self.maker_index.maker = self.maker.key();
+        self.maker_index.bump = bumps.maker_index;
+
+        self.maker_index.push(self.escrow.key(), self.config.open_escrow_limit())
+    }
+
+    //Returns args with both deposits set to what their vaults gained, less than sent under a Token-2022 transfer fee
+    pub fn deposit(&mut self, args: MakeBasketArgs) -> Result<MakeBasketArgs> {
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.maker_ata_a.to_account_info(),
+            to: self.vault.to_account_info(),
+            authority: self.maker.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        let vault_before = self.vault.amount;
+        transfer_checked(cpi_ctx, args.deposit, self.mint_a.decimals)?;
+        let deposit = received_since(&mut self.vault, vault_before)?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.maker_ata_a2.to_account_info(),
+            to: self.vault_a2.to_account_info(),
+            authority: self.maker.to_account_info(),
+            mint: self.mint_a2.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        let vault_a2_before = self.vault_a2.amount;
+        transfer_checked(cpi_ctx, args.deposit_a2, self.mint_a2.decimals)?;
+        let deposit_a2 = received_since(&mut self.vault_a2, vault_a2_before)?;
+
+        require!(deposit > 0 && deposit_a2 > 0, EscrowError::InvalidAmount);
+
+        Ok(MakeBasketArgs {
+            deposit,
+            deposit_a2,
+            ..args
+        })
+    }
+
+    //The escrow discriminator is only written on exit, so read from args instead of load()
+    pub fn emit_made(&self, args: &MakeBasketArgs) {
+        emit!(EscrowMade {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: self.mint_b.key(),
+            deposit: args.deposit,
+            receive: args.receive,
+            seed: args.seed,
+        });
+    }
+}
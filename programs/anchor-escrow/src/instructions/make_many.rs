@@ -0,0 +1,182 @@
+use anchor_lang::{prelude::*, Discriminator};
+use anchor_spl::{associated_token::{self, get_associated_token_address_with_program_id, AssociatedToken, Create}, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}};
+
+use crate::{create::create_program_account, error::EscrowError, events::EscrowMade, state::{Config, Escrow, EscrowStatus, MakerIndex, PausedMint}, transfer::received_since};
+
+//mint_a, mint_b, maker_ata_a, escrow, vault, paused_mint_a, paused_mint_b
+pub const ACCOUNTS_PER_MAKE: usize = 7;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct MakeOrder {
+    pub seed: u64,
+    pub deposit: u64,
+    pub receive: u64,
+}
+
+#[derive(Accounts)]
+pub struct MakeMany<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = maker,
+        seeds = [b"index", maker.key().as_ref()],
+        bump,
+        space = 8 + MakerIndex::INIT_SPACE,
+    )]
+    pub maker_index: Account<'info, MakerIndex>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ EscrowError::ProgramPaused,
+    )]
+    pub config: Account<'info, Config>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+//Make one plain escrow per order, each in the default book with nonce 0, no expiry and no extras
+//Any order that fails aborts the whole transaction, so either every escrow exists or none do
+impl<'info> MakeMany<'info> {
+    pub fn index_maker(&mut self, bumps: &MakeManyBumps) {
+        self.maker_index.maker = self.maker.key();
+        self.maker_index.bump = bumps.maker_index;
+    }
+
+    pub fn make_all(&mut self, remaining_accounts: &'info [AccountInfo<'info>], orders: &[MakeOrder]) -> Result<()> {
+        require!(
+            !orders.is_empty() && remaining_accounts.len() == orders.len() * ACCOUNTS_PER_MAKE,
+            EscrowError::InvalidRemainingAccounts
+        );
+
+        //An unexpiring escrow is only allowed when the config has no lifetime cap
+        let now = Clock::get()?.unix_timestamp;
+        require!(self.config.allows_expiry(0, now), EscrowError::LifetimeTooLong);
+
+        for (accounts, order) in remaining_accounts.chunks(ACCOUNTS_PER_MAKE).zip(orders) {
+            self.make_one(accounts, order, now)?;
+            self.config.record_make()?;
+        }
+
+        Ok(())
+    }
+
+    fn make_one(&mut self, accounts: &'info [AccountInfo<'info>], order: &MakeOrder, now: i64) -> Result<()> {
+        let [mint_a, mint_b, maker_ata_a, escrow, vault, paused_mint_a, paused_mint_b] = accounts else {
+            return err!(EscrowError::InvalidRemainingAccounts);
+        };
+
+        require!(order.deposit > 0, EscrowError::InvalidAmount);
+        require!(order.receive > 0, EscrowError::InvalidAmount);
+
+        //Same checks Make gets from its account constraints
+        require_keys_eq!(*mint_a.owner, self.token_program.key(), EscrowError::InvalidMint);
+        require_keys_eq!(*mint_b.owner, self.token_program.key(), EscrowError::InvalidMint);
+        require_keys_neq!(mint_a.key(), mint_b.key(), EscrowError::SameMint);
+        let mint_a_data = InterfaceAccount::<Mint>::try_from(mint_a)?;
+        let mint_b_data = InterfaceAccount::<Mint>::try_from(mint_b)?;
+
+        require!(self.config.allows_receive_mint(&mint_b.key()), EscrowError::MintNotAllowed);
+        require!(self.config.allows_deposit_mint(&mint_a_data.freeze_authority), EscrowError::FreezableMint);
+
+        let (paused_a_address, _) = Pubkey::find_program_address(&[b"paused", mint_a.key().as_ref()], &crate::ID);
+        let (paused_b_address, _) = Pubkey::find_program_address(&[b"paused", mint_b.key().as_ref()], &crate::ID);
+        require_keys_eq!(paused_mint_a.key(), paused_a_address, EscrowError::InvalidRemainingAccounts);
+        require_keys_eq!(paused_mint_b.key(), paused_b_address, EscrowError::InvalidRemainingAccounts);
+        require!(!PausedMint::is_paused(paused_mint_a), EscrowError::MintPaused);
+        require!(!PausedMint::is_paused(paused_mint_b), EscrowError::MintPaused);
+
+        let maker = self.maker.key();
+        let token_program = self.token_program.key();
+        require_keys_eq!(
+            maker_ata_a.key(),
+            get_associated_token_address_with_program_id(&maker, &mint_a.key(), &token_program),
+            EscrowError::InvalidRemainingAccounts
+        );
+
+        let seed_bytes = order.seed.to_le_bytes();
+        let (escrow_address, bump) = Pubkey::find_program_address(&[b"escrow", maker.as_ref(), &seed_bytes], &crate::ID);
+        require_keys_eq!(escrow.key(), escrow_address, EscrowError::InvalidRemainingAccounts);
+        require_keys_eq!(
+            vault.key(),
+            get_associated_token_address_with_program_id(&escrow_address, &mint_a.key(), &token_program),
+            EscrowError::InvalidRemainingAccounts
+        );
+
+        create_program_account(
+            &self.maker.to_account_info(),
+            escrow,
+            8 + Escrow::INIT_SPACE,
+            &[b"escrow", maker.as_ref(), &seed_bytes, &[bump]],
+            &self.system_program.to_account_info(),
+        )?;
+
+        let cpi_program = self.associated_token_program.to_account_info();
+
+        let cpi_accounts = Create {
+            payer: self.maker.to_account_info(),
+            associated_token: vault.clone(),
+            authority: escrow.clone(),
+            mint: mint_a.clone(),
+            system_program: self.system_program.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+        };
+
+        associated_token::create_idempotent(CpiContext::new(cpi_program, cpi_accounts))?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: maker_ata_a.clone(),
+            to: vault.clone(),
+            authority: self.maker.to_account_info(),
+            mint: mint_a.clone(),
+        };
+
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        //Same as Make, the escrow records what the vault gained rather than what was sent
+        let mut vault_data = InterfaceAccount::<TokenAccount>::try_from(vault)?;
+        let vault_before = vault_data.amount;
+        transfer_checked(cpi_ctx, order.deposit, mint_a_data.decimals)?;
+        let deposit = received_since(&mut vault_data, vault_before)?;
+        require!(deposit > 0, EscrowError::InvalidAmount);
+
+        let mut data = escrow.try_borrow_mut_data()?;
+        data[..8].copy_from_slice(Escrow::DISCRIMINATOR);
+        *bytemuck::from_bytes_mut::<Escrow>(&mut data[8..]) = Escrow {
+            version: Escrow::CURRENT_VERSION,
+            seed: order.seed,
+            maker,
+            seed_maker: maker,
+            mint_a: mint_a.key(),
+            mint_b: mint_b.key(),
+            receive: order.receive,
+            deposit,
+            created_at: now,
+            start_receive: order.receive,
+            bump,
+            receive_decimals: mint_b_data.decimals,
+            status: EscrowStatus::Open as u8,
+            status_before_stage: EscrowStatus::Open as u8,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        drop(data);
+
+        self.maker_index.push(escrow.key(), self.config.open_escrow_limit())?;
+
+        emit!(EscrowMade {
+            escrow: escrow.key(),
+            maker,
+            mint_a: mint_a.key(),
+            mint_b: mint_b.key(),
+            deposit,
+            receive: order.receive,
+            seed: order.seed,
+        });
+
+        Ok(())
+    }
+}
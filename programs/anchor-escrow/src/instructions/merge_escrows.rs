@@ -0,0 +1,182 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, CloseAccount, close_account};
+
+use crate::{close::close_escrow, error::EscrowError, state::{Escrow, EscrowStatus, MakerIndex}};
+
+//Folds `other` into `escrow`: vaults, deposits and receives are summed and `other` is closed
+//Both must already share their expiry, taker, arbiter, fill and refund terms, see check_terms
+#[derive(Accounts)]
+pub struct MergeEscrows<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(mint::token_program = token_program)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        has_one = mint_a @ EscrowError::InvalidMint,
+        has_one = maker @ EscrowError::InvalidMaker,
+        constraint = !escrow.load()?.is_basket() @ EscrowError::BasketNotSupported,
+        constraint = !escrow.load()?.has_tip() @ EscrowError::TipNotSupported,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = other.key() != escrow.key() @ EscrowError::MergeMismatch,
+        constraint = other.load()?.maker == maker.key() @ EscrowError::MergeMismatch,
+        constraint = other.load()?.mint_a == mint_a.key() @ EscrowError::MergeMismatch,
+        constraint = other.load()?.mint_b == escrow.load()?.mint_b @ EscrowError::MergeMismatch,
+        constraint = other.load()?.namespace == escrow.load()?.namespace @ EscrowError::MergeMismatch,
+        constraint = !other.load()?.is_basket() @ EscrowError::BasketNotSupported,
+        constraint = !other.load()?.has_tip() @ EscrowError::TipNotSupported,
+        seeds = [b"escrow", Escrow::namespace_seed(&other.load()?.namespace), other.load()?.seed_maker.as_ref(), other.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&other.load()?.nonce)],
+        bump = other.load()?.bump,
+    )]
+    pub other: AccountLoader<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = other,
+        associated_token::token_program = token_program,
+    )]
+    pub other_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"index", maker.key().as_ref()],
+        bump = maker_index.bump,
+    )]
+    pub maker_index: Box<Account<'info, MakerIndex>>,
+    /// CHECK: address is pinned by seeds, it only has to be empty
+    #[account(
+        seeds = [b"stage", escrow.key().as_ref()],
+        bump,
+    )]
+    pub staged_take: UncheckedAccount<'info>,
+    /// CHECK: address is pinned by seeds, it only has to be empty
+    #[account(
+        seeds = [b"stage", other.key().as_ref()],
+        bump,
+    )]
+    pub other_staged_take: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MergeEscrows<'info> {
+    //A staged take was priced against a single vault
+    pub fn check_no_stage(&self) -> Result<()> {
+        require!(self.staged_take.data_is_empty(), EscrowError::StagedTakeActive);
+        require!(self.other_staged_take.data_is_empty(), EscrowError::StagedTakeActive);
+
+        Ok(())
+    }
+
+    //Summing receives only keeps the price right when both decay the same way and are quoted alike
+    pub fn check_pricing(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+        let other = *self.other.load()?;
+
+        require!(escrow.receive_decimals == other.receive_decimals, EscrowError::MergeMismatch);
+        require!(escrow.decay_end == other.decay_end, EscrowError::MergeMismatch);
+        require!(escrow.decay_end == 0 || escrow.created_at == other.created_at, EscrowError::MergeMismatch);
+        require!(escrow.royalty_bps == other.royalty_bps, EscrowError::MergeMismatch);
+        require_keys_eq!(escrow.royalty_recipient, other.royalty_recipient, EscrowError::MergeMismatch);
+        require!(escrow.accepted_receive_mints == other.accepted_receive_mints, EscrowError::MergeMismatch);
+        require_keys_eq!(escrow.settlement_hook, other.settlement_hook, EscrowError::MergeMismatch);
+        require_keys_eq!(escrow.oracle, other.oracle, EscrowError::MergeMismatch);
+        require!(escrow.oracle_max_staleness == other.oracle_max_staleness, EscrowError::MergeMismatch);
+        require!(escrow.oracle_min_price == other.oracle_min_price, EscrowError::MergeMismatch);
+        require!(escrow.oracle_max_price == other.oracle_max_price, EscrowError::MergeMismatch);
+        require!(escrow.rounding == other.rounding, EscrowError::MergeMismatch);
+
+        Ok(())
+    }
+
+    //`other`'s tokens end up under escrow's terms, so a looser escrow would lift a cooldown or an arbiter early
+    pub fn check_terms(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+        let other = *self.other.load()?;
+
+        require!(escrow.expiry == other.expiry, EscrowError::MergeMismatch);
+        require_keys_eq!(escrow.allowed_taker, other.allowed_taker, EscrowError::MergeMismatch);
+        require_keys_eq!(escrow.arbiter, other.arbiter, EscrowError::MergeMismatch);
+        require_keys_eq!(escrow.refund_authority, other.refund_authority, EscrowError::MergeMismatch);
+        require!(escrow.min_fill == other.min_fill, EscrowError::MergeMismatch);
+        require!(escrow.all_or_nothing == other.all_or_nothing, EscrowError::MergeMismatch);
+        //The cooldown runs from created_at, so escrow must also unlock no sooner than other would have
+        require!(escrow.refund_cooldown == other.refund_cooldown, EscrowError::MergeMismatch);
+        require!(escrow.refund_cooldown == 0 || escrow.created_at >= other.created_at, EscrowError::MergeMismatch);
+
+        Ok(())
+    }
+
+    pub fn merge(&mut self) -> Result<()> {
+        let other = *self.other.load()?;
+        let mut escrow = self.escrow.load_mut()?;
+
+        escrow.receive = escrow.receive.checked_add(other.receive).ok_or(EscrowError::MathOverflow)?;
+        escrow.deposit = escrow.deposit.checked_add(other.deposit).ok_or(EscrowError::MathOverflow)?;
+        escrow.start_receive = escrow.start_receive.checked_add(other.start_receive).ok_or(EscrowError::MathOverflow)?;
+        escrow.floor_receive = escrow.floor_receive.checked_add(other.floor_receive).ok_or(EscrowError::MathOverflow)?;
+        //Neither side is staged, so status alone says whether a fill happened
+        if other.status() == EscrowStatus::PartiallyFilled {
+            escrow.record_partial_fill();
+        }
+
+        Ok(())
+    }
+
+    pub fn move_and_close_other_vault(&mut self) -> Result<()> {
+        let other = *self.other.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&other.namespace),
+            other.seed_maker.as_ref(),
+            &other.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&other.nonce),
+            &[other.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.other_vault.to_account_info(),
+            to: self.vault.to_account_info(),
+            authority: self.other.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, self.other_vault.amount, self.mint_a.decimals)?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: self.other_vault.to_account_info(),
+            destination: self.maker.to_account_info(),
+            authority: self.other.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)
+    }
+
+    pub fn unindex_other(&mut self) {
+        self.maker_index.remove(&self.other.key());
+    }
+
+    pub fn close_other(&self) -> Result<()> {
+        close_escrow(&self.other.to_account_info(), &self.maker.to_account_info())
+    }
+}
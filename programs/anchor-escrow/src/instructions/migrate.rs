@@ -0,0 +1,73 @@
+use anchor_lang::{prelude::*, system_program::{transfer, Transfer}, Discriminator};
+
+use crate::{error::EscrowError, state::Escrow};
+
+//Upgrades a v1–v11 escrow to the current version in place: grows it to the new size, shifting a v1 body behind the version header
+//Fields added since are zeroed, which keeps their old behavior
+//Anyone can pay for the extra rent, the escrow contents are not changed
+#[derive(Accounts)]
+pub struct Migrate<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: an older layout can't be loaded as an Escrow, owner and discriminator are checked here
+    #[account(mut, owner = crate::ID)]
+    pub escrow: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Migrate<'info> {
+    pub fn check_version(&self) -> Result<()> {
+        let data = self.escrow.try_borrow_data()?;
+
+        require!(data.starts_with(Escrow::DISCRIMINATOR), EscrowError::UnsupportedVersion);
+        let is_v1 = data.len() == 8 + Escrow::V1_LEN;
+        let is_v2 = data.len() == 8 + Escrow::V2_LEN && data[8] == 2;
+        let is_v3 = data.len() == 8 + Escrow::V3_LEN && data[8] == 3;
+        let is_v4 = data.len() == 8 + Escrow::V4_LEN && data[8] == 4;
+        let is_v5 = data.len() == 8 + Escrow::V5_LEN && data[8] == 5;
+        let is_v6 = data.len() == 8 + Escrow::V6_LEN && data[8] == 6;
+        let is_v7 = data.len() == 8 + Escrow::V7_LEN && data[8] == 7;
+        let is_v8 = data.len() == 8 + Escrow::V8_LEN && data[8] == 8;
+        let is_v9 = data.len() == 8 + Escrow::V9_LEN && data[8] == 9;
+        let is_v10 = data.len() == 8 + Escrow::V10_LEN && data[8] == 10;
+        let is_v11 = data.len() == 8 + Escrow::V11_LEN && data[8] == 11;
+        require!(is_v1 || is_v2 || is_v3 || is_v4 || is_v5 || is_v6 || is_v7 || is_v8 || is_v9 || is_v10 || is_v11, EscrowError::UnsupportedVersion);
+
+        Ok(())
+    }
+
+    pub fn fund_rent(&self) -> Result<()> {
+        let required = Rent::get()?.minimum_balance(8 + Escrow::INIT_SPACE);
+        let shortfall = required.saturating_sub(self.escrow.lamports());
+        if shortfall == 0 {
+            return Ok(());
+        }
+
+        let cpi_accounts = Transfer {
+            from: self.payer.to_account_info(),
+            to: self.escrow.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+
+        transfer(cpi_ctx, shortfall)
+    }
+
+    pub fn migrate(&mut self) -> Result<()> {
+        let escrow_info = self.escrow.to_account_info();
+        let is_v1 = escrow_info.data_len() == 8 + Escrow::V1_LEN;
+        //Where the old fields end once a v1 body has moved behind the header
+        let body_end = escrow_info.data_len() + if is_v1 { 8 } else { 0 };
+        escrow_info.realloc(8 + Escrow::INIT_SPACE, true)?;
+
+        let mut data = escrow_info.try_borrow_mut_data()?;
+        if is_v1 {
+            data.copy_within(8..8 + Escrow::V1_LEN, 16);
+            data[9..16].fill(0);
+        }
+        data[8] = Escrow::CURRENT_VERSION;
+        data[body_end..].fill(0);
+
+        Ok(())
+    }
+}
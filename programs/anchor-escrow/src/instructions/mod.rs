@@ -1,7 +1,73 @@
+pub mod add_allowed_mint;
+pub mod admin_force_refund;
+pub mod admin_rescue;
+pub mod arbiter_release;
+pub mod cancel_escrow;
+pub mod cancel_stage;
+pub mod clear_fee_tier;
+pub mod init_config;
 pub mod make;
+pub mod make_basket;
+pub mod make_many;
+pub mod merge_escrows;
+pub mod migrate;
+pub mod quote;
+pub mod reclaim_expired;
 pub mod refund;
+pub mod refund_many;
+pub mod remove_allowed_mint;
+pub mod set_config_authority;
+pub mod set_cranker_bps;
+pub mod set_fee_tier;
+pub mod set_max_lifetime;
+pub mod set_max_open_per_maker;
+pub mod set_mint_paused;
+pub mod set_paused;
+pub mod set_strict_mints;
+pub mod set_taker_fee;
+pub mod settle;
+pub mod split_escrow;
+pub mod stage_take;
 pub mod take;
+pub mod take_many;
+pub mod take_with_sol;
+pub mod top_up;
+pub mod transfer_maker;
+pub mod update_offer;
 
+pub use add_allowed_mint::*;
+pub use admin_force_refund::*;
+pub use admin_rescue::*;
+pub use arbiter_release::*;
+pub use cancel_escrow::*;
+pub use cancel_stage::*;
+pub use clear_fee_tier::*;
+pub use init_config::*;
 pub use make::*;
+pub use make_basket::*;
+pub use make_many::*;
+pub use merge_escrows::*;
+pub use migrate::*;
+pub use quote::*;
+pub use reclaim_expired::*;
 pub use refund::*;
-pub use take::*;
\ No newline at end of file
+pub use refund_many::*;
+pub use remove_allowed_mint::*;
+pub use set_config_authority::*;
+pub use set_cranker_bps::*;
+pub use set_fee_tier::*;
+pub use set_max_lifetime::*;
+pub use set_max_open_per_maker::*;
+pub use set_mint_paused::*;
+pub use set_paused::*;
+pub use set_strict_mints::*;
+pub use set_taker_fee::*;
+pub use settle::*;
+pub use split_escrow::*;
+pub use stage_take::*;
+pub use take::*;
+pub use take_many::*;
+pub use take_with_sol::*;
+pub use top_up::*;
+pub use transfer_maker::*;
+pub use update_offer::*;
\ No newline at end of file
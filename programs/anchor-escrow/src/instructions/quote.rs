@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{error::EscrowError, events::EscrowQuote, state::{Config, Escrow}};
+
+//Read only: prices a take of `take_amount` the way Take would and emits it
+#[derive(Accounts)]
+pub struct Quote<'info> {
+    #[account(mint::token_program = token_program)]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    #[account(
+        has_one = mint_a @ EscrowError::InvalidMint,
+        constraint = !escrow.load()?.has_oracle() @ EscrowError::OracleNotSupported,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> Quote<'info> {
+    pub fn emit_quote(&self, take_amount: u64) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let fillable = escrow.fillable(self.vault.amount);
+
+        require!(take_amount > 0, EscrowError::InvalidAmount);
+        require!(take_amount <= fillable, EscrowError::PartialFillTooLarge);
+
+        let now = Clock::get()?.unix_timestamp;
+        let (receive, _) = escrow.fill(take_amount, fillable, now)?;
+
+        emit!(EscrowQuote {
+            escrow: self.escrow.key(),
+            deposit: take_amount,
+            receive,
+            fee: self.config.fee_for(&escrow.mint_b, receive)?,
+            taker_fee: self.config.taker_fee_for(take_amount)?,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked, CloseAccount, close_account}};
+
+use crate::{close::close_escrow, error::EscrowError, state::{Config, Escrow, MakerIndex}};
+
+//The cranker is paid config.cranker_bps of the vault, the maker gets the rest
+#[derive(Accounts)]
+pub struct ReclaimExpired<'info> {
+    #[account(mut)]
+    cranker: Signer<'info>,
+    #[account(mut)]
+    maker: SystemAccount<'info>,
+    mint_a: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+    )]
+    maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = cranker,
+        associated_token::mint = mint_a,
+        associated_token::authority = cranker,
+        associated_token::token_program = token_program,
+    )]
+    cranker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        has_one = mint_a @ EscrowError::InvalidMint,
+        has_one = maker @ EscrowError::InvalidMaker,
+        constraint = !escrow.load()?.is_basket() @ EscrowError::BasketNotSupported,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+    )]
+    vault: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: address is pinned by seeds, it only has to be empty
+    #[account(
+        seeds = [b"stage", escrow.key().as_ref()],
+        bump,
+    )]
+    staged_take: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"index", maker.key().as_ref()],
+        bump = maker_index.bump,
+    )]
+    maker_index: Account<'info, MakerIndex>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    config: Account<'info, Config>,
+    associated_token_program: Program<'info, AssociatedToken>,
+    token_program: Interface<'info, TokenInterface>,
+    system_program: Program<'info, System>,
+}
+
+impl<'info> ReclaimExpired<'info> {
+    //Same as Refund, an escrow with a staged take is wound down through CancelEscrow
+    pub fn check_no_stage(&self) -> Result<()> {
+        require!(self.staged_take.data_is_empty(), EscrowError::StagedTakeActive);
+
+        Ok(())
+    }
+
+    pub fn check_expiry(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(escrow.is_expired(now), EscrowError::EscrowNotExpired);
+
+        Ok(())
+    }
+
+    pub fn reclaim_and_close_vault(&mut self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let bounty = self.config.cranker_bounty_for(self.vault.amount)?;
+
+        if bounty > 0 {
+            let cpi_program = self.token_program.to_account_info();
+
+            let cpi_accounts = TransferChecked {
+                from: self.vault.to_account_info(),
+                to: self.cranker_ata_a.to_account_info(),
+                mint: self.mint_a.to_account_info(),
+                authority: self.escrow.to_account_info(),
+            };
+
+            let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+            transfer_checked(cpi_context, bounty, self.mint_a.decimals)?;
+        }
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            to: self.maker_ata_a.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, self.vault.amount.checked_sub(bounty).ok_or(EscrowError::MathOverflow)?, self.mint_a.decimals)?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.maker.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)
+    }
+
+    pub fn record_refund(&mut self) -> Result<()> {
+        self.config.record_refund()
+    }
+
+    pub fn unindex(&mut self) {
+        self.maker_index.remove(&self.escrow.key());
+    }
+
+    pub fn close_escrow(&self) -> Result<()> {
+        close_escrow(&self.escrow.to_account_info(), &self.maker.to_account_info())
+    }
+}
@@ -1,59 +1,155 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked, CloseAccount, close_account};
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked, CloseAccount, close_account}};
 
-use crate::state::Escrow;
+use crate::{close::close_escrow, error::EscrowError, events::EscrowRefunded, state::{Config, Escrow, MakerIndex}};
 
+//Either the maker or the escrow's refund_authority signs, checked in check_authority
+//The tokens and rent always go back to the maker
 #[derive(Accounts)]
 pub struct Refund<'info> {
     #[account(mut)]
-    maker: Signer<'info>,
+    maker: SystemAccount<'info>,
+    refund_authority: Option<Signer<'info>>,
+    #[account(mint::token_program = token_program)]
     mint_a: InterfaceAccount<'info, Mint>,
+    //Recreated at the maker's expense if they closed it after Make, which needs the maker's signature
     #[account(
-        mut,
+        init_if_needed,
+        payer = maker,
         associated_token::mint = mint_a,
         associated_token::authority = maker,
+        associated_token::token_program = token_program,
     )]
     maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    //Any mint_a account the maker owns, falls back to maker_ata_a when omitted
+    #[account(
+        mut,
+        token::mint = mint_a,
+        token::authority = maker,
+        token::token_program = token_program,
+    )]
+    refund_destination: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
-        close = maker,
-        has_one = mint_a,
-        has_one = maker,
-        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
-        bump = escrow.bump,
+        has_one = mint_a @ EscrowError::InvalidMint,
+        has_one = maker @ EscrowError::InvalidMaker,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
     )]
-    pub escrow: Account<'info, Escrow>,
+    pub escrow: AccountLoader<'info, Escrow>,
     #[account(
         mut,
         associated_token::mint = mint_a,
         associated_token::authority = escrow,
+        associated_token::token_program = token_program,
     )]
     vault: InterfaceAccount<'info, TokenAccount>,
+    //mint_a2, maker_ata_a2 and vault_a2 are only passed for basket escrows
+    #[account(mint::token_program = token_program)]
+    mint_a2: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a2,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    maker_ata_a2: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a2,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    vault_a2: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: address is pinned by seeds, it only has to be empty
+    #[account(
+        seeds = [b"stage", escrow.key().as_ref()],
+        bump,
+    )]
+    staged_take: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"index", escrow.load()?.maker.as_ref()],
+        bump = maker_index.bump,
+    )]
+    maker_index: Account<'info, MakerIndex>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    config: Account<'info, Config>,
+    associated_token_program: Program<'info, AssociatedToken>,
+    //Interface only accepts the Token and Token-2022 program ids, so the vault CPIs can't reach a look-alike program that re-enters Refund
     token_program: Interface<'info, TokenInterface>,
     system_program: Program<'info, System>,
 }
 
 impl<'info> Refund<'info> {
+    #[cfg(feature = "debug")]
+    pub fn log_pdas(&self) {
+        msg!("escrow: {}, vault: {}", self.escrow.key(), self.vault.key());
+    }
+
+    //A staged taker's mint_b would be stranded in the take vault, CancelEscrow refunds both sides
+    pub fn check_no_stage(&self) -> Result<()> {
+        require!(self.staged_take.data_is_empty(), EscrowError::StagedTakeActive);
+
+        Ok(())
+    }
+
+    pub fn check_authority(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let authority_signed = self.refund_authority.as_ref().is_some_and(|authority| escrow.can_refund(&authority.key()));
+        require!(self.maker.is_signer || authority_signed, EscrowError::RefundNotAuthorized);
+
+        Ok(())
+    }
+
+    pub fn check_cooldown(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(escrow.refund_unlocked(now), EscrowError::CooldownActive);
+
+        Ok(())
+    }
+
+    fn destination(&self) -> AccountInfo<'info> {
+        match &self.refund_destination {
+            Some(refund_destination) => refund_destination.to_account_info(),
+            None => self.maker_ata_a.to_account_info(),
+        }
+    }
+
     pub fn refund_and_close_vault(&mut self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
         let signer_seeds: [&[&[u8]]; 1] = [&[
             b"escrow",
-            self.maker.key.as_ref(),
-            &self.escrow.seed.to_le_bytes()[..],
-            &[self.escrow.bump]
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
         ]];
         
         let cpi_program = self.token_program.to_account_info();
 
         let cpi_accounts = TransferChecked {
             from: self.vault.to_account_info(),
-            to: self.maker_ata_a.to_account_info(),
+            to: self.destination(),
             mint: self.mint_a.to_account_info(),
             authority: self.escrow.to_account_info(),
         };
 
         let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
 
-        transfer_checked(cpi_context, self.vault.amount, self.mint_a.decimals)?;
+        //An open escrow can be left with an empty vault, it still closes and returns its rent
+        if self.vault.amount > 0 {
+            transfer_checked(cpi_context, self.vault.amount, self.mint_a.decimals)?;
+        }
 
         let cpi_program = self.token_program.to_account_info();
 
@@ -69,4 +165,107 @@ impl<'info> Refund<'info> {
         
         Ok(())
     }
+
+    pub fn refund_and_close_basket(&mut self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        if !escrow.is_basket() {
+            return Ok(());
+        }
+
+        let (Some(mint_a2), Some(maker_ata_a2), Some(vault_a2)) = (&self.mint_a2, &self.maker_ata_a2, &self.vault_a2) else {
+            return err!(EscrowError::BasketAccountsMissing);
+        };
+        require_keys_eq!(mint_a2.key(), escrow.mint_a2, EscrowError::InvalidMint);
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: vault_a2.to_account_info(),
+            to: maker_ata_a2.to_account_info(),
+            mint: mint_a2.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, vault_a2.amount, mint_a2.decimals)?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: vault_a2.to_account_info(),
+            destination: self.maker.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)
+    }
+
+    //Only worth asking for with a refund_destination, otherwise the refund itself refills maker_ata_a
+    //A nonzero balance leaves the ATA open rather than failing the refund
+    pub fn close_maker_ata(&mut self, close_maker_ata: bool) -> Result<()> {
+        if !close_maker_ata {
+            return Ok(());
+        }
+
+        //Closing the ATA is the maker's call even when the refund authority runs the refund
+        require!(self.maker.is_signer, EscrowError::RefundNotAuthorized);
+
+        self.maker_ata_a.reload()?;
+        if self.maker_ata_a.amount != 0 {
+            return Ok(());
+        }
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: self.maker_ata_a.to_account_info(),
+            destination: self.maker.to_account_info(),
+            authority: self.maker.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new(cpi_program, cpi_accounts);
+
+        close_account(cpi_context)
+    }
+
+    pub fn unindex(&mut self) {
+        self.maker_index.remove(&self.escrow.key());
+    }
+
+    pub fn record_refund(&mut self) -> Result<()> {
+        self.config.record_refund()
+    }
+
+    pub fn close_escrow(&self) -> Result<()> {
+        close_escrow(&self.escrow.to_account_info(), &self.maker.to_account_info())
+    }
+
+    pub fn emit_refunded(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        emit!(EscrowRefunded {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: escrow.mint_b,
+            deposit: self.vault.amount,
+            receive: escrow.receive,
+            seed: escrow.seed,
+        });
+
+        Ok(())
+    }
 }
\ No newline at end of file
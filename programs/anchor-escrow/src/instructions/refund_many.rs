@@ -0,0 +1,152 @@
+use anchor_lang::{error::ErrorCode, prelude::*, solana_program::program::set_return_data};
+use anchor_spl::{associated_token::get_associated_token_address_with_program_id, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, CloseAccount, close_account}};
+
+use crate::{close::close_escrow, error::EscrowError, events::EscrowRefunded, state::{Config, Escrow, MakerIndex}};
+
+//mint_a, escrow, vault, maker_ata_a, staged_take
+pub const ACCOUNTS_PER_REFUND: usize = 5;
+
+#[derive(Accounts)]
+pub struct RefundMany<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"index", maker.key().as_ref()],
+        bump = maker_index.bump,
+    )]
+    pub maker_index: Account<'info, MakerIndex>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+//Refund every expired escrow described in remaining_accounts
+//Escrows that can't be refunded yet abort the transaction with fail_fast, otherwise they are skipped
+impl<'info> RefundMany<'info> {
+    pub fn refund_all(&mut self, remaining_accounts: &'info [AccountInfo<'info>], fail_fast: bool) -> Result<()> {
+        require!(
+            !remaining_accounts.is_empty() && remaining_accounts.len().is_multiple_of(ACCOUNTS_PER_REFUND),
+            EscrowError::InvalidRemainingAccounts
+        );
+
+        let mut refunded: u64 = 0;
+        for accounts in remaining_accounts.chunks(ACCOUNTS_PER_REFUND) {
+            if self.refund_one(accounts, fail_fast)? {
+                self.config.record_refund()?;
+                refunded += 1;
+            }
+        }
+
+        set_return_data(&refunded.try_to_vec()?);
+
+        Ok(())
+    }
+
+    //Whether the escrow was refunded
+    fn refund_one(&mut self, accounts: &'info [AccountInfo<'info>], fail_fast: bool) -> Result<bool> {
+        let [mint_a, escrow, vault, maker_ata_a, staged_take] = accounts else {
+            return err!(EscrowError::InvalidRemainingAccounts);
+        };
+
+        let escrow_loader = AccountLoader::<Escrow>::try_from(escrow)?;
+        let escrow_data = *escrow_loader.load()?;
+        let mint_a_data = InterfaceAccount::<Mint>::try_from(mint_a)?;
+        let vault_data = InterfaceAccount::<TokenAccount>::try_from(vault)?;
+
+        //Same checks the Refund context enforces through constraints
+        let escrow_key = Pubkey::create_program_address(
+            &[b"escrow", Escrow::namespace_seed(&escrow_data.namespace), escrow_data.seed_maker.as_ref(), &escrow_data.seed.to_le_bytes(), Escrow::nonce_seed(&escrow_data.nonce), &[escrow_data.bump]],
+            &crate::ID,
+        ).map_err(|_| error!(ErrorCode::ConstraintSeeds))?;
+        require_keys_eq!(escrow_key, escrow.key(), ErrorCode::ConstraintSeeds);
+        require_keys_eq!(escrow_data.maker, self.maker.key(), EscrowError::InvalidMaker);
+        require_keys_eq!(escrow_data.mint_a, mint_a.key(), EscrowError::InvalidMint);
+        require!(!escrow_data.is_basket(), EscrowError::BasketNotSupported);
+
+        let token_program = self.token_program.key();
+        require_keys_eq!(
+            vault.key(),
+            get_associated_token_address_with_program_id(&escrow.key(), &mint_a.key(), &token_program),
+            ErrorCode::AccountNotAssociatedTokenAccount
+        );
+        require_keys_eq!(
+            maker_ata_a.key(),
+            get_associated_token_address_with_program_id(&self.maker.key(), &mint_a.key(), &token_program),
+            ErrorCode::AccountNotAssociatedTokenAccount
+        );
+
+        //Refund's check_no_stage, CancelEscrow is the way out for a staged escrow
+        let (staged_take_key, _) = Pubkey::find_program_address(&[b"stage", escrow.key().as_ref()], &crate::ID);
+        require_keys_eq!(staged_take.key(), staged_take_key, ErrorCode::ConstraintSeeds);
+        if !staged_take.data_is_empty() {
+            require!(!fail_fast, EscrowError::StagedTakeActive);
+            return Ok(false);
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        if !escrow_data.is_expired(now) {
+            require!(!fail_fast, EscrowError::EscrowNotExpired);
+            return Ok(false);
+        }
+        if !escrow_data.refund_unlocked(now) {
+            require!(!fail_fast, EscrowError::CooldownActive);
+            return Ok(false);
+        }
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow_data.namespace),
+            escrow_data.seed_maker.as_ref(),
+            &escrow_data.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow_data.nonce),
+            &[escrow_data.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: vault.clone(),
+            to: maker_ata_a.clone(),
+            mint: mint_a.clone(),
+            authority: escrow.clone(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, vault_data.amount, mint_a_data.decimals)?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: vault.clone(),
+            destination: self.maker.to_account_info(),
+            authority: escrow.clone(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)?;
+
+        emit!(EscrowRefunded {
+            escrow: escrow.key(),
+            maker: self.maker.key(),
+            mint_a: mint_a.key(),
+            mint_b: escrow_data.mint_b,
+            deposit: vault_data.amount,
+            receive: escrow_data.receive,
+            seed: escrow_data.seed,
+        });
+
+        self.maker_index.remove(&escrow.key());
+
+        close_escrow(escrow, &self.maker.to_account_info())?;
+
+        Ok(true)
+    }
+}
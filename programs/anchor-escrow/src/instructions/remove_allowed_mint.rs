@@ -0,0 +1,27 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Config};
+
+//Removing the last mint reopens Make to any mint_b
+#[derive(Accounts)]
+pub struct RemoveAllowedMint<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> RemoveAllowedMint<'info> {
+    pub fn remove_allowed_mint(&mut self, mint: Pubkey) -> Result<()> {
+        let allowed = &mut self.config.allowed_receive_mints;
+
+        let index = allowed.iter().position(|allowed_mint| *allowed_mint == mint).ok_or(EscrowError::MintNotAllowed)?;
+        allowed.remove(index);
+
+        Ok(())
+    }
+}
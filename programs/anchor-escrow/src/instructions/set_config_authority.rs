@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Config};
+
+//Takes effect immediately; fee_vault and fee_vault_a of later takes belong to the new authority
+#[derive(Accounts)]
+pub struct SetConfigAuthority<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> SetConfigAuthority<'info> {
+    pub fn set_config_authority(&mut self, new_authority: Pubkey) -> Result<()> {
+        require!(new_authority != Pubkey::default(), EscrowError::InvalidAuthority);
+
+        self.config.authority = new_authority;
+
+        Ok(())
+    }
+}
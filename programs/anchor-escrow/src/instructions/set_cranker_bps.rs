@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Config};
+
+#[derive(Accounts)]
+pub struct SetCrankerBps<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> SetCrankerBps<'info> {
+    pub fn set_cranker_bps(&mut self, cranker_bps: u16) -> Result<()> {
+        require!(cranker_bps <= Config::MAX_FEE_BPS, EscrowError::FeeTooHigh);
+
+        self.config.cranker_bps = cranker_bps;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::{Config, FeeTier}};
+
+//Adds a tier for `mint`, or replaces the fee of an existing one
+#[derive(Accounts)]
+pub struct SetFeeTier<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> SetFeeTier<'info> {
+    pub fn set_fee_tier(&mut self, mint: Pubkey, fee_bps: u16) -> Result<()> {
+        require!(fee_bps <= Config::MAX_FEE_BPS, EscrowError::FeeTooHigh);
+
+        let tiers = &mut self.config.fee_tiers;
+
+        if let Some(tier) = tiers.iter_mut().find(|tier| tier.mint == mint) {
+            tier.fee_bps = fee_bps;
+            return Ok(());
+        }
+
+        require!(tiers.len() < Config::MAX_FEE_TIERS, EscrowError::FeeTiersFull);
+
+        tiers.push(FeeTier { mint, fee_bps });
+
+        Ok(())
+    }
+}
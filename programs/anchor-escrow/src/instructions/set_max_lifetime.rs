@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Config};
+
+#[derive(Accounts)]
+pub struct SetMaxLifetime<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> SetMaxLifetime<'info> {
+    //Only checked at Make, escrows already open keep their expiry
+    pub fn set_max_lifetime(&mut self, max_lifetime: i64) -> Result<()> {
+        require!(max_lifetime >= 0, EscrowError::InvalidLifetime);
+
+        self.config.max_lifetime = max_lifetime;
+
+        Ok(())
+    }
+}
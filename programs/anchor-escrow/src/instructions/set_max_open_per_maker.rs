@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::{Config, MakerIndex}};
+
+#[derive(Accounts)]
+pub struct SetMaxOpenPerMaker<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> SetMaxOpenPerMaker<'info> {
+    //Lowering it below what a maker has open only blocks their new makes until they drop under it
+    pub fn set_max_open_per_maker(&mut self, max_open_per_maker: u16) -> Result<()> {
+        require!(max_open_per_maker as usize <= MakerIndex::MAX_OPEN_ESCROWS, EscrowError::InvalidOpenEscrowLimit);
+
+        self.config.max_open_per_maker = max_open_per_maker;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::state::{Config, PausedMint};
+
+//The PausedMint account is created on the first pause and kept for later toggles
+#[derive(Accounts)]
+pub struct SetMintPaused<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"paused", mint.key().as_ref()],
+        bump,
+        space = 8 + PausedMint::INIT_SPACE,
+    )]
+    pub paused_mint: Account<'info, PausedMint>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetMintPaused<'info> {
+    pub fn set_mint_paused(&mut self, paused: bool, bumps: &SetMintPausedBumps) -> Result<()> {
+        self.paused_mint.set_inner(PausedMint {
+            mint: self.mint.key(),
+            paused,
+            bump: bumps.paused_mint,
+        });
+
+        Ok(())
+    }
+}
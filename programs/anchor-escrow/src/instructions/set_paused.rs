@@ -0,0 +1,23 @@
+use anchor_lang::prelude::*;
+
+use crate::state::Config;
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> SetPaused<'info> {
+    pub fn set_paused(&mut self, paused: bool) -> Result<()> {
+        self.config.paused = paused;
+
+        Ok(())
+    }
+}
\ No newline at end of file
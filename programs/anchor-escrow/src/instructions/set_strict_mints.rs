@@ -0,0 +1,23 @@
+use anchor_lang::prelude::*;
+
+use crate::state::Config;
+
+#[derive(Accounts)]
+pub struct SetStrictMints<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> SetStrictMints<'info> {
+    pub fn set_strict_mints(&mut self, reject_freezable_mints: bool) -> Result<()> {
+        self.config.reject_freezable_mints = reject_freezable_mints;
+
+        Ok(())
+    }
+}
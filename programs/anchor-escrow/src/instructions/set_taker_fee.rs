@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Config};
+
+#[derive(Accounts)]
+pub struct SetTakerFee<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, Config>,
+}
+
+impl<'info> SetTakerFee<'info> {
+    pub fn set_taker_fee(&mut self, taker_fee_bps: u16) -> Result<()> {
+        require!(taker_fee_bps <= Config::MAX_FEE_BPS, EscrowError::FeeTooHigh);
+
+        self.config.taker_fee_bps = taker_fee_bps;
+
+        Ok(())
+    }
+}
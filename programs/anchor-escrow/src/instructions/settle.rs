@@ -0,0 +1,214 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, CloseAccount, close_account}};
+
+use crate::{close::close_escrow, error::EscrowError, events::EscrowTaken, pricing::effective_receive, state::{Config, Escrow, MakerIndex, StagedTake}};
+
+#[derive(Accounts)]
+pub struct Settle<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = taker,
+    )]
+    pub taker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_b,
+        associated_token::authority = maker,
+    )]
+    pub maker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = mint_a @ EscrowError::InvalidMint,
+        has_one = mint_b @ EscrowError::InvalidMint,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"index", maker.key().as_ref()],
+        bump = maker_index.bump,
+    )]
+    pub maker_index: Box<Account<'info, MakerIndex>>,
+    #[account(
+        mut,
+        close = taker,
+        has_one = taker @ EscrowError::UnauthorizedTaker,
+        has_one = escrow,
+        seeds = [b"stage", escrow.key().as_ref()],
+        bump = staged_take.bump,
+    )]
+    pub staged_take: Box<Account<'info, StagedTake>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = staged_take,
+    )]
+    pub take_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ EscrowError::ProgramPaused,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    #[account(address = config.authority)]
+    pub fee_authority: SystemAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_b,
+        associated_token::authority = fee_authority,
+    )]
+    pub fee_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+//Release staged mint_b to the maker and fee vault
+//Release the whole vault to the taker
+//Close both vaults and the escrow; the stage is closed by its constraint
+impl<'info> Settle<'info> {
+    pub fn check_settleable(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(!escrow.is_expired(now), EscrowError::EscrowExpired);
+        require!(!self.staged_take.is_past_deadline(now), EscrowError::TakeDeadlinePassed);
+        require!(self.staged_take.amount >= effective_receive(&escrow, now)?, EscrowError::StagedAmountMismatch);
+
+        Ok(())
+    }
+
+    pub fn release_take_vault(&mut self) -> Result<()> {
+        let fee = self.config.fee_for(&self.mint_b.key(), self.staged_take.amount)?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"stage",
+            self.staged_take.escrow.as_ref(),
+            &[self.staged_take.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.take_vault.to_account_info(),
+            to: self.maker_ata_b.to_account_info(),
+            authority: self.staged_take.to_account_info(),
+            mint: self.mint_b.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, self.staged_take.amount.checked_sub(fee).ok_or(EscrowError::MathOverflow)?, self.mint_b.decimals)?;
+
+        if fee > 0 {
+            let cpi_program = self.token_program.to_account_info();
+
+            let cpi_accounts = TransferChecked {
+                from: self.take_vault.to_account_info(),
+                to: self.fee_vault.to_account_info(),
+                authority: self.staged_take.to_account_info(),
+                mint: self.mint_b.to_account_info(),
+            };
+
+            let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+            transfer_checked(cpi_context, fee, self.mint_b.decimals)?;
+        }
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: self.take_vault.to_account_info(),
+            destination: self.taker.to_account_info(),
+            authority: self.staged_take.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)
+    }
+
+    pub fn withdraw_and_close_vault(&mut self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            to: self.taker_ata_a.to_account_info(),
+            authority: self.escrow.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, self.vault.amount, self.mint_a.decimals)?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.maker.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)
+    }
+
+    pub fn emit_taken(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        emit!(EscrowTaken {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            taker: self.taker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: self.mint_b.key(),
+            deposit: self.vault.amount,
+            receive: self.staged_take.amount,
+            fee: self.config.fee_for(&self.mint_b.key(), self.staged_take.amount)?,
+            taker_fee: 0,
+            seed: escrow.seed,
+        });
+
+        Ok(())
+    }
+
+    pub fn unindex(&mut self) {
+        self.maker_index.remove(&self.escrow.key());
+    }
+
+    pub fn close_escrow(&self) -> Result<()> {
+        close_escrow(&self.escrow.to_account_info(), &self.maker.to_account_info())
+    }
+}
@@ -0,0 +1,184 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}};
+
+use crate::{error::EscrowError, events::EscrowMade, state::{Config, Escrow, EscrowStatus, MakerIndex}, transfer::received_since};
+
+//Moves `amount` of the vault and the matching share of receive into a new escrow under `new_seed`
+//Both halves keep the original terms, so each prices a take exactly as the unsplit escrow would
+#[derive(Accounts)]
+#[instruction(new_seed: u64)]
+pub struct SplitEscrow<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(mint::token_program = token_program)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        has_one = mint_a @ EscrowError::InvalidMint,
+        has_one = maker @ EscrowError::InvalidMaker,
+        constraint = !escrow.load()?.is_basket() @ EscrowError::BasketNotSupported,
+        constraint = !escrow.load()?.has_tip() @ EscrowError::TipNotSupported,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: address is pinned by seeds, it only has to be empty
+    #[account(
+        seeds = [b"stage", escrow.key().as_ref()],
+        bump,
+    )]
+    pub staged_take: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = maker,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), maker.key().as_ref(), new_seed.to_le_bytes().as_ref()],
+        bump,
+        space = 8 + Escrow::INIT_SPACE,
+    )]
+    pub new_escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_a,
+        associated_token::authority = new_escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub new_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = maker,
+        seeds = [b"index", maker.key().as_ref()],
+        bump,
+        space = 8 + MakerIndex::INIT_SPACE,
+    )]
+    pub maker_index: Box<Account<'info, MakerIndex>>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SplitEscrow<'info> {
+    //A staged take was priced against the whole vault
+    pub fn check_no_stage(&self) -> Result<()> {
+        require!(self.staged_take.data_is_empty(), EscrowError::StagedTakeActive);
+
+        Ok(())
+    }
+
+    //Returns the receive carried over to the new escrow
+    //`received` is what move_tokens landed in the new vault, so the new escrow records that instead of `amount`
+    pub fn split(&mut self, new_seed: u64, amount: u64, received: u64, bumps: &SplitEscrowBumps) -> Result<u64> {
+        require!(amount > 0, EscrowError::InvalidAmount);
+        require!(amount < self.vault.amount, EscrowError::InvalidAmount);
+        require!(received > 0, EscrowError::InvalidAmount);
+
+        let mut escrow = self.escrow.load_mut()?;
+        let vault_amount = self.vault.amount;
+
+        //The new escrow rounds down, the original keeps the remainder
+        let share = |value: u64| -> Result<u64> {
+            let part = (value as u128)
+                .checked_mul(amount as u128)
+                .ok_or(EscrowError::MathOverflow)?
+                / vault_amount as u128;
+
+            u64::try_from(part).map_err(|_| error!(EscrowError::MathOverflow))
+        };
+
+        let receive = share(escrow.receive)?;
+        let start_receive = share(escrow.start_receive)?;
+        let floor_receive = share(escrow.floor_receive)?;
+        require!(receive > 0, EscrowError::InvalidAmount);
+
+        *self.new_escrow.load_init()? = Escrow {
+            seed: new_seed,
+            maker: escrow.maker,
+            seed_maker: self.maker.key(),
+            receive,
+            deposit: received,
+            start_receive,
+            floor_receive,
+            min_fill: escrow.min_fill.min(received),
+            nonce: 0,
+            bump: bumps.new_escrow,
+            //Nothing has been taken from the new half yet
+            status: EscrowStatus::Open as u8,
+            status_before_stage: EscrowStatus::Open as u8,
+            ..*escrow
+        };
+
+        escrow.receive = escrow.receive.checked_sub(receive).ok_or(EscrowError::MathOverflow)?;
+        escrow.deposit = escrow.deposit.saturating_sub(amount);
+        escrow.start_receive = escrow.start_receive.checked_sub(start_receive).ok_or(EscrowError::MathOverflow)?;
+        escrow.floor_receive = escrow.floor_receive.checked_sub(floor_receive).ok_or(EscrowError::MathOverflow)?;
+        escrow.min_fill = escrow.min_fill.min(vault_amount - amount);
+
+        Ok(receive)
+    }
+
+    pub fn index_escrow(&mut self, bumps: &SplitEscrowBumps) -> Result<()> {
+        self.maker_index.maker = self.maker.key();
+        self.maker_index.bump = bumps.maker_index;
+
+        self.maker_index.push(self.new_escrow.key(), self.config.open_escrow_limit())
+    }
+
+    //Returns what the new vault gained, which is less than `amount` under a Token-2022 transfer fee
+    pub fn move_tokens(&mut self, amount: u64) -> Result<u64> {
+        let escrow = *self.escrow.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            to: self.new_vault.to_account_info(),
+            authority: self.escrow.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        let new_vault_before = self.new_vault.amount;
+        transfer_checked(cpi_context, amount, self.mint_a.decimals)?;
+
+        received_since(&mut self.new_vault, new_vault_before)
+    }
+
+    //The new escrow discriminator is only written on exit, so read the original instead
+    pub fn emit_made(&self, new_seed: u64, amount: u64, receive: u64) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        emit!(EscrowMade {
+            escrow: self.new_escrow.key(),
+            maker: self.maker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: escrow.mint_b,
+            deposit: amount,
+            receive,
+            seed: new_seed,
+        });
+
+        Ok(())
+    }
+}
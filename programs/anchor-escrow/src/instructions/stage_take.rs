@@ -0,0 +1,103 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}};
+
+use crate::{error::EscrowError, pricing::effective_receive, state::{Config, Escrow, StagedTake}};
+
+#[derive(Accounts)]
+pub struct StageTake<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+    pub maker: SystemAccount<'info>,
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = taker,
+    )]
+    pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = mint_b @ EscrowError::InvalidMint,
+        constraint = !escrow.load()?.is_basket() @ EscrowError::BasketNotSupported,
+        constraint = !escrow.load()?.has_tip() @ EscrowError::TipNotSupported,
+        constraint = !escrow.load()?.has_settlement_hook() @ EscrowError::SettlementHookNotSupported,
+        constraint = !escrow.load()?.has_oracle() @ EscrowError::OracleNotSupported,
+        constraint = !escrow.load()?.has_royalty() @ EscrowError::RoyaltyNotSupported,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        init,
+        payer = taker,
+        seeds = [b"stage", escrow.key().as_ref()],
+        bump,
+        space = 8 + StagedTake::INIT_SPACE,
+    )]
+    pub staged_take: Box<Account<'info, StagedTake>>,
+    #[account(
+        init,
+        payer = taker,
+        associated_token::mint = mint_b,
+        associated_token::authority = staged_take,
+    )]
+    pub take_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ EscrowError::ProgramPaused,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> StageTake<'info> {
+    pub fn check_takeable(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(!escrow.is_expired(now), EscrowError::EscrowExpired);
+        require!(escrow.can_be_taken_by(self.taker.key), EscrowError::UnauthorizedTaker);
+
+        Ok(())
+    }
+
+    //take_window is how long the taker has to settle, 0 = no deadline
+    pub fn stage(&mut self, take_window: i64, bumps: &StageTakeBumps) -> Result<()> {
+        require!(take_window >= 0, EscrowError::InvalidAmount);
+
+        let escrow = *self.escrow.load()?;
+        self.escrow.load_mut()?.record_stage();
+
+        let now = Clock::get()?.unix_timestamp;
+        let amount = effective_receive(&escrow, now)?;
+        let take_deadline = match take_window {
+            0 => 0,
+            window => now.checked_add(window).ok_or(EscrowError::MathOverflow)?,
+        };
+
+        self.staged_take.set_inner(StagedTake {
+            escrow: self.escrow.key(),
+            taker: self.taker.key(),
+            amount,
+            take_deadline,
+            bump: bumps.staged_take,
+        });
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.taker_ata_b.to_account_info(),
+            to: self.take_vault.to_account_info(),
+            authority: self.taker.to_account_info(),
+            mint: self.mint_b.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        transfer_checked(cpi_ctx, amount, self.mint_b.decimals)
+    }
+}
\ No newline at end of file
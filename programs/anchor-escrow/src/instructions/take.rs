@@ -1,53 +1,158 @@
-use anchor_lang::prelude::*;
-use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, CloseAccount, close_account}};
+use anchor_lang::{prelude::*, solana_program::program::set_return_data, system_program};
+use anchor_spl::{associated_token::{self, get_associated_token_address_with_program_id, AssociatedToken, Create}, token::spl_token::native_mint, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, CloseAccount, close_account}};
 
-use crate::state::Escrow;
+use crate::{close::close_escrow, error::EscrowError, events::{EscrowPartiallyFilled, EscrowTaken}, settlement_hook::{invoke_settlement_hook, SettlementHookArgs}, state::{Config, Escrow, MakerIndex, OraclePrice, PausedMint, Receipt}, transfer::{gross_for, net_of, verify_received}};
 
 //Create context
+//maker is pinned to the escrow by has_one and mint_b must be a mint the escrow accepts, so the maker_ata_b checks bind it to escrow.maker and that mint
 #[derive(Accounts)]
 pub struct Take<'info> {
     #[account(mut)]
     pub taker: Signer<'info>,
     #[account(mut)]
     pub maker: SystemAccount<'info>,
-    pub mint_a: InterfaceAccount<'info, Mint>,
-    pub mint_b: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_program)]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mint::token_program = token_program)]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         init_if_needed,
         payer = taker,
         associated_token::mint = mint_a,
         associated_token::authority = taker,
+        associated_token::token_program = token_program,
+        constraint = taker_ata_a.owner == taker.key() @ EscrowError::InvalidTokenAccount,
+        constraint = taker_ata_a.mint == mint_a.key() @ EscrowError::InvalidTokenAccount,
     )]
-    pub taker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    pub taker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         associated_token::mint = mint_b,
         associated_token::authority = taker,
+        associated_token::token_program = token_program,
+        constraint = taker_ata_b.owner == taker.key() @ EscrowError::InvalidTokenAccount,
+        constraint = taker_ata_b.mint == mint_b.key() @ EscrowError::InvalidTokenAccount,
     )]
-    pub taker_ata_b: InterfaceAccount<'info, TokenAccount>,
+    pub taker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         init_if_needed,
         payer = taker,
         associated_token::mint = mint_b,
         associated_token::authority = maker,
+        associated_token::token_program = token_program,
+        constraint = maker_ata_b.owner == maker.key() @ EscrowError::InvalidTokenAccount,
+        constraint = maker_ata_b.mint == mint_b.key() @ EscrowError::InvalidTokenAccount,
     )]
-    pub maker_ata_b: InterfaceAccount<'info, TokenAccount>,
+    pub maker_ata_b: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
-        close = maker,
-        has_one = maker,
-        has_one = mint_a,
-        has_one = mint_b,
-        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
-        bump = escrow.bump,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = mint_a @ EscrowError::InvalidMint,
+        constraint = escrow.load()?.accepts_receive_mint(&mint_b.key()) @ EscrowError::InvalidMint,
+        constraint = !escrow.load()?.is_staged() @ EscrowError::EscrowStaged,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
     )]
-    pub escrow: Account<'info, Escrow>,
+    pub escrow: AccountLoader<'info, Escrow>,
     #[account(
         mut,
         associated_token::mint = mint_a,
         associated_token::authority = escrow,
+        associated_token::token_program = token_program,
     )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    //mint_a2, taker_ata_a2 and vault_a2 are only passed for basket escrows
+    #[account(mint::token_program = token_program)]
+    pub mint_a2: Option<Box<InterfaceAccount<'info, Mint>>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a2,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program,
+    )]
+    pub taker_ata_a2: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a2,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_a2: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    //Only needed when the escrow has a royalty
+    #[account(
+        mut,
+        constraint = royalty_ata_b.owner == escrow.load()?.royalty_recipient @ EscrowError::InvalidTokenAccount,
+        constraint = royalty_ata_b.mint == mint_b.key() @ EscrowError::InvalidTokenAccount,
+    )]
+    pub royalty_ata_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    //Where the maker's mint_b goes instead of maker_ata_b, e.g. a treasury
+    //The maker must own it; a delegate is set by the account's owner, so naming the maker as one proves nothing
+    #[account(
+        mut,
+        constraint = maker_receive_account.mint == mint_b.key() @ EscrowError::InvalidTokenAccount,
+        constraint = maker_receive_account.owner == maker.key() @ EscrowError::InvalidTokenAccount,
+    )]
+    pub maker_receive_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// CHECK: only needed when the escrow unwraps SOL, must be the escrow's wSOL ATA and is created and closed here
+    #[account(mut)]
+    pub sol_unwrap: Option<UncheckedAccount<'info>>,
+    /// CHECK: only needed when the escrow is priced by an oracle, must be the feed named at Make and is parsed as OraclePrice
+    #[account(address = escrow.load()?.oracle @ EscrowError::InvalidOracle)]
+    pub oracle: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [b"index", escrow.load()?.maker.as_ref()],
+        bump = maker_index.bump,
+    )]
+    pub maker_index: Box<Account<'info, MakerIndex>>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ EscrowError::ProgramPaused,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    /// CHECK: address is pinned by seeds, only read through PausedMint::is_paused
+    #[account(
+        seeds = [b"paused", mint_a.key().as_ref()],
+        bump,
+        constraint = !PausedMint::is_paused(&paused_mint_a) @ EscrowError::MintPaused,
+    )]
+    pub paused_mint_a: UncheckedAccount<'info>,
+    /// CHECK: address is pinned by seeds, only read through PausedMint::is_paused
+    #[account(
+        seeds = [b"paused", mint_b.key().as_ref()],
+        bump,
+        constraint = !PausedMint::is_paused(&paused_mint_b) @ EscrowError::MintPaused,
+    )]
+    pub paused_mint_b: UncheckedAccount<'info>,
+    #[account(address = config.authority)]
+    pub fee_authority: SystemAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_b,
+        associated_token::authority = fee_authority,
+        associated_token::token_program = token_program,
+    )]
+    pub fee_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = fee_authority,
+        associated_token::token_program = token_program,
+    )]
+    pub fee_vault_a: Box<InterfaceAccount<'info, TokenAccount>>,
+    //The taker pays its rent, a fixed Receipt::INIT_SPACE on their first fill of this escrow
+    #[account(
+        init_if_needed,
+        payer = taker,
+        seeds = [b"receipt", escrow.key().as_ref(), taker.key().as_ref()],
+        bump,
+        space = 8 + Receipt::INIT_SPACE,
+    )]
+    pub receipt: Box<Account<'info, Receipt>>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
@@ -55,31 +160,287 @@ pub struct Take<'info> {
 
 //Deposit tokens from taker to maker
 //Transfer tokens from vault to taker
-//Close vault account
+//Close vault and escrow once the vault is empty
 impl<'info> Take<'info> {
-    pub fn deposit(&mut self) -> Result<()> {
+    #[cfg(feature = "debug")]
+    pub fn log_pdas(&self) {
+        msg!("escrow: {}, vault: {}", self.escrow.key(), self.vault.key());
+    }
+
+    pub fn check_expiry(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(!escrow.is_expired(now), EscrowError::EscrowExpired);
+
+        Ok(())
+    }
+
+    pub fn check_taker(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        require!(escrow.can_be_taken_by(self.taker.key), EscrowError::UnauthorizedTaker);
+
+        Ok(())
+    }
+
+    //Guards against the vault holding less than the escrow recorded
+    pub fn check_vault(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let funded = escrow.deposit.checked_add(escrow.taker_tip).ok_or(EscrowError::MathOverflow)?;
+        require!(self.vault.amount >= funded, EscrowError::InsufficientVaultBalance);
+
+        Ok(())
+    }
+
+    //receive was quoted in the decimals the maker saw at make time
+    pub fn check_decimals(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        require!(self.mint_b.decimals == escrow.receive_decimals, EscrowError::DecimalsMismatch);
+
+        Ok(())
+    }
+
+    //Baskets release both vaults together, so they can only be taken in full
+    pub fn check_basket(&self, take_amount: u64) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        if !escrow.is_basket() {
+            return Ok(());
+        }
+
+        let (Some(mint_a2), Some(vault_a2)) = (&self.mint_a2, &self.vault_a2) else {
+            return err!(EscrowError::BasketAccountsMissing);
+        };
+        require!(self.taker_ata_a2.is_some(), EscrowError::BasketAccountsMissing);
+        require_keys_eq!(mint_a2.key(), escrow.mint_a2, EscrowError::InvalidMint);
+        require!(vault_a2.amount >= escrow.deposit_a2, EscrowError::InsufficientVaultBalance);
+        require!(take_amount == self.vault.amount, EscrowError::BasketPartialFill);
+
+        Ok(())
+    }
+
+    //(mint_b owed, receive consumed) for `take_amount` of the vault
+    pub fn fill_for(&self, take_amount: u64) -> Result<(u64, u64)> {
+        let escrow = *self.escrow.load()?;
+
+        let fillable = escrow.fillable(self.vault.amount);
+
+        require!(take_amount > 0, EscrowError::InvalidAmount);
+        require!(take_amount <= fillable, EscrowError::PartialFillTooLarge);
+        if take_amount < fillable {
+            require!(!escrow.is_all_or_nothing(), EscrowError::PartialFillNotAllowed);
+            require!(take_amount >= escrow.min_fill, EscrowError::FillTooSmall);
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+
+        escrow.fill(take_amount, fillable, now)
+    }
+
+    //An oracle escrow charges the feed price instead of its receive, `receive_amount` still sets how much receive is consumed
+    pub fn oracle_receive_for(&self, take_amount: u64, receive_amount: u64) -> Result<u64> {
+        let escrow = *self.escrow.load()?;
+
+        if !escrow.has_oracle() {
+            return Ok(receive_amount);
+        }
+
+        let Some(oracle) = &self.oracle else {
+            return err!(EscrowError::OracleAccountMissing);
+        };
+        let feed = OraclePrice::load(oracle).ok_or(EscrowError::InvalidOracle)?;
+
+        let now = Clock::get()?.unix_timestamp;
+
+        escrow.oracle_receive(&feed, take_amount, now)
+    }
+
+    //Share of the taker tip paid out with `take_amount`, read before consume lowers it
+    pub fn tip_for(&self, take_amount: u64) -> Result<u64> {
+        let escrow = *self.escrow.load()?;
+
+        escrow.tip_for(take_amount, escrow.fillable(self.vault.amount))
+    }
+
+    //The price can move between quote and execution through decay or UpdateOffer
+    pub fn check_slippage(&self, receive_amount: u64, max_receive: u64) -> Result<()> {
+        require!(receive_amount <= max_receive, EscrowError::SlippageExceeded);
+
+        Ok(())
+    }
+
+    //The royalty comes out of the maker's share, the taker pays the same either way
+    pub fn deposit(&mut self, receive_amount: u64) -> Result<()> {
+        let fee = self.config.fee_for(&self.mint_b.key(), receive_amount)?;
+        let maker_amount = receive_amount.checked_sub(fee).ok_or(EscrowError::MathOverflow)?;
+        let royalty = self.escrow.load()?.royalty_for(maker_amount)?;
+        let gross = gross_for(&self.mint_b, maker_amount.checked_sub(royalty).ok_or(EscrowError::MathOverflow)?)?;
+
+        if self.escrow.load()?.unwraps_sol() && self.mint_b.key() == native_mint::ID {
+            self.deposit_unwrapped(gross)?;
+        } else {
+            let maker_destination = match &mut self.maker_receive_account {
+                Some(maker_receive_account) => maker_receive_account,
+                None => &mut self.maker_ata_b,
+            };
+
+            let cpi_program = self.token_program.to_account_info();
+
+            let cpi_accounts = TransferChecked {
+                from: self.taker_ata_b.to_account_info(),
+                to: maker_destination.to_account_info(),
+                authority: self.taker.to_account_info(),
+                mint: self.mint_b.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+            let maker_before = maker_destination.amount;
+            transfer_checked(cpi_ctx, gross, self.mint_b.decimals)?;
+            let expected = net_of(&self.mint_b, gross)?;
+            verify_received(maker_destination, maker_before, expected)?;
+        }
+
+        if royalty > 0 {
+            let Some(royalty_ata_b) = &self.royalty_ata_b else {
+                return err!(EscrowError::RoyaltyAccountMissing);
+            };
+
+            let cpi_program = self.token_program.to_account_info();
+
+            let cpi_accounts = TransferChecked {
+                from: self.taker_ata_b.to_account_info(),
+                to: royalty_ata_b.to_account_info(),
+                authority: self.taker.to_account_info(),
+                mint: self.mint_b.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+            transfer_checked(cpi_ctx, gross_for(&self.mint_b, royalty)?, self.mint_b.decimals)?;
+        }
+
+        if fee > 0 {
+            let cpi_program = self.token_program.to_account_info();
+
+            let cpi_accounts = TransferChecked {
+                from: self.taker_ata_b.to_account_info(),
+                to: self.fee_vault.to_account_info(),
+                authority: self.taker.to_account_info(),
+                mint: self.mint_b.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+            transfer_checked(cpi_ctx, fee, self.mint_b.decimals)?;
+        }
+
+        Ok(())
+    }
+
+    //The maker never signs a take, so their wSOL ATA can't be closed here
+    //Instead the taker's wSOL goes through the escrow's own wSOL ATA, which is closed back to the taker,
+    //and the taker pays the maker the same amount in lamports; the taker ends up with its rent back
+    fn deposit_unwrapped(&mut self, amount: u64) -> Result<()> {
+        let Some(sol_unwrap) = &self.sol_unwrap else {
+            return err!(EscrowError::UnwrapAccountMissing);
+        };
+        require_keys_eq!(
+            sol_unwrap.key(),
+            get_associated_token_address_with_program_id(&self.escrow.key(), &native_mint::ID, &self.token_program.key()),
+            EscrowError::UnwrapAccountMissing
+        );
+
+        let cpi_program = self.associated_token_program.to_account_info();
+
+        let cpi_accounts = Create {
+            payer: self.taker.to_account_info(),
+            associated_token: sol_unwrap.to_account_info(),
+            authority: self.escrow.to_account_info(),
+            mint: self.mint_b.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+        };
+
+        associated_token::create_idempotent(CpiContext::new(cpi_program, cpi_accounts))?;
+
         let cpi_program = self.token_program.to_account_info();
 
         let cpi_accounts = TransferChecked {
             from: self.taker_ata_b.to_account_info(),
-            to: self.maker_ata_b.to_account_info(),
+            to: sol_unwrap.to_account_info(),
             authority: self.taker.to_account_info(),
             mint: self.mint_b.to_account_info(),
         };
 
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
 
-        transfer_checked(cpi_ctx, self.escrow.receive, self.mint_b.decimals)
+        transfer_checked(cpi_ctx, amount, self.mint_b.decimals)?;
+
+        let escrow = *self.escrow.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: sol_unwrap.to_account_info(),
+            destination: self.taker.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)?;
+
+        let cpi_program = self.system_program.to_account_info();
+
+        let cpi_accounts = system_program::Transfer {
+            from: self.taker.to_account_info(),
+            to: self.maker.to_account_info(),
+        };
+
+        system_program::transfer(CpiContext::new(cpi_program, cpi_accounts), amount)
     }
 
-    pub fn withdraw_and_close_vault(&mut self) -> Result<()> {
+    pub fn consume(&mut self, consumed: u64, take_amount: u64, tip: u64) -> Result<()> {
+        let mut escrow = self.escrow.load_mut()?;
+        escrow.receive = escrow.receive.checked_sub(consumed).ok_or(EscrowError::MathOverflow)?;
+        escrow.deposit = escrow.deposit.saturating_sub(take_amount);
+        escrow.taker_tip = escrow.taker_tip.checked_sub(tip).ok_or(EscrowError::MathOverflow)?;
+        //A full take closes the escrow right after, so this only shows for partial fills
+        escrow.record_partial_fill();
+        //Baskets are only taken in full, so the whole second leg goes too
+        escrow.deposit_a2 = 0;
+
+        Ok(())
+    }
+
+    //The tip rides along with the taker's transfer and is not charged the taker fee
+    pub fn withdraw(&mut self, take_amount: u64, tip: u64) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
         let signer_seeds: [&[&[u8]]; 1] = [&[
             b"escrow",
-            self.maker.key.as_ref(),
-            &self.escrow.seed.to_le_bytes()[..],
-            &[self.escrow.bump]
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
         ]];
 
+        let taker_fee = self.config.taker_fee_for(take_amount)?;
+
         let cpi_program = self.token_program.to_account_info();
 
         let cpi_accounts = TransferChecked {
@@ -91,12 +452,71 @@ impl<'info> Take<'info> {
 
         let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
 
-        transfer_checked(cpi_context, self.vault.amount, self.mint_a.decimals)?;
+        let sent = take_amount
+            .checked_sub(taker_fee)
+            .and_then(|net| net.checked_add(tip))
+            .ok_or(EscrowError::MathOverflow)?;
+        let taker_before = self.taker_ata_a.amount;
+        transfer_checked(cpi_context, sent, self.mint_a.decimals)?;
+        let expected = net_of(&self.mint_a, sent)?;
+        verify_received(&mut self.taker_ata_a, taker_before, expected)?;
+
+        if taker_fee > 0 {
+            let cpi_program = self.token_program.to_account_info();
+
+            let cpi_accounts = TransferChecked {
+                from: self.vault.to_account_info(),
+                to: self.fee_vault_a.to_account_info(),
+                authority: self.escrow.to_account_info(),
+                mint: self.mint_a.to_account_info(),
+            };
+
+            let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+            transfer_checked(cpi_context, taker_fee, self.mint_a.decimals)?;
+        }
+
+        self.vault.reload()?;
+
+        Ok(())
+    }
+
+    pub fn withdraw_basket(&mut self) -> Result<()> {
+        let (Some(mint_a2), Some(taker_ata_a2), Some(vault_a2)) = (&self.mint_a2, &self.taker_ata_a2, &self.vault_a2) else {
+            return Ok(());
+        };
+
+        let escrow = *self.escrow.load()?;
+        if !escrow.is_basket() {
+            return Ok(());
+        }
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: vault_a2.to_account_info(),
+            to: taker_ata_a2.to_account_info(),
+            authority: self.escrow.to_account_info(),
+            mint: mint_a2.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, vault_a2.amount, mint_a2.decimals)?;
 
         let cpi_program = self.token_program.to_account_info();
 
         let cpi_accounts = CloseAccount {
-            account: self.vault.to_account_info(),
+            account: vault_a2.to_account_info(),
             destination: self.maker.to_account_info(),
             authority: self.escrow.to_account_info(),
         };
@@ -105,4 +525,131 @@ impl<'info> Take<'info> {
 
         close_account(cpi_context)
     }
-}
\ No newline at end of file
+
+    //Runs after both legs have moved, so the hook sees the settled balances
+    pub fn call_settlement_hook(&self, remaining_accounts: &[AccountInfo], take_amount: u64, receive_amount: u64) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+        if !escrow.has_settlement_hook() {
+            return Ok(());
+        }
+
+        let args = SettlementHookArgs {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            taker: self.taker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: self.mint_b.key(),
+            take_amount,
+            receive_amount,
+            seed: escrow.seed,
+        };
+
+        invoke_settlement_hook(&escrow.settlement_hook, remaining_accounts, &args)
+    }
+
+    pub fn unindex_if_filled(&mut self) {
+        if self.vault.amount > 0 {
+            return;
+        }
+
+        self.maker_index.remove(&self.escrow.key());
+    }
+
+    pub fn close_if_filled(&mut self) -> Result<()> {
+        if self.vault.amount > 0 {
+            return Ok(());
+        }
+
+        let escrow = *self.escrow.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.maker.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)?;
+
+        close_escrow(&self.escrow.to_account_info(), &self.maker.to_account_info())
+    }
+
+    pub fn emit_taken(&self, take_amount: u64, receive_amount: u64) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        emit!(EscrowTaken {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            taker: self.taker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: self.mint_b.key(),
+            deposit: take_amount,
+            receive: receive_amount,
+            fee: self.config.fee_for(&self.mint_b.key(), receive_amount)?,
+            taker_fee: self.config.taker_fee_for(take_amount)?,
+            seed: escrow.seed,
+        });
+
+        Ok(())
+    }
+
+    pub fn emit_partial_fill(&self, take_amount: u64) -> Result<()> {
+        if self.vault.amount == 0 {
+            return Ok(());
+        }
+
+        let escrow = *self.escrow.load()?;
+
+        emit!(EscrowPartiallyFilled {
+            escrow: self.escrow.key(),
+            taker: self.taker.key(),
+            filled: take_amount,
+            remaining_deposit: escrow.fillable(self.vault.amount),
+            remaining_receive: escrow.receive,
+            seed: escrow.seed,
+        });
+
+        Ok(())
+    }
+
+    //Borsh (amount_a_out, amount_b_in) for CPI callers: mint_a the taker received after the taker fee and with the tip, mint_b they paid
+    //Set last so no later CPI can overwrite it
+    pub fn record_receipt(&mut self, take_amount: u64, receive_amount: u64, tip: u64, bumps: &TakeBumps) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        self.receipt.reset_if_stale(self.escrow.key(), &escrow, self.taker.key(), self.mint_b.key(), bumps.receipt);
+
+        let amount_a_out = self.amount_a_out(take_amount, tip)?;
+        let now = Clock::get()?.unix_timestamp;
+
+        self.receipt.record(amount_a_out, receive_amount, now)
+    }
+
+    //mint_a the taker ends up with: the fill less the taker fee, plus the tip
+    fn amount_a_out(&self, take_amount: u64, tip: u64) -> Result<u64> {
+        take_amount
+            .checked_sub(self.config.taker_fee_for(take_amount)?)
+            .and_then(|net| net.checked_add(tip))
+            .ok_or(error!(EscrowError::MathOverflow))
+    }
+
+    pub fn set_take_return(&self, take_amount: u64, receive_amount: u64, tip: u64) -> Result<()> {
+        let amount_a_out = self.amount_a_out(take_amount, tip)?;
+
+        set_return_data(&(amount_a_out, receive_amount).try_to_vec()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+use anchor_lang::{error::ErrorCode, prelude::*, Discriminator};
+use anchor_spl::{associated_token::{self, get_associated_token_address_with_program_id, AssociatedToken, Create}, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, CloseAccount, close_account}};
+
+use crate::{close::close_escrow, create::create_program_account, error::EscrowError, events::EscrowTaken, pricing::effective_receive, state::{Config, Escrow, MakerIndex, PausedMint, Receipt}, transfer::{gross_for, net_of, verify_received}};
+
+//maker, mint_a, mint_b, taker_ata_a, taker_ata_b, maker_ata_b, escrow, vault, fee_vault, fee_vault_a, paused_mint_a, paused_mint_b, maker_index, receipt
+//fee_vault_a is only created when the config charges a taker fee
+pub const ACCOUNTS_PER_TAKE: usize = 14;
+
+#[derive(Accounts)]
+pub struct TakeMany<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ EscrowError::ProgramPaused,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(address = config.authority)]
+    pub fee_authority: SystemAccount<'info>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+//Fully take every escrow described in remaining_accounts
+//Any failing swap aborts the whole transaction
+impl<'info> TakeMany<'info> {
+    pub fn take_all(&mut self, remaining_accounts: &'info [AccountInfo<'info>], max_receives: &[u64]) -> Result<()> {
+        require!(
+            !remaining_accounts.is_empty() && remaining_accounts.len() == max_receives.len() * ACCOUNTS_PER_TAKE,
+            EscrowError::InvalidRemainingAccounts
+        );
+
+        for (accounts, max_receive) in remaining_accounts.chunks(ACCOUNTS_PER_TAKE).zip(max_receives) {
+            self.take_one(accounts, *max_receive)?;
+            self.config.record_take()?;
+        }
+
+        Ok(())
+    }
+
+    fn take_one(&self, accounts: &'info [AccountInfo<'info>], max_receive: u64) -> Result<()> {
+        let [maker, mint_a, mint_b, taker_ata_a, taker_ata_b, maker_ata_b, escrow, vault, fee_vault, fee_vault_a, paused_mint_a, paused_mint_b, maker_index, receipt] = accounts else {
+            return err!(EscrowError::InvalidRemainingAccounts);
+        };
+
+        let escrow_loader = AccountLoader::<Escrow>::try_from(escrow)?;
+        let escrow_data = *escrow_loader.load()?;
+        let mint_a_data = InterfaceAccount::<Mint>::try_from(mint_a)?;
+        let mint_b_data = InterfaceAccount::<Mint>::try_from(mint_b)?;
+        let vault_data = InterfaceAccount::<TokenAccount>::try_from(vault)?;
+        let mut maker_index_data = Account::<MakerIndex>::try_from(maker_index)?;
+
+        //Same checks the Take context enforces through constraints
+        let escrow_key = Pubkey::create_program_address(
+            &[b"escrow", Escrow::namespace_seed(&escrow_data.namespace), escrow_data.seed_maker.as_ref(), &escrow_data.seed.to_le_bytes(), Escrow::nonce_seed(&escrow_data.nonce), &[escrow_data.bump]],
+            &crate::ID,
+        ).map_err(|_| error!(ErrorCode::ConstraintSeeds))?;
+        require_keys_eq!(escrow_key, escrow.key(), ErrorCode::ConstraintSeeds);
+        require_keys_eq!(escrow_data.maker, maker.key(), EscrowError::InvalidMaker);
+        let maker_index_key = Pubkey::create_program_address(
+            &[b"index", maker.key().as_ref(), &[maker_index_data.bump]],
+            &crate::ID,
+        ).map_err(|_| error!(ErrorCode::ConstraintSeeds))?;
+        require_keys_eq!(maker_index_key, maker_index.key(), ErrorCode::ConstraintSeeds);
+        require_keys_eq!(escrow_data.mint_a, mint_a.key(), EscrowError::InvalidMint);
+        require_keys_eq!(escrow_data.mint_b, mint_b.key(), EscrowError::InvalidMint);
+
+        let token_program = self.token_program.key();
+        require_keys_eq!(
+            vault.key(),
+            get_associated_token_address_with_program_id(&escrow.key(), &mint_a.key(), &token_program),
+            ErrorCode::AccountNotAssociatedTokenAccount
+        );
+        require_keys_eq!(
+            maker_ata_b.key(),
+            get_associated_token_address_with_program_id(&maker.key(), &mint_b.key(), &token_program),
+            ErrorCode::AccountNotAssociatedTokenAccount
+        );
+        require_keys_eq!(
+            fee_vault.key(),
+            get_associated_token_address_with_program_id(&self.fee_authority.key(), &mint_b.key(), &token_program),
+            ErrorCode::AccountNotAssociatedTokenAccount
+        );
+        require_keys_eq!(
+            fee_vault_a.key(),
+            get_associated_token_address_with_program_id(&self.fee_authority.key(), &mint_a.key(), &token_program),
+            ErrorCode::AccountNotAssociatedTokenAccount
+        );
+
+        let (paused_a_address, _) = Pubkey::find_program_address(&[b"paused", mint_a.key().as_ref()], &crate::ID);
+        let (paused_b_address, _) = Pubkey::find_program_address(&[b"paused", mint_b.key().as_ref()], &crate::ID);
+        require_keys_eq!(paused_mint_a.key(), paused_a_address, EscrowError::InvalidRemainingAccounts);
+        require_keys_eq!(paused_mint_b.key(), paused_b_address, EscrowError::InvalidRemainingAccounts);
+        require!(!PausedMint::is_paused(paused_mint_a), EscrowError::MintPaused);
+        require!(!PausedMint::is_paused(paused_mint_b), EscrowError::MintPaused);
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(!escrow_data.is_expired(now), EscrowError::EscrowExpired);
+        require!(escrow_data.can_be_taken_by(self.taker.key), EscrowError::UnauthorizedTaker);
+        require!(!escrow_data.is_staged(), EscrowError::EscrowStaged);
+
+        require!(!escrow_data.is_basket(), EscrowError::BasketNotSupported);
+        require!(!escrow_data.has_tip(), EscrowError::TipNotSupported);
+        require!(!escrow_data.has_settlement_hook(), EscrowError::SettlementHookNotSupported);
+        require!(!escrow_data.has_oracle(), EscrowError::OracleNotSupported);
+        require!(!escrow_data.has_royalty(), EscrowError::RoyaltyNotSupported);
+        require!(vault_data.amount >= escrow_data.deposit, EscrowError::InsufficientVaultBalance);
+        require!(mint_b_data.decimals == escrow_data.receive_decimals, EscrowError::DecimalsMismatch);
+
+        let take_amount = vault_data.amount;
+        let receive_amount = effective_receive(&escrow_data, now)?;
+        //Same as Take, decay or UpdateOffer can move the price after the taker signed
+        require!(receive_amount <= max_receive, EscrowError::SlippageExceeded);
+        let fee = self.config.fee_for(&mint_b.key(), receive_amount)?;
+        let taker_fee = self.config.taker_fee_for(take_amount)?;
+
+        //Grossed up like Take so a Token-2022 transfer fee doesn't come out of the maker's share
+        let gross = gross_for(&mint_b_data, receive_amount.checked_sub(fee).ok_or(EscrowError::MathOverflow)?)?;
+        let mut maker_ata_b_data = InterfaceAccount::<TokenAccount>::try_from(maker_ata_b)?;
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: taker_ata_b.clone(),
+            to: maker_ata_b.clone(),
+            authority: self.taker.to_account_info(),
+            mint: mint_b.clone(),
+        };
+
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        let maker_before = maker_ata_b_data.amount;
+        transfer_checked(cpi_ctx, gross, mint_b_data.decimals)?;
+        verify_received(&mut maker_ata_b_data, maker_before, net_of(&mint_b_data, gross)?)?;
+
+        if fee > 0 {
+            let cpi_program = self.token_program.to_account_info();
+
+            let cpi_accounts = TransferChecked {
+                from: taker_ata_b.clone(),
+                to: fee_vault.clone(),
+                authority: self.taker.to_account_info(),
+                mint: mint_b.clone(),
+            };
+
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+            transfer_checked(cpi_ctx, fee, mint_b_data.decimals)?;
+        }
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow_data.namespace),
+            escrow_data.seed_maker.as_ref(),
+            &escrow_data.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow_data.nonce),
+            &[escrow_data.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: vault.clone(),
+            to: taker_ata_a.clone(),
+            authority: escrow.clone(),
+            mint: mint_a.clone(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        transfer_checked(cpi_context, take_amount.checked_sub(taker_fee).ok_or(EscrowError::MathOverflow)?, mint_a_data.decimals)?;
+
+        if taker_fee > 0 {
+            let cpi_program = self.associated_token_program.to_account_info();
+
+            let cpi_accounts = Create {
+                payer: self.taker.to_account_info(),
+                associated_token: fee_vault_a.clone(),
+                authority: self.fee_authority.to_account_info(),
+                mint: mint_a.clone(),
+                system_program: self.system_program.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+            };
+
+            associated_token::create_idempotent(CpiContext::new(cpi_program, cpi_accounts))?;
+
+            let cpi_program = self.token_program.to_account_info();
+
+            let cpi_accounts = TransferChecked {
+                from: vault.clone(),
+                to: fee_vault_a.clone(),
+                authority: escrow.clone(),
+                mint: mint_a.clone(),
+            };
+
+            let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+            transfer_checked(cpi_context, taker_fee, mint_a_data.decimals)?;
+        }
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: vault.clone(),
+            destination: maker.clone(),
+            authority: escrow.clone(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)?;
+
+        let amount_a_out = take_amount.checked_sub(taker_fee).ok_or(EscrowError::MathOverflow)?;
+        self.record_receipt(receipt, escrow, &escrow_data, amount_a_out, receive_amount)?;
+
+        emit!(EscrowTaken {
+            escrow: escrow.key(),
+            maker: maker.key(),
+            taker: self.taker.key(),
+            mint_a: mint_a.key(),
+            mint_b: mint_b.key(),
+            deposit: take_amount,
+            receive: receive_amount,
+            fee,
+            taker_fee,
+            seed: escrow_data.seed,
+        });
+
+        maker_index_data.remove(&escrow.key());
+        maker_index_data.exit(&crate::ID)?;
+
+        close_escrow(escrow, maker)
+    }
+
+    //Same receipt Take leaves; the taker may already have one from an earlier partial fill
+    fn record_receipt(&self, receipt: &'info AccountInfo<'info>, escrow: &AccountInfo<'info>, escrow_data: &Escrow, amount_a: u64, amount_b: u64) -> Result<()> {
+        let escrow_key = escrow.key();
+        let taker = self.taker.key();
+        let (receipt_address, bump) = Pubkey::find_program_address(&[b"receipt", escrow_key.as_ref(), taker.as_ref()], &crate::ID);
+        require_keys_eq!(receipt.key(), receipt_address, EscrowError::InvalidRemainingAccounts);
+
+        if *receipt.owner != crate::ID {
+            create_program_account(
+                &self.taker.to_account_info(),
+                receipt,
+                8 + Receipt::INIT_SPACE,
+                &[b"receipt", escrow_key.as_ref(), taker.as_ref(), &[bump]],
+                &self.system_program.to_account_info(),
+            )?;
+
+            //All-zero fields deserialize fine, reset_if_stale fills them in below
+            receipt.try_borrow_mut_data()?[..8].copy_from_slice(Receipt::DISCRIMINATOR);
+        }
+
+        let mut receipt_data = Account::<Receipt>::try_from(receipt)?;
+        receipt_data.reset_if_stale(escrow_key, escrow_data, taker, escrow_data.mint_b, bump);
+        let now = Clock::get()?.unix_timestamp;
+        receipt_data.record(amount_a, amount_b, now)?;
+
+        receipt_data.exit(&crate::ID)
+    }
+}
\ No newline at end of file
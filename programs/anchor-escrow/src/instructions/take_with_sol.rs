@@ -0,0 +1,320 @@
+use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
+use anchor_spl::{associated_token::AssociatedToken, token::spl_token::native_mint, token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked, CloseAccount, close_account}};
+
+use crate::{close::close_escrow, error::EscrowError, events::{EscrowPartiallyFilled, EscrowTaken}, state::{Config, Escrow, MakerIndex, PausedMint, Receipt}};
+
+//Same as Take, but for escrows whose mint_b is wrapped SOL. The taker pays
+//in native lamports sent straight to the maker, so neither side needs a wSOL ATA.
+#[derive(Accounts)]
+pub struct TakeWithSol<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = native_mint::ID @ EscrowError::NotNativeMint)]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = taker,
+    )]
+    pub taker_ata_a: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        has_one = maker @ EscrowError::InvalidMaker,
+        has_one = mint_a @ EscrowError::InvalidMint,
+        has_one = mint_b @ EscrowError::InvalidMint,
+        constraint = !escrow.load()?.is_staged() @ EscrowError::EscrowStaged,
+        constraint = !escrow.load()?.is_basket() @ EscrowError::BasketNotSupported,
+        constraint = !escrow.load()?.has_tip() @ EscrowError::TipNotSupported,
+        constraint = !escrow.load()?.has_settlement_hook() @ EscrowError::SettlementHookNotSupported,
+        constraint = !escrow.load()?.has_oracle() @ EscrowError::OracleNotSupported,
+        constraint = !escrow.load()?.has_royalty() @ EscrowError::RoyaltyNotSupported,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"index", maker.key().as_ref()],
+        bump = maker_index.bump,
+    )]
+    pub maker_index: Box<Account<'info, MakerIndex>>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        constraint = !config.paused @ EscrowError::ProgramPaused,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    /// CHECK: address is pinned by seeds, only read through PausedMint::is_paused
+    #[account(
+        seeds = [b"paused", mint_a.key().as_ref()],
+        bump,
+        constraint = !PausedMint::is_paused(&paused_mint_a) @ EscrowError::MintPaused,
+    )]
+    pub paused_mint_a: UncheckedAccount<'info>,
+    /// CHECK: address is pinned by seeds, only read through PausedMint::is_paused
+    #[account(
+        seeds = [b"paused", mint_b.key().as_ref()],
+        bump,
+        constraint = !PausedMint::is_paused(&paused_mint_b) @ EscrowError::MintPaused,
+    )]
+    pub paused_mint_b: UncheckedAccount<'info>,
+    #[account(mut, address = config.authority)]
+    pub fee_authority: SystemAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_a,
+        associated_token::authority = fee_authority,
+    )]
+    pub fee_vault_a: Box<InterfaceAccount<'info, TokenAccount>>,
+    //Same receipt Take leaves, so a taker's fills add up whichever route they use
+    #[account(
+        init_if_needed,
+        payer = taker,
+        seeds = [b"receipt", escrow.key().as_ref(), taker.key().as_ref()],
+        bump,
+        space = 8 + Receipt::INIT_SPACE,
+    )]
+    pub receipt: Box<Account<'info, Receipt>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TakeWithSol<'info> {
+    pub fn check_expiry(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(!escrow.is_expired(now), EscrowError::EscrowExpired);
+
+        Ok(())
+    }
+
+    pub fn check_taker(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        require!(escrow.can_be_taken_by(self.taker.key), EscrowError::UnauthorizedTaker);
+
+        Ok(())
+    }
+
+    //Guards against the vault holding less than the escrow recorded
+    pub fn check_vault(&self) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        require!(self.vault.amount >= escrow.deposit, EscrowError::InsufficientVaultBalance);
+
+        Ok(())
+    }
+
+    //(lamports owed, receive consumed) for `take_amount` of the vault
+    pub fn fill_for(&self, take_amount: u64) -> Result<(u64, u64)> {
+        let escrow = *self.escrow.load()?;
+
+        require!(take_amount > 0, EscrowError::InvalidAmount);
+        require!(take_amount <= self.vault.amount, EscrowError::PartialFillTooLarge);
+        if take_amount < self.vault.amount {
+            require!(!escrow.is_all_or_nothing(), EscrowError::PartialFillNotAllowed);
+            require!(take_amount >= escrow.min_fill, EscrowError::FillTooSmall);
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+
+        escrow.fill(take_amount, self.vault.amount, now)
+    }
+
+    //The price can move between quote and execution through decay or UpdateOffer
+    pub fn check_slippage(&self, receive_amount: u64, max_receive: u64) -> Result<()> {
+        require!(receive_amount <= max_receive, EscrowError::SlippageExceeded);
+
+        Ok(())
+    }
+
+    pub fn deposit(&mut self, receive_amount: u64) -> Result<()> {
+        let fee = self.config.fee_for(&self.mint_b.key(), receive_amount)?;
+
+        let cpi_program = self.system_program.to_account_info();
+
+        let cpi_accounts = Transfer {
+            from: self.taker.to_account_info(),
+            to: self.maker.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        transfer(cpi_ctx, receive_amount.checked_sub(fee).ok_or(EscrowError::MathOverflow)?)?;
+
+        if fee > 0 {
+            let cpi_program = self.system_program.to_account_info();
+
+            let cpi_accounts = Transfer {
+                from: self.taker.to_account_info(),
+                to: self.fee_authority.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+            transfer(cpi_ctx, fee)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn consume(&mut self, consumed: u64, take_amount: u64) -> Result<()> {
+        let mut escrow = self.escrow.load_mut()?;
+        escrow.receive = escrow.receive.checked_sub(consumed).ok_or(EscrowError::MathOverflow)?;
+        escrow.deposit = escrow.deposit.saturating_sub(take_amount);
+        //A full take closes the escrow right after, so this only shows for partial fills
+        escrow.record_partial_fill();
+
+        Ok(())
+    }
+
+    pub fn withdraw(&mut self, take_amount: u64) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            to: self.taker_ata_a.to_account_info(),
+            authority: self.escrow.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        let taker_fee = self.config.taker_fee_for(take_amount)?;
+        transfer_checked(cpi_context, take_amount.checked_sub(taker_fee).ok_or(EscrowError::MathOverflow)?, self.mint_a.decimals)?;
+
+        if taker_fee > 0 {
+            let cpi_program = self.token_program.to_account_info();
+
+            let cpi_accounts = TransferChecked {
+                from: self.vault.to_account_info(),
+                to: self.fee_vault_a.to_account_info(),
+                authority: self.escrow.to_account_info(),
+                mint: self.mint_a.to_account_info(),
+            };
+
+            let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+            transfer_checked(cpi_context, taker_fee, self.mint_a.decimals)?;
+        }
+
+        self.vault.reload()?;
+
+        Ok(())
+    }
+
+    //Records the mint_a the taker kept after the taker fee, same as Take
+    pub fn record_receipt(&mut self, take_amount: u64, receive_amount: u64, bumps: &TakeWithSolBumps) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        self.receipt.reset_if_stale(self.escrow.key(), &escrow, self.taker.key(), self.mint_b.key(), bumps.receipt);
+
+        let now = Clock::get()?.unix_timestamp;
+
+        let amount_a_out = take_amount.checked_sub(self.config.taker_fee_for(take_amount)?).ok_or(EscrowError::MathOverflow)?;
+
+        self.receipt.record(amount_a_out, receive_amount, now)
+    }
+
+    pub fn unindex_if_filled(&mut self) {
+        if self.vault.amount > 0 {
+            return;
+        }
+
+        self.maker_index.remove(&self.escrow.key());
+    }
+
+    pub fn close_if_filled(&mut self) -> Result<()> {
+        if self.vault.amount > 0 {
+            return Ok(());
+        }
+
+        let escrow = *self.escrow.load()?;
+
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"escrow",
+            Escrow::namespace_seed(&escrow.namespace),
+            escrow.seed_maker.as_ref(),
+            &escrow.seed.to_le_bytes()[..],
+            Escrow::nonce_seed(&escrow.nonce),
+            &[escrow.bump]
+        ]];
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.maker.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_accounts, &signer_seeds);
+
+        close_account(cpi_context)?;
+
+        close_escrow(&self.escrow.to_account_info(), &self.maker.to_account_info())
+    }
+
+    pub fn emit_taken(&self, take_amount: u64, receive_amount: u64) -> Result<()> {
+        let escrow = *self.escrow.load()?;
+
+        emit!(EscrowTaken {
+            escrow: self.escrow.key(),
+            maker: self.maker.key(),
+            taker: self.taker.key(),
+            mint_a: self.mint_a.key(),
+            mint_b: self.mint_b.key(),
+            deposit: take_amount,
+            receive: receive_amount,
+            fee: self.config.fee_for(&self.mint_b.key(), receive_amount)?,
+            taker_fee: self.config.taker_fee_for(take_amount)?,
+            seed: escrow.seed,
+        });
+
+        Ok(())
+    }
+
+    pub fn emit_partial_fill(&self, take_amount: u64) -> Result<()> {
+        if self.vault.amount == 0 {
+            return Ok(());
+        }
+
+        let escrow = *self.escrow.load()?;
+
+        emit!(EscrowPartiallyFilled {
+            escrow: self.escrow.key(),
+            taker: self.taker.key(),
+            filled: take_amount,
+            remaining_deposit: self.vault.amount,
+            remaining_receive: escrow.receive,
+            seed: escrow.seed,
+        });
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,60 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{error::EscrowError, state::Escrow, transfer::received_since};
+
+#[derive(Accounts)]
+pub struct TopUp<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+    )]
+    pub maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        has_one = mint_a @ EscrowError::InvalidMint,
+        has_one = maker @ EscrowError::InvalidMaker,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> TopUp<'info> {
+    pub fn deposit(&mut self, deposit: u64) -> Result<()> {
+        require!(deposit > 0, EscrowError::InvalidAmount);
+
+        let cpi_program = self.token_program.to_account_info();
+
+        let cpi_accounts = TransferChecked {
+            from: self.maker_ata_a.to_account_info(),
+            to: self.vault.to_account_info(),
+            authority: self.maker.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        let vault_before = self.vault.amount;
+        transfer_checked(cpi_ctx, deposit, self.mint_a.decimals)?;
+
+        //A Token-2022 transfer fee lands less than `deposit`, so only what the vault gained is recorded
+        let received = received_since(&mut self.vault, vault_before)?;
+
+        let mut escrow = self.escrow.load_mut()?;
+        escrow.deposit = escrow.deposit.checked_add(received).ok_or(EscrowError::MathOverflow)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::{Config, Escrow, MakerIndex}};
+
+//The PDA stays derived from seed_maker, only the stored owner changes
+//The escrow moves to the new maker's index, which the old maker pays for if it doesn't exist yet
+#[derive(Accounts)]
+#[instruction(new_maker: Pubkey)]
+pub struct TransferMaker<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(
+        mut,
+        has_one = maker @ EscrowError::InvalidMaker,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+    #[account(
+        mut,
+        seeds = [b"index", maker.key().as_ref()],
+        bump = maker_index.bump,
+    )]
+    pub maker_index: Box<Account<'info, MakerIndex>>,
+    #[account(
+        init_if_needed,
+        payer = maker,
+        seeds = [b"index", new_maker.as_ref()],
+        bump,
+        space = 8 + MakerIndex::INIT_SPACE,
+    )]
+    pub new_maker_index: Box<Account<'info, MakerIndex>>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TransferMaker<'info> {
+    pub fn transfer_maker(&mut self, new_maker: Pubkey) -> Result<()> {
+        require!(new_maker != Pubkey::default(), EscrowError::InvalidMaker);
+
+        let mut escrow = self.escrow.load_mut()?;
+        escrow.maker = new_maker;
+        //The refund authority was the old maker's delegate, the new one can name their own by remaking
+        escrow.refund_authority = Pubkey::default();
+
+        Ok(())
+    }
+
+    //Transferring to oneself leaves both indexes as the same account, so the entry stays put
+    pub fn move_index(&mut self, new_maker: Pubkey, bumps: &TransferMakerBumps) -> Result<()> {
+        if new_maker == self.maker.key() {
+            return Ok(());
+        }
+
+        self.maker_index.remove(&self.escrow.key());
+
+        self.new_maker_index.maker = new_maker;
+        self.new_maker_index.bump = bumps.new_maker_index;
+
+        self.new_maker_index.push(self.escrow.key(), self.config.open_escrow_limit())
+    }
+}
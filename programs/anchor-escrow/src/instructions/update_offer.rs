@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Escrow};
+
+#[derive(Accounts)]
+pub struct UpdateOffer<'info> {
+    pub maker: Signer<'info>,
+    #[account(
+        mut,
+        has_one = maker @ EscrowError::InvalidMaker,
+        constraint = !escrow.load()?.is_staged() @ EscrowError::EscrowStaged,
+        seeds = [b"escrow", Escrow::namespace_seed(&escrow.load()?.namespace), escrow.load()?.seed_maker.as_ref(), escrow.load()?.seed.to_le_bytes().as_ref(), Escrow::nonce_seed(&escrow.load()?.nonce)],
+        bump = escrow.load()?.bump,
+    )]
+    pub escrow: AccountLoader<'info, Escrow>,
+}
+
+impl<'info> UpdateOffer<'info> {
+    pub fn update_receive(&mut self, new_receive: u64) -> Result<()> {
+        require!(new_receive > 0, EscrowError::InvalidAmount);
+
+        self.escrow.load_mut()?.receive = new_receive;
+
+        Ok(())
+    }
+}
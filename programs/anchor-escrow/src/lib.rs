@@ -5,7 +5,16 @@ use anchor_lang::prelude::*;
 
 mod state;
 mod instructions;
+mod error;
+mod events;
+mod close;
+mod create;
+mod transfer;
+pub mod pricing;
+pub mod settlement_hook;
 mod tests;
+#[cfg(any(test, feature = "client"))]
+pub mod client;
 
 use instructions::*;
 
@@ -15,17 +24,290 @@ declare_id!("FircrADQ2wgGuvpm8qneNCfKM7o5zoHTWnDQxngpTQ3J");
 pub mod anchor_escrow {
     use super::*;
 
-    pub fn make(ctx: Context<Make>, seed: u64, deposit: u64, receive: u64) -> Result<()> {
-        ctx.accounts.init_escrow(seed, receive, &ctx.bumps)?;
+    pub fn init_config(ctx: Context<InitConfig>, fee_bps: u16) -> Result<()> {
+        ctx.accounts.init_config(fee_bps, &ctx.bumps)
+    }
+
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        ctx.accounts.set_paused(paused)
+    }
+
+    pub fn set_config_authority(ctx: Context<SetConfigAuthority>, new_authority: Pubkey) -> Result<()> {
+        ctx.accounts.set_config_authority(new_authority)
+    }
+
+    pub fn set_strict_mints(ctx: Context<SetStrictMints>, reject_freezable_mints: bool) -> Result<()> {
+        ctx.accounts.set_strict_mints(reject_freezable_mints)
+    }
+
+    pub fn set_mint_paused(ctx: Context<SetMintPaused>, paused: bool) -> Result<()> {
+        ctx.accounts.set_mint_paused(paused, &ctx.bumps)
+    }
+
+    //Ignores expiry, cooldown and pause; escrows with a staged take have to go through CancelEscrow
+    pub fn admin_force_refund(ctx: Context<AdminForceRefund>) -> Result<()> {
+        ctx.accounts.check_no_stage()?;
+        ctx.accounts.refund_and_close_vault()?;
+        ctx.accounts.emit_refunded()?;
+        ctx.accounts.unindex();
+        ctx.accounts.record_refund()?;
+        ctx.accounts.close_escrow()
+    }
+
+    //Only accounts outside the escrow's deposit mints, the vaults are never swept
+    pub fn admin_rescue(ctx: Context<AdminRescue>) -> Result<()> {
+        ctx.accounts.rescue()
+    }
+
+    pub fn set_taker_fee(ctx: Context<SetTakerFee>, taker_fee_bps: u16) -> Result<()> {
+        ctx.accounts.set_taker_fee(taker_fee_bps)
+    }
+
+    pub fn set_cranker_bps(ctx: Context<SetCrankerBps>, cranker_bps: u16) -> Result<()> {
+        ctx.accounts.set_cranker_bps(cranker_bps)
+    }
+
+    //Seconds, 0 = no cap
+    pub fn set_max_lifetime(ctx: Context<SetMaxLifetime>, max_lifetime: i64) -> Result<()> {
+        ctx.accounts.set_max_lifetime(max_lifetime)
+    }
+
+    pub fn set_max_open_per_maker(ctx: Context<SetMaxOpenPerMaker>, max_open_per_maker: u16) -> Result<()> {
+        ctx.accounts.set_max_open_per_maker(max_open_per_maker)
+    }
+
+    pub fn add_allowed_mint(ctx: Context<AddAllowedMint>, mint: Pubkey) -> Result<()> {
+        ctx.accounts.add_allowed_mint(mint)
+    }
+
+    pub fn remove_allowed_mint(ctx: Context<RemoveAllowedMint>, mint: Pubkey) -> Result<()> {
+        ctx.accounts.remove_allowed_mint(mint)
+    }
+
+    pub fn set_fee_tier(ctx: Context<SetFeeTier>, mint: Pubkey, fee_bps: u16) -> Result<()> {
+        ctx.accounts.set_fee_tier(mint, fee_bps)
+    }
+
+    pub fn clear_fee_tier(ctx: Context<ClearFeeTier>, mint: Pubkey) -> Result<()> {
+        ctx.accounts.clear_fee_tier(mint)
+    }
+
+    pub fn make(ctx: Context<Make>, args: MakeArgs) -> Result<()> {
+        #[cfg(feature = "debug")]
+        ctx.accounts.log_pdas();
+        ctx.accounts.validate(&args)?;
+        let received = ctx.accounts.deposit(args.vault_amount()?)?;
+        let args = args.with_received(received)?;
+        ctx.accounts.init_escrow(&args, &ctx.bumps)?;
+        ctx.accounts.index_escrow(&ctx.bumps)?;
+        ctx.accounts.emit_made(&args);
+        ctx.accounts.config.record_make()
+    }
+
+    //Make with deposit and receive in whole tokens; args.deposit and args.receive are ignored
+    pub fn make_whole(ctx: Context<Make>, args: MakeArgs, deposit_whole: u64, receive_whole: u64) -> Result<()> {
+        let args = ctx.accounts.scale_whole(args, deposit_whole, receive_whole)?;
+        ctx.accounts.validate(&args)?;
+        let received = ctx.accounts.deposit(args.vault_amount()?)?;
+        let args = args.with_received(received)?;
+        ctx.accounts.init_escrow(&args, &ctx.bumps)?;
+        ctx.accounts.index_escrow(&ctx.bumps)?;
+        ctx.accounts.emit_made(&args);
+        ctx.accounts.config.record_make()
+    }
+
+    pub fn make_basket(ctx: Context<MakeBasket>, args: MakeBasketArgs) -> Result<()> {
+        ctx.accounts.validate(&args)?;
+        let args = ctx.accounts.deposit(args)?;
+        ctx.accounts.init_escrow(&args, &ctx.bumps)?;
+        ctx.accounts.index_escrow(&ctx.bumps)?;
+        ctx.accounts.emit_made(&args);
+        ctx.accounts.config.record_make()
+    }
+
+    //remaining_accounts holds one group of ACCOUNTS_PER_MAKE accounts per order, in this order:
+    //mint_a, mint_b, maker_ata_a (mut), escrow (mut), vault (mut), paused_mint_a, paused_mint_b
+    //Every escrow uses the default namespace and nonce and never expires
+    pub fn make_many<'info>(ctx: Context<'_, '_, 'info, 'info, MakeMany<'info>>, orders: Vec<MakeOrder>) -> Result<()> {
+        ctx.accounts.index_maker(&ctx.bumps);
+        ctx.accounts.make_all(ctx.remaining_accounts, &orders)
+    }
+
+    //close_maker_ata closes maker_ata_a into the maker once the refund leaves it empty
+    pub fn refund(ctx: Context<Refund>, close_maker_ata: bool) -> Result<()> {
+        #[cfg(feature = "debug")]
+        ctx.accounts.log_pdas();
+        ctx.accounts.check_authority()?;
+        ctx.accounts.check_no_stage()?;
+        ctx.accounts.check_cooldown()?;
+        ctx.accounts.refund_and_close_vault()?;
+        ctx.accounts.refund_and_close_basket()?;
+        ctx.accounts.close_maker_ata(close_maker_ata)?;
+        ctx.accounts.emit_refunded()?;
+        ctx.accounts.unindex();
+        ctx.accounts.record_refund()?;
+        ctx.accounts.close_escrow()
+    }
+
+    //taker, mint_b, taker_ata_b and take_vault are only required when a staged take exists
+    pub fn cancel_escrow(ctx: Context<CancelEscrow>) -> Result<()> {
+        ctx.accounts.check_cooldown()?;
+        ctx.accounts.refund_staged_take()?;
+        ctx.accounts.refund_and_close_vault()?;
+        ctx.accounts.emit_refunded()?;
+        ctx.accounts.unindex();
+        ctx.accounts.record_refund()?;
+        ctx.accounts.close_escrow()
+    }
+
+    //Checks, then escrow state, then token CPIs; only the close has to wait for the vault to empty
+    //max_receive caps the receive this take is charged, protocol fee included
+    //A Token-2022 transfer fee on mint_b is grossed up on top, so the taker's balance can drop by more
+    //remaining_accounts is only read when the escrow has a settlement hook:
+    //the hook program first, then whatever accounts the hook takes
+    pub fn take(ctx: Context<Take>, take_amount: u64, max_receive: u64) -> Result<()> {
+        #[cfg(feature = "debug")]
+        ctx.accounts.log_pdas();
+        ctx.accounts.check_expiry()?;
+        ctx.accounts.check_taker()?;
+        ctx.accounts.check_vault()?;
+        ctx.accounts.check_decimals()?;
+        ctx.accounts.check_basket(take_amount)?;
+        let (receive_amount, consumed) = ctx.accounts.fill_for(take_amount)?;
+        let receive_amount = ctx.accounts.oracle_receive_for(take_amount, receive_amount)?;
+        let tip = ctx.accounts.tip_for(take_amount)?;
+        ctx.accounts.check_slippage(receive_amount, max_receive)?;
+        ctx.accounts.consume(consumed, take_amount, tip)?;
+        ctx.accounts.deposit(receive_amount)?;
+        ctx.accounts.withdraw(take_amount, tip)?;
+        ctx.accounts.withdraw_basket()?;
+        ctx.accounts.call_settlement_hook(ctx.remaining_accounts, take_amount, receive_amount)?;
+        ctx.accounts.record_receipt(take_amount, receive_amount, tip, &ctx.bumps)?;
+        ctx.accounts.emit_taken(take_amount, receive_amount)?;
+        ctx.accounts.emit_partial_fill(take_amount)?;
+        ctx.accounts.config.record_take()?;
+        ctx.accounts.unindex_if_filled();
+        ctx.accounts.close_if_filled()?;
+        ctx.accounts.set_take_return(take_amount, receive_amount, tip)
+    }
+
+    //remaining_accounts holds one group of ACCOUNTS_PER_TAKE accounts per escrow, in this order:
+    //maker (mut), mint_a, mint_b, taker_ata_a (mut), taker_ata_b (mut), maker_ata_b (mut),
+    //escrow (mut), vault (mut), fee_vault (mut)
+    //Every escrow is taken in full and all token accounts must already exist
+    //max_receives holds one cap per group, checked like Take's max_receive
+    pub fn take_many<'info>(ctx: Context<'_, '_, 'info, 'info, TakeMany<'info>>, max_receives: Vec<u64>) -> Result<()> {
+        ctx.accounts.take_all(ctx.remaining_accounts, &max_receives)
+    }
+
+    //remaining_accounts holds one group of ACCOUNTS_PER_REFUND accounts per escrow, in this order:
+    //mint_a, escrow (mut), vault (mut), maker_ata_a (mut), staged_take
+    //Returns the number of escrows refunded as a Borsh u64 in return data
+    pub fn refund_many<'info>(ctx: Context<'_, '_, 'info, 'info, RefundMany<'info>>, fail_fast: bool) -> Result<()> {
+        ctx.accounts.refund_all(ctx.remaining_accounts, fail_fast)
+    }
+
+    //max_receive caps the lamports this take costs, protocol fee included
+    pub fn take_with_sol(ctx: Context<TakeWithSol>, take_amount: u64, max_receive: u64) -> Result<()> {
+        ctx.accounts.check_expiry()?;
+        ctx.accounts.check_taker()?;
+        ctx.accounts.check_vault()?;
+        let (receive_amount, consumed) = ctx.accounts.fill_for(take_amount)?;
+        ctx.accounts.check_slippage(receive_amount, max_receive)?;
+        ctx.accounts.consume(consumed, take_amount)?;
+        ctx.accounts.deposit(receive_amount)?;
+        ctx.accounts.withdraw(take_amount)?;
+        ctx.accounts.record_receipt(take_amount, receive_amount, &ctx.bumps)?;
+        ctx.accounts.emit_taken(take_amount, receive_amount)?;
+        ctx.accounts.emit_partial_fill(take_amount)?;
+        ctx.accounts.config.record_take()?;
+        ctx.accounts.unindex_if_filled();
+        ctx.accounts.close_if_filled()
+    }
+
+    pub fn stage_take(ctx: Context<StageTake>, take_window: i64) -> Result<()> {
+        ctx.accounts.check_takeable()?;
+        ctx.accounts.stage(take_window, &ctx.bumps)
+    }
+
+    pub fn settle(ctx: Context<Settle>) -> Result<()> {
+        ctx.accounts.check_settleable()?;
+        ctx.accounts.release_take_vault()?;
+        ctx.accounts.withdraw_and_close_vault()?;
+        ctx.accounts.emit_taken()?;
+        ctx.accounts.config.record_take()?;
+        ctx.accounts.unindex();
+        ctx.accounts.close_escrow()
+    }
+
+    pub fn cancel_stage(ctx: Context<CancelStage>) -> Result<()> {
+        ctx.accounts.check_caller()?;
+        ctx.accounts.unstage_escrow()?;
+        ctx.accounts.return_and_close_take_vault()
+    }
+
+    pub fn arbiter_release(ctx: Context<ArbiterRelease>, decision: ArbiterDecision) -> Result<()> {
+        match decision {
+            ArbiterDecision::Settle => {
+                ctx.accounts.force_settle()?;
+                ctx.accounts.config.record_take()?;
+            }
+            ArbiterDecision::Refund => {
+                ctx.accounts.force_refund()?;
+                ctx.accounts.config.record_refund()?;
+            }
+        }
+        ctx.accounts.unindex();
+        ctx.accounts.close_escrow()
+    }
+
+    pub fn reclaim_expired(ctx: Context<ReclaimExpired>) -> Result<()> {
+        ctx.accounts.check_expiry()?;
+        ctx.accounts.check_no_stage()?;
+        ctx.accounts.reclaim_and_close_vault()?;
+        ctx.accounts.record_refund()?;
+        ctx.accounts.unindex();
+        ctx.accounts.close_escrow()
+    }
+
+    pub fn update_offer(ctx: Context<UpdateOffer>, new_receive: u64) -> Result<()> {
+        ctx.accounts.update_receive(new_receive)
+    }
+
+    pub fn transfer_maker(ctx: Context<TransferMaker>, new_maker: Pubkey) -> Result<()> {
+        ctx.accounts.transfer_maker(new_maker)?;
+        ctx.accounts.move_index(new_maker, &ctx.bumps)
+    }
+
+    pub fn top_up(ctx: Context<TopUp>, deposit: u64) -> Result<()> {
         ctx.accounts.deposit(deposit)
     }
 
-    pub fn refund(ctx: Context<Refund>) -> Result<()> {
-        ctx.accounts.refund_and_close_vault()
+    pub fn split_escrow(ctx: Context<SplitEscrow>, new_seed: u64, amount: u64) -> Result<()> {
+        ctx.accounts.check_no_stage()?;
+        let received = ctx.accounts.move_tokens(amount)?;
+        let receive = ctx.accounts.split(new_seed, amount, received, &ctx.bumps)?;
+        ctx.accounts.index_escrow(&ctx.bumps)?;
+        ctx.accounts.emit_made(new_seed, received, receive)
+    }
+
+    pub fn merge_escrows(ctx: Context<MergeEscrows>) -> Result<()> {
+        ctx.accounts.check_no_stage()?;
+        ctx.accounts.check_pricing()?;
+        ctx.accounts.check_terms()?;
+        ctx.accounts.merge()?;
+        ctx.accounts.move_and_close_other_vault()?;
+        ctx.accounts.unindex_other();
+        ctx.accounts.close_other()
+    }
+
+    pub fn quote(ctx: Context<Quote>, take_amount: u64) -> Result<()> {
+        ctx.accounts.emit_quote(take_amount)
     }
 
-    pub fn take(ctx: Context<Take>) -> Result<()> {
-        ctx.accounts.deposit()?;
-        ctx.accounts.withdraw_and_close_vault()
+    pub fn migrate(ctx: Context<Migrate>) -> Result<()> {
+        ctx.accounts.check_version()?;
+        ctx.accounts.fund_rent()?;
+        ctx.accounts.migrate()
     }
 }
\ No newline at end of file
@@ -0,0 +1,39 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Escrow};
+
+//Shared by the handlers and off-chain quotes so both price a take the same way
+
+//mint_b a taker pays right now for the whole remaining vault
+//The protocol fee is carved out of this amount by the caller, not added on top; the maker nets the rest
+pub fn effective_receive(escrow: &Escrow, now: i64) -> Result<u64> {
+    if escrow.decay_end == 0 {
+        return Ok(escrow.receive);
+    }
+
+    let price = escrow.auction_price(now)? as u128;
+    let owed = (escrow.receive as u128)
+        .checked_mul(price)
+        .ok_or(EscrowError::MathOverflow)?
+        .div_ceil(escrow.start_receive as u128);
+
+    u64::try_from(owed).map_err(|_| error!(EscrowError::MathOverflow))
+}
+
+//Part of a mint_b payment routed to the fee vault, the maker gets the rest
+pub fn protocol_fee(amount: u64, fee_bps: u16) -> Result<u64> {
+    let fee = (amount as u128)
+        .checked_mul(fee_bps as u128)
+        .ok_or(EscrowError::MathOverflow)?
+        / 10_000;
+
+    u64::try_from(fee).map_err(|_| error!(EscrowError::MathOverflow))
+}
+
+//`whole` tokens of a mint with `decimals` in base units, for MakeWhole
+pub fn whole_to_base_units(whole: u64, decimals: u8) -> Result<u64> {
+    10u64
+        .checked_pow(decimals as u32)
+        .and_then(|scale| whole.checked_mul(scale))
+        .ok_or(error!(EscrowError::MathOverflow))
+}
@@ -0,0 +1,50 @@
+use anchor_lang::{prelude::*, solana_program::{hash::hash, instruction::{AccountMeta, Instruction}, program::invoke}};
+
+use crate::error::EscrowError;
+
+//What Take sends an escrow's settlement hook after every fill
+//The call is not signed by the escrow, so a hook that must only trust this program should check the instructions sysvar
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SettlementHookArgs {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub take_amount: u64, //mint_a released from the vault, before the taker fee
+    pub receive_amount: u64, //mint_b the taker paid
+    pub seed: u64,
+}
+
+//Anchor's discriminator for an instruction named settlement_hook, so an Anchor hook only has to declare it
+pub fn settlement_hook_discriminator() -> [u8; 8] {
+    let mut discriminator = [0; 8];
+    discriminator.copy_from_slice(&hash(b"global:settlement_hook").to_bytes()[..8]);
+    discriminator
+}
+
+//remaining_accounts[0] is the hook program, the rest are passed on with the flags the transaction gave them
+//Any error from the hook fails the take
+pub fn invoke_settlement_hook(hook: &Pubkey, remaining_accounts: &[AccountInfo], args: &SettlementHookArgs) -> Result<()> {
+    let Some((program, accounts)) = remaining_accounts.split_first() else {
+        return err!(EscrowError::SettlementHookMissing);
+    };
+    require_keys_eq!(program.key(), *hook, EscrowError::SettlementHookMissing);
+    require!(program.executable, EscrowError::SettlementHookMissing);
+
+    let mut data = settlement_hook_discriminator().to_vec();
+    args.serialize(&mut data)?;
+
+    let hook_ix = Instruction {
+        program_id: *hook,
+        accounts: accounts
+            .iter()
+            .map(|account| AccountMeta { pubkey: account.key(), is_signer: account.is_signer, is_writable: account.is_writable })
+            .collect(),
+        data,
+    };
+
+    invoke(&hook_ix, remaining_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use anchor_lang::{prelude::*, solana_program::program_option::COption};
+
+use crate::{error::EscrowError, pricing::protocol_fee, state::MakerIndex};
+
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct Config {
+    pub authority: Pubkey,
+    pub fee_bps: u16, //charged on the mint_b side of every take
+    pub taker_fee_bps: u16, //withheld from the mint_a a taker receives, on every take route
+    pub cranker_bps: u16, //share of an expired vault paid to whoever reclaims it
+    pub paused: bool, //blocks new makes and takes, refunds still work
+    pub reject_freezable_mints: bool, //Make fails for a deposit mint with a freeze authority, off by default
+    pub max_lifetime: i64, //longest expiry Make accepts, in seconds after created_at, 0 = unlimited
+    pub max_open_per_maker: u16, //open escrows one maker's index may list, 0 = MakerIndex::MAX_OPEN_ESCROWS
+    pub total_makes: u64,
+    pub total_takes: u64, //every take instruction, partial fills included
+    pub total_refunds: u64,
+    #[max_len(16)]
+    pub allowed_receive_mints: Vec<Pubkey>, //empty = any mint_b
+    #[max_len(8)]
+    pub fee_tiers: Vec<FeeTier>, //mint_b specific overrides of fee_bps
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct FeeTier {
+    pub mint: Pubkey,
+    pub fee_bps: u16,
+}
+
+impl Config {
+    pub const MAX_FEE_BPS: u16 = 1_000;
+    pub const MAX_ALLOWED_MINTS: usize = 16;
+    pub const MAX_FEE_TIERS: usize = 8;
+
+    //A freeze authority could lock the vault, leaving the deposit unspendable
+    pub fn allows_deposit_mint(&self, freeze_authority: &COption<Pubkey>) -> bool {
+        !self.reject_freezable_mints || freeze_authority.is_none()
+    }
+
+    //With a cap set, an escrow that never expires is over it too
+    pub fn allows_expiry(&self, expiry: i64, now: i64) -> bool {
+        self.max_lifetime == 0 || (expiry != 0 && expiry.saturating_sub(now) <= self.max_lifetime)
+    }
+
+    pub fn open_escrow_limit(&self) -> usize {
+        match self.max_open_per_maker {
+            0 => MakerIndex::MAX_OPEN_ESCROWS,
+            limit => (limit as usize).min(MakerIndex::MAX_OPEN_ESCROWS),
+        }
+    }
+
+    pub fn allows_receive_mint(&self, mint: &Pubkey) -> bool {
+        self.allowed_receive_mints.is_empty() || self.allowed_receive_mints.contains(mint)
+    }
+
+    pub fn record_make(&mut self) -> Result<()> {
+        self.total_makes = self.total_makes.checked_add(1).ok_or(EscrowError::MathOverflow)?;
+
+        Ok(())
+    }
+
+    pub fn record_take(&mut self) -> Result<()> {
+        self.total_takes = self.total_takes.checked_add(1).ok_or(EscrowError::MathOverflow)?;
+
+        Ok(())
+    }
+
+    pub fn record_refund(&mut self) -> Result<()> {
+        self.total_refunds = self.total_refunds.checked_add(1).ok_or(EscrowError::MathOverflow)?;
+
+        Ok(())
+    }
+
+    //fee_bps unless mint_b has its own tier
+    pub fn fee_bps_for(&self, mint_b: &Pubkey) -> u16 {
+        self.fee_tiers
+            .iter()
+            .find(|tier| tier.mint == *mint_b)
+            .map_or(self.fee_bps, |tier| tier.fee_bps)
+    }
+
+    pub fn fee_for(&self, mint_b: &Pubkey, amount: u64) -> Result<u64> {
+        protocol_fee(amount, self.fee_bps_for(mint_b))
+    }
+
+    pub fn taker_fee_for(&self, amount: u64) -> Result<u64> {
+        protocol_fee(amount, self.taker_fee_bps)
+    }
+
+    pub fn cranker_bounty_for(&self, amount: u64) -> Result<u64> {
+        protocol_fee(amount, self.cranker_bps)
+    }
+}
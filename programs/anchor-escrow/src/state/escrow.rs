@@ -1,13 +1,299 @@
 use anchor_lang::prelude::*;
 
-#[account]
+use crate::{error::EscrowError, pricing::{effective_receive, protocol_fee}, state::OraclePrice};
+
+//Fields are ordered so every u64/i64 sits on an 8 byte boundary, keeping the repr(C) layout free of implicit padding
+#[account(zero_copy)]
 #[derive(InitSpace, Debug)]
 pub struct Escrow {
+    pub version: u8, //layout version, see Migrate for upgrading older accounts
+    pub _version_padding: [u8; 7],
     pub seed: u64,
-    pub maker: Pubkey,
+    pub maker: Pubkey, //current owner, receives mint_b and refunds
+    pub seed_maker: Pubkey, //maker the PDA was derived from, kept across TransferMaker
     pub mint_a: Pubkey,
     pub mint_b: Pubkey,
     pub receive: u64,
+    pub deposit: u64, //mint_a the vault must still hold
     pub created_at: i64, //unix timestamp
-    pub bump: u8,
+    pub expiry: i64, //unix timestamp, 0 = never expires
+    pub allowed_taker: Pubkey, //Pubkey::default() = anyone can take
+    pub arbiter: Pubkey, //Pubkey::default() = no arbiter
+    pub start_receive: u64, //dutch auction price at created_at
+    pub floor_receive: u64, //dutch auction price from decay_end on
+    pub decay_end: i64, //unix timestamp, 0 = fixed price
+    pub mint_a2: Pubkey, //second basket mint, Pubkey::default() = single mint escrow
+    pub deposit_a2: u64, //mint_a2 the vault_a2 must still hold
+    pub min_fill: u64, //smallest partial take of mint_a, 0 = any size
+    pub memo: [u8; 32], //free-form maker reference, not interpreted on chain
+    pub bump: u8, //canonical bump from Make, reused by every seeds check and vault signer so none re-derive it
+    pub receive_decimals: u8, //mint_b decimals the maker priced receive in
+    pub all_or_nothing: u8, //1 = partial takes are rejected, stored as u8 to stay Pod
+    pub _padding: [u8; 5],
+    pub refund_cooldown: i64, //seconds after created_at before the maker can refund, 0 = any time
+    pub nonce: u64, //last PDA seed, lets one maker and seed hold several escrows
+    pub royalty_recipient: Pubkey, //paid royalty_bps of the maker's mint_b on every take, kept across TransferMaker
+    pub royalty_bps: u16, //0 = no royalty
+    pub _royalty_padding: [u8; 6],
+    pub namespace: [u8; 8], //first PDA seed after b"escrow", separates escrow books sharing this program
+    pub accepted_receive_mints: [Pubkey; 4], //Take also accepts these in place of mint_b, Pubkey::default() = unused slot
+    pub taker_tip: u64, //mint_a bonus held in the vault on top of deposit, paid out to takers pro rata
+    pub status: u8, //EscrowStatus, stored as u8 to stay Pod
+    pub status_before_stage: u8, //what CancelStage puts back while status is Staged
+    pub unwrap_sol: u8, //1 = Take pays the maker wSOL as native lamports
+    pub rounding: u8, //RoundingMode, taken from padding so older escrows read as MakerFavored
+    pub _status_padding: [u8; 4],
+    pub settlement_hook: Pubkey, //program Take calls after every fill, Pubkey::default() = no hook
+    pub oracle: Pubkey, //OraclePrice feed Take prices fills from instead of receive, Pubkey::default() = fixed terms
+    pub oracle_max_staleness: i64, //seconds a feed update stays usable
+    pub oracle_min_price: u64, //feed prices below this fail the take, in the feed's own units
+    pub oracle_max_price: u64, //feed prices above this fail the take, 0 = no ceiling
+    pub refund_authority: Pubkey, //may sign Refund in the maker's place, Pubkey::default() = the maker alone
+}
+
+//Which way the pro rata receive of a partial fill rounds when it doesn't divide evenly
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RoundingMode {
+    #[default]
+    MakerFavored, //round up, the taker pays the extra base unit
+    TakerFavored, //round down, the maker gives it up
+}
+
+//Closing wipes an escrow's data, so Closed is never read back from an account; client::escrow_status reports it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EscrowStatus {
+    Open,
+    PartiallyFilled,
+    Staged,
+    Closed,
+}
+
+impl Escrow {
+    pub const CURRENT_VERSION: u8 = 12;
+    //Each older layout is the next one without its trailing fields (v1 also lacks the version header)
+    pub const V11_LEN: usize = Self::INIT_SPACE - 32;
+    pub const V10_LEN: usize = Self::V11_LEN - 32 - 8 * 3;
+    pub const V9_LEN: usize = Self::V10_LEN - 32;
+    pub const V8_LEN: usize = Self::V9_LEN - 8;
+    pub const V7_LEN: usize = Self::V8_LEN - 8;
+    pub const V6_LEN: usize = Self::V7_LEN - 32 * 4;
+    pub const V5_LEN: usize = Self::V6_LEN - 8;
+    pub const V4_LEN: usize = Self::V5_LEN - 40;
+    pub const V3_LEN: usize = Self::V4_LEN - 8;
+    pub const V2_LEN: usize = Self::V3_LEN - 8;
+    pub const V1_LEN: usize = Self::V2_LEN - 8;
+
+    //PDA seeds are [b"escrow", namespace, seed_maker, seed, nonce] with nonce as 8 little endian bytes
+    //A zero nonce is left out entirely, so escrows made before nonces keep their address
+    pub fn nonce_seed(nonce: &u64) -> &[u8] {
+        if *nonce == 0 {
+            return &[];
+        }
+
+        bytemuck::bytes_of(nonce)
+    }
+
+    //Same for an all zero namespace, the default book keeps its addresses
+    //It goes first so a namespace can't line up with another escrow's nonce bytes
+    pub fn namespace_seed(namespace: &[u8; 8]) -> &[u8] {
+        if *namespace == [0; 8] {
+            return &[];
+        }
+
+        namespace
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expiry != 0 && now >= self.expiry
+    }
+
+    pub fn is_basket(&self) -> bool {
+        self.mint_a2 != Pubkey::default()
+    }
+
+    //The staged taker has already paid the price at stage time, so nothing may fill or reprice the escrow under them
+    pub fn is_staged(&self) -> bool {
+        self.status() == EscrowStatus::Staged
+    }
+
+    pub fn is_all_or_nothing(&self) -> bool {
+        self.all_or_nothing != 0
+    }
+
+    //Unknown bytes read as Open, which is also what a migrated escrow starts as
+    pub fn status(&self) -> EscrowStatus {
+        match self.status {
+            1 => EscrowStatus::PartiallyFilled,
+            2 => EscrowStatus::Staged,
+            3 => EscrowStatus::Closed,
+            _ => EscrowStatus::Open,
+        }
+    }
+
+    //Takes are rejected while a stage is pending, so this only keeps CancelStage's restore right
+    pub fn record_partial_fill(&mut self) {
+        if self.status() == EscrowStatus::Staged {
+            self.status_before_stage = EscrowStatus::PartiallyFilled as u8;
+        } else {
+            self.status = EscrowStatus::PartiallyFilled as u8;
+        }
+    }
+
+    pub fn record_stage(&mut self) {
+        self.status_before_stage = self.status;
+        self.status = EscrowStatus::Staged as u8;
+    }
+
+    pub fn record_unstage(&mut self) {
+        if self.status() == EscrowStatus::Staged {
+            self.status = self.status_before_stage;
+        }
+    }
+
+    pub fn rounding(&self) -> RoundingMode {
+        if self.rounding == RoundingMode::TakerFavored as u8 {
+            RoundingMode::TakerFavored
+        } else {
+            RoundingMode::MakerFavored
+        }
+    }
+
+    pub fn unwraps_sol(&self) -> bool {
+        self.unwrap_sol != 0
+    }
+
+    pub fn can_refund(&self, signer: &Pubkey) -> bool {
+        *signer == self.maker || (self.refund_authority != Pubkey::default() && *signer == self.refund_authority)
+    }
+
+    pub fn has_oracle(&self) -> bool {
+        self.oracle != Pubkey::default()
+    }
+
+    //mint_b owed for take_amount at the feed's price, rounded in the direction set by `rounding`
+    pub fn oracle_receive(&self, feed: &OraclePrice, take_amount: u64, now: i64) -> Result<u64> {
+        require!(now.saturating_sub(feed.publish_time) <= self.oracle_max_staleness, EscrowError::OracleStale);
+        require!(feed.decimals <= OraclePrice::MAX_DECIMALS, EscrowError::InvalidOracle);
+        require!(feed.price >= self.oracle_min_price, EscrowError::OraclePriceOutOfBounds);
+        require!(self.oracle_max_price == 0 || feed.price <= self.oracle_max_price, EscrowError::OraclePriceOutOfBounds);
+
+        let product = (take_amount as u128)
+            .checked_mul(feed.price as u128)
+            .ok_or(EscrowError::MathOverflow)?;
+        let scale = 10u128.pow(feed.decimals as u32);
+        let receive = match self.rounding() {
+            RoundingMode::MakerFavored => product.div_ceil(scale),
+            RoundingMode::TakerFavored => product / scale,
+        };
+        let receive = u64::try_from(receive).map_err(|_| error!(EscrowError::MathOverflow))?;
+        require!(receive > 0, EscrowError::InvalidAmount);
+
+        Ok(receive)
+    }
+
+    pub fn has_settlement_hook(&self) -> bool {
+        self.settlement_hook != Pubkey::default()
+    }
+
+    pub fn has_tip(&self) -> bool {
+        self.taker_tip != 0
+    }
+
+    //The part of the vault a take can ask for, the rest is the unpaid tip
+    pub fn fillable(&self, vault_amount: u64) -> u64 {
+        vault_amount.saturating_sub(self.taker_tip)
+    }
+
+    //Tip owed for `take_amount` out of `fillable`, rounded down; the take that empties the escrow gets the rest
+    pub fn tip_for(&self, take_amount: u64, fillable: u64) -> Result<u64> {
+        if take_amount == fillable {
+            return Ok(self.taker_tip);
+        }
+
+        let tip = (take_amount as u128)
+            .checked_mul(self.taker_tip as u128)
+            .ok_or(EscrowError::MathOverflow)?
+            / fillable as u128;
+
+        u64::try_from(tip).map_err(|_| error!(EscrowError::MathOverflow))
+    }
+
+    pub fn has_royalty(&self) -> bool {
+        self.royalty_bps != 0
+    }
+
+    //Share of the maker's mint_b proceeds owed to royalty_recipient, rounded down
+    pub fn royalty_for(&self, maker_amount: u64) -> Result<u64> {
+        protocol_fee(maker_amount, self.royalty_bps)
+    }
+
+    pub fn refund_unlocked(&self, now: i64) -> bool {
+        self.created_at.saturating_add(self.refund_cooldown) <= now
+    }
+
+    //Saturates so a corrupt created_at can't overflow; clients call this with any clock they like
+    pub fn age(&self, now: i64) -> i64 {
+        now.saturating_sub(self.created_at)
+    }
+
+    //Auction price for the original deposit, decaying linearly from start_receive to floor_receive
+    pub fn auction_price(&self, now: i64) -> Result<u64> {
+        if now >= self.decay_end {
+            return Ok(self.floor_receive);
+        }
+        if now <= self.created_at {
+            return Ok(self.start_receive);
+        }
+
+        let elapsed = now.checked_sub(self.created_at).ok_or(EscrowError::MathOverflow)? as u128;
+        let duration = self.decay_end.checked_sub(self.created_at).ok_or(EscrowError::MathOverflow)? as u128;
+        let decay = self.start_receive.checked_sub(self.floor_receive).ok_or(EscrowError::MathOverflow)? as u128;
+        let decayed = decay
+            .checked_mul(elapsed)
+            .and_then(|scaled| scaled.checked_div(duration))
+            .ok_or(EscrowError::MathOverflow)?;
+
+        let price = self.start_receive.checked_sub(decayed as u64).ok_or(EscrowError::MathOverflow)?;
+
+        Ok(price)
+    }
+
+    //(mint_b owed now, receive consumed) for `take_amount` out of `vault_amount`, rounded in the direction set by `rounding`
+    pub fn fill(&self, take_amount: u64, vault_amount: u64, now: i64) -> Result<(u64, u64)> {
+        let owed = effective_receive(self, now)?;
+
+        if take_amount == vault_amount {
+            return Ok((owed, self.receive));
+        }
+
+        let rounding = self.rounding();
+        let pro_rata = |amount: u64| -> Result<u64> {
+            let product = (take_amount as u128)
+                .checked_mul(amount as u128)
+                .ok_or(EscrowError::MathOverflow)?;
+            let share = match rounding {
+                RoundingMode::MakerFavored => product.div_ceil(vault_amount as u128),
+                RoundingMode::TakerFavored => product / vault_amount as u128,
+            };
+
+            u64::try_from(share).map_err(|_| error!(EscrowError::MathOverflow))
+        };
+
+        //Rounding down could otherwise hand out mint_a for nothing
+        let owed_share = pro_rata(owed)?;
+        require!(owed_share > 0 || owed == 0, EscrowError::FillTooSmall);
+
+        Ok((owed_share, pro_rata(self.receive)?))
+    }
+
+    //Alternatives are paid 1:1 in base units, so they only work with the same decimals as mint_b
+    pub fn accepts_receive_mint(&self, mint: &Pubkey) -> bool {
+        *mint == self.mint_b || (*mint != Pubkey::default() && self.accepted_receive_mints.contains(mint))
+    }
+
+    pub fn can_be_taken_by(&self, taker: &Pubkey) -> bool {
+        self.allowed_taker == Pubkey::default() || self.allowed_taker == *taker
+    }
 }
\ No newline at end of file
@@ -0,0 +1,31 @@
+use anchor_lang::prelude::*;
+
+use crate::error::EscrowError;
+
+//Open escrows held by one maker, kept by every instruction that opens, closes or transfers an escrow
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct MakerIndex {
+    pub maker: Pubkey, //current maker of every listed escrow
+    #[max_len(32)]
+    pub open_escrows: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl MakerIndex {
+    pub const MAX_OPEN_ESCROWS: usize = 32;
+
+    //limit is Config::open_escrow_limit, never above MAX_OPEN_ESCROWS
+    pub fn push(&mut self, escrow: Pubkey, limit: usize) -> Result<()> {
+        require!(self.open_escrows.len() < limit.min(Self::MAX_OPEN_ESCROWS), EscrowError::TooManyOpenEscrows);
+
+        self.open_escrows.push(escrow);
+
+        Ok(())
+    }
+
+    //Escrows made before the index existed are simply not listed
+    pub fn remove(&mut self, escrow: &Pubkey) {
+        self.open_escrows.retain(|open| open != escrow);
+    }
+}
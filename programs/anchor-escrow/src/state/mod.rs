@@ -1,3 +1,15 @@
+pub mod config;
 pub mod escrow;
+pub mod maker_index;
+pub mod oracle_price;
+pub mod paused_mint;
+pub mod receipt;
+pub mod staged_take;
 
-pub use escrow::*;
\ No newline at end of file
+pub use config::*;
+pub use escrow::*;
+pub use maker_index::*;
+pub use oracle_price::*;
+pub use paused_mint::*;
+pub use receipt::*;
+pub use staged_take::*;
\ No newline at end of file
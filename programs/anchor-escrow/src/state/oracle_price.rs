@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+use bytemuck::{Pod, Zeroable};
+
+//Price account an oracle escrow reads: this struct at the start of the data, no discriminator
+//Any program may own the feed, the maker trusts it by naming it at Make
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct OraclePrice {
+    pub price: u64, //mint_b base units per 10^decimals mint_a base units
+    pub publish_time: i64, //unix timestamp of the last update
+    pub decimals: u8,
+    pub _padding: [u8; 7],
+}
+
+impl OraclePrice {
+    pub const LEN: usize = std::mem::size_of::<Self>();
+    pub const MAX_DECIMALS: u8 = 18;
+
+    pub fn load(info: &AccountInfo) -> Option<Self> {
+        let data = info.try_borrow_data().ok()?;
+
+        Some(bytemuck::pod_read_unaligned(data.get(..Self::LEN)?))
+    }
+}
@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+//Pauses Make and Take for a single mint, the rest of the program keeps running
+//Mints that were never paused have no account at all
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct PausedMint {
+    pub mint: Pubkey,
+    pub paused: bool,
+    pub bump: u8,
+}
+
+impl PausedMint {
+    //Make and Take pass the PDA unchecked since most mints never get one
+    pub fn is_paused(info: &AccountInfo) -> bool {
+        if info.owner != &crate::ID || info.data_is_empty() {
+            return false;
+        }
+
+        let Ok(data) = info.try_borrow_data() else {
+            return false;
+        };
+
+        PausedMint::try_deserialize(&mut &data[..]).is_ok_and(|paused_mint| paused_mint.paused)
+    }
+}
@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::EscrowError, state::Escrow};
+
+//Left behind by Take, TakeWithSol and TakeMany for audit trails, the program never closes it so it outlives the escrow
+//One per escrow and taker, so repeat partial fills by the same taker add up in the same receipt
+//An escrow address can be made again after it closes, so created_at tells the old escrow's fills from the new one's
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct Receipt {
+    pub escrow: Pubkey,
+    pub maker: Pubkey, //owner of the escrow at the first fill
+    pub taker: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub amount_a: u64, //mint_a sent to the taker, after the taker fee and with any tip
+    pub amount_b: u64, //mint_b the taker paid, protocol fee included
+    pub fills: u32,
+    pub first_fill_at: i64, //unix timestamp
+    pub last_fill_at: i64, //unix timestamp
+    pub created_at: i64, //created_at of the escrow the fills were against
+    pub bump: u8,
+}
+
+impl Receipt {
+    //Writes a fresh receipt for this escrow unless it already holds fills against the same one
+    pub fn reset_if_stale(&mut self, escrow: Pubkey, escrow_data: &Escrow, taker: Pubkey, mint_b: Pubkey, bump: u8) {
+        if self.fills > 0 && self.escrow == escrow && self.created_at == escrow_data.created_at {
+            return;
+        }
+
+        *self = Receipt {
+            escrow,
+            maker: escrow_data.maker,
+            taker,
+            mint_a: escrow_data.mint_a,
+            mint_b,
+            amount_a: 0,
+            amount_b: 0,
+            fills: 0,
+            first_fill_at: 0,
+            last_fill_at: 0,
+            created_at: escrow_data.created_at,
+            bump,
+        };
+    }
+
+    pub fn record(&mut self, amount_a: u64, amount_b: u64, now: i64) -> Result<()> {
+        if self.fills == 0 {
+            self.first_fill_at = now;
+        }
+        self.amount_a = self.amount_a.checked_add(amount_a).ok_or(EscrowError::MathOverflow)?;
+        self.amount_b = self.amount_b.checked_add(amount_b).ok_or(EscrowError::MathOverflow)?;
+        self.fills = self.fills.checked_add(1).ok_or(EscrowError::MathOverflow)?;
+        self.last_fill_at = now;
+
+        Ok(())
+    }
+}
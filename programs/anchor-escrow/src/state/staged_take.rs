@@ -0,0 +1,17 @@
+use anchor_lang::prelude::*;
+
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct StagedTake {
+    pub escrow: Pubkey,
+    pub taker: Pubkey,
+    pub amount: u64, //mint_b held in the take_vault
+    pub take_deadline: i64, //unix timestamp, 0 = no deadline; after it Settle fails and anyone can CancelStage
+    pub bump: u8,
+}
+
+impl StagedTake {
+    pub fn is_past_deadline(&self, now: i64) -> bool {
+        self.take_deadline != 0 && now > self.take_deadline
+    }
+}
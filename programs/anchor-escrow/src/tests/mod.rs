@@ -1,22 +1,33 @@
 #[cfg(test)]
+#[allow(clippy::module_inception, clippy::result_large_err)]
 mod tests {
     use {
-        anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas},
+        anchor_lang::{
+            prelude::Clock,
+            solana_program::{program_option::COption, program_pack::Pack},
+            AccountDeserialize, Event, InstructionData, Space, ToAccountMetas,
+        },
         anchor_spl::{
             associated_token::{self, spl_associated_token_account},
-            token::TokenAccount,
+            token::spl_token::{self, native_mint},
+            token_interface::TokenAccount,
         },
-        litesvm::LiteSVM,
+        base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+        crate::instructions::MakeArgs,
+        litesvm::{types::{TransactionMetadata, TransactionResult}, LiteSVM},
         litesvm_token::{
-            spl_token::ID as TOKEN_PROGRAM_ID, CreateAssociatedTokenAccount, CreateMint, MintTo,
+            spl_token::ID as TOKEN_PROGRAM_ID, Approve, CreateAccount, CreateAssociatedTokenAccount, CreateMint,
+            MintTo,
         },
-        solana_instruction::Instruction,
+        solana_account::Account,
+        solana_instruction::{error::InstructionError, AccountMeta, Instruction},
         solana_keypair::Keypair,
         solana_native_token::LAMPORTS_PER_SOL,
         solana_pubkey::Pubkey,
         solana_sdk_ids::system_program::ID as SYSTEM_PROGRAM_ID,
         solana_signer::Signer,
         solana_transaction::Transaction,
+        solana_transaction_error::TransactionError,
         std::path::PathBuf,
     };
 
@@ -31,50 +42,259 @@ mod tests {
         svm
     }
 
-    fn get_token_balance(svm: &LiteSVM, ata: &Pubkey) -> u64 {
-        TokenAccount::try_deserialize(
-            &mut svm.get_account(ata).unwrap().data.as_slice()
-        )
-        .unwrap()
-        .amount
+    // The usual two-sided setup: maker controls mint_a, taker controls mint_b, and both
+    // hold SOL, an ATA for each mint and PARTY_BALANCE of their own mint
+    struct Parties {
+        maker: Keypair,
+        taker: Keypair,
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+        maker_ata_a: Pubkey,
+        maker_ata_b: Pubkey,
+        taker_ata_a: Pubkey,
+        taker_ata_b: Pubkey,
     }
 
-    #[test]
-    fn test_escrow_full_lifecycle() {
-        let mut svm = setup();
+    const PARTY_BALANCE: u64 = 1_000_000_000;
+
+    fn setup_parties(svm: &mut LiteSVM) -> Parties {
+        setup_parties_with_decimals(svm, 6)
+    }
 
-        // Fund participants
+    fn setup_parties_with_decimals(svm: &mut LiteSVM, decimals: u8) -> Parties {
         let maker = Keypair::new();
         let taker = Keypair::new();
         svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
         svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
 
-        // Create mints (maker controls mint_a, taker controls mint_b)
-        let mint_a = CreateMint::new(&mut svm, &maker)
+        let mint_a = CreateMint::new(svm, &maker)
             .authority(&maker.pubkey())
-            .decimals(6)
+            .decimals(decimals)
             .send()
             .unwrap();
-
-        let mint_b = CreateMint::new(&mut svm, &taker)
+        let mint_b = CreateMint::new(svm, &taker)
             .authority(&taker.pubkey())
-            .decimals(6)
+            .decimals(decimals)
             .send()
             .unwrap();
 
-        // Create ATAs
-        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+        let maker_ata_a = CreateAssociatedTokenAccount::new(svm, &maker, &mint_a)
             .owner(&maker.pubkey()).send().unwrap();
-        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+        let maker_ata_b = CreateAssociatedTokenAccount::new(svm, &maker, &mint_b)
             .owner(&maker.pubkey()).send().unwrap();
-        let taker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_a)
+        let taker_ata_a = CreateAssociatedTokenAccount::new(svm, &taker, &mint_a)
             .owner(&taker.pubkey()).send().unwrap();
-        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+        let taker_ata_b = CreateAssociatedTokenAccount::new(svm, &taker, &mint_b)
             .owner(&taker.pubkey()).send().unwrap();
 
-        // Mint initial balances
-        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
-        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000_000_000).send().unwrap();
+        MintTo::new(svm, &maker, &mint_a, &maker_ata_a, PARTY_BALANCE).send().unwrap();
+        MintTo::new(svm, &taker, &mint_b, &taker_ata_b, PARTY_BALANCE).send().unwrap();
+
+        Parties { maker, taker, mint_a, mint_b, maker_ata_a, maker_ata_b, taker_ata_a, taker_ata_b }
+    }
+
+    // token_interface::TokenAccount reads legacy and Token-2022 accounts alike
+    fn get_token_balance(svm: &LiteSVM, ata: &Pubkey) -> u64 {
+        TokenAccount::try_deserialize(
+            &mut svm.get_account(ata).unwrap().data.as_slice()
+        )
+        .unwrap()
+        .amount
+    }
+
+    // Escrow is zero-copy, so it is read straight from the bytes after the discriminator
+    fn read_escrow(account: &Account) -> crate::state::Escrow {
+        crate::client::load_escrow(&account.data).expect("Account is not an escrow")
+    }
+
+    fn open_escrows(svm: &LiteSVM, maker: &Pubkey) -> Vec<Pubkey> {
+        let account = svm.get_account(&crate::client::maker_index_pda(maker).0).expect("Maker has no index");
+        crate::state::MakerIndex::try_deserialize(&mut account.data.as_slice()).unwrap().open_escrows
+    }
+
+    // Copies of the given accounts, None for accounts that don't exist yet
+    type AccountSnapshot = Vec<(Pubkey, Option<Account>)>;
+
+    fn snapshot_accounts(svm: &LiteSVM, keys: &[Pubkey]) -> AccountSnapshot {
+        keys.iter().map(|key| (*key, svm.get_account(key))).collect()
+    }
+
+    // Keys whose lamports, data, owner or existence differ between two snapshots of the same accounts
+    fn diff_accounts(before: &AccountSnapshot, after: &AccountSnapshot) -> Vec<Pubkey> {
+        assert_eq!(
+            before.iter().map(|(key, _)| key).collect::<Vec<_>>(),
+            after.iter().map(|(key, _)| key).collect::<Vec<_>>(),
+            "Snapshots cover different accounts"
+        );
+        before
+            .iter()
+            .zip(after)
+            .filter(|((_, old), (_, new))| old != new)
+            .map(|((key, _), _)| *key)
+            .collect()
+    }
+
+    fn warp_to_timestamp(svm: &mut LiteSVM, unix_timestamp: i64) {
+        let mut clock = svm.get_sysvar::<Clock>();
+        clock.unix_timestamp = unix_timestamp;
+        svm.set_sysvar::<Clock>(&clock);
+    }
+
+    // LiteSVM does not ship the wrapped SOL mint, so install it by hand
+    fn ensure_native_mint(svm: &mut LiteSVM) {
+        if svm.get_account(&native_mint::ID).is_some() {
+            return;
+        }
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint {
+            mint_authority: COption::None,
+            supply: 0,
+            decimals: 9,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+        .pack_into_slice(&mut data);
+        svm.set_account(
+            native_mint::ID,
+            Account {
+                lamports: svm.minimum_balance_for_rent_exemption(data.len()),
+                data,
+                owner: TOKEN_PROGRAM_ID,
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+        .unwrap();
+    }
+
+    // Token-2022 mint with 6 decimals that withholds fee_bps of every transfer, authority is both mint and fee authority
+    fn create_transfer_fee_mint(svm: &mut LiteSVM, authority: &Keypair, fee_bps: u16) -> Pubkey {
+        use spl_token_2022::extension::{transfer_fee::instruction::initialize_transfer_fee_config, ExtensionType};
+
+        let token_program = spl_token_2022::ID;
+        let mint = Keypair::new();
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[ExtensionType::TransferFeeConfig]).unwrap();
+        let create_mint_ixs = [
+            solana_system_interface::instruction::create_account(
+                &authority.pubkey(),
+                &mint.pubkey(),
+                svm.minimum_balance_for_rent_exemption(space),
+                space as u64,
+                &token_program,
+            ),
+            initialize_transfer_fee_config(&token_program, &mint.pubkey(), Some(&authority.pubkey()), Some(&authority.pubkey()), fee_bps, u64::MAX).unwrap(),
+            spl_token_2022::instruction::initialize_mint2(&token_program, &mint.pubkey(), &authority.pubkey(), None, 6).unwrap(),
+        ];
+        let tx = Transaction::new_signed_with_payer(
+            &create_mint_ixs,
+            Some(&authority.pubkey()),
+            &[authority, &mint],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Creating the transfer fee mint failed");
+        mint.pubkey()
+    }
+
+    fn init_config(svm: &mut LiteSVM, authority: &Keypair, fee_bps: u16) -> Pubkey {
+        let config = Pubkey::find_program_address(&[b"config"], &PROGRAM_ID).0;
+        let init_config_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::InitConfig {
+                authority: authority.pubkey(),
+                config,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::InitConfig { fee_bps }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[init_config_ix],
+            Some(&authority.pubkey()),
+            &[authority],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Init config failed");
+        config
+    }
+
+    fn set_paused(svm: &mut LiteSVM, authority: &Keypair, config: Pubkey, paused: bool) -> TransactionResult {
+        let set_paused_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::SetPaused {
+                authority: authority.pubkey(),
+                config,
+            }.to_account_metas(None),
+            data: crate::instruction::SetPaused { paused }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[set_paused_ix],
+            Some(&authority.pubkey()),
+            &[authority],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx)
+    }
+
+    fn find_event<T: Event>(logs: &[String]) -> Option<T> {
+        logs.iter()
+            .filter_map(|log| log.strip_prefix("Program data: "))
+            .filter_map(|data| BASE64.decode(data).ok())
+            .find(|data| data.starts_with(T::DISCRIMINATOR))
+            .map(|data| T::try_from_slice(&data[T::DISCRIMINATOR.len()..]).unwrap())
+    }
+
+    fn assert_error(result: TransactionResult, expected: impl Into<u32>) {
+        let expected = expected.into();
+        let failed = result.expect_err("Transaction should have failed");
+        match failed.err {
+            TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+                assert_eq!(code, expected, "Unexpected error code, logs: {:#?}", failed.meta.logs)
+            }
+            err => panic!("Expected custom error {expected}, got {err:?}"),
+        }
+    }
+
+    // Refund takes maker as a plain account so a refund authority can sign instead, the maker still signs these
+    fn refund_metas(accounts: crate::accounts::Refund) -> Vec<AccountMeta> {
+        let mut metas = accounts.to_account_metas(None);
+        metas[0].is_signer = true;
+        metas
+    }
+
+    // One TakeMany remaining_accounts group, in ACCOUNTS_PER_TAKE order
+    fn take_many_group(maker: &Pubkey, taker: &Pubkey, fee_authority: &Pubkey, mint_a: &Pubkey, mint_b: &Pubkey, escrow: &Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(*maker, false),
+            AccountMeta::new_readonly(*mint_a, false),
+            AccountMeta::new_readonly(*mint_b, false),
+            AccountMeta::new(associated_token::get_associated_token_address(taker, mint_a), false),
+            AccountMeta::new(associated_token::get_associated_token_address(taker, mint_b), false),
+            AccountMeta::new(associated_token::get_associated_token_address(maker, mint_b), false),
+            AccountMeta::new(*escrow, false),
+            AccountMeta::new(associated_token::get_associated_token_address(escrow, mint_a), false),
+            AccountMeta::new(associated_token::get_associated_token_address(fee_authority, mint_b), false),
+            AccountMeta::new(associated_token::get_associated_token_address(fee_authority, mint_a), false),
+            AccountMeta::new_readonly(crate::client::paused_mint_pda(mint_a).0, false),
+            AccountMeta::new_readonly(crate::client::paused_mint_pda(mint_b).0, false),
+            AccountMeta::new(crate::client::maker_index_pda(maker).0, false),
+            AccountMeta::new(crate::client::receipt_pda(escrow, taker).0, false),
+        ]
+    }
+
+    fn assert_max_cu(result: &TransactionMetadata, limit: u64) {
+        assert!(
+            result.compute_units_consumed <= limit,
+            "Used {} compute units, over the {limit} ceiling, logs: {:#?}",
+            result.compute_units_consumed,
+            result.logs
+        );
+    }
+
+    #[test]
+    fn test_escrow_full_lifecycle() {
+        let mut svm = setup();
+
+        // Funded participants with both mints and all four ATAs
+        let Parties { maker, taker, mint_a, mint_b, maker_ata_a, maker_ata_b, taker_ata_a, taker_ata_b } = setup_parties(&mut svm);
 
         // Derive PDAs
         let seed: u64 = 123;
@@ -86,6 +306,11 @@ mod tests {
 
         let associated_token_program = spl_associated_token_account::ID;
 
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
         // Make
         let make_ix = Instruction {
             program_id: PROGRAM_ID,
@@ -94,11 +319,15 @@ mod tests {
                 mint_a, mint_b,
                 maker_ata_a,
                 escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
                 associated_token_program,
                 token_program: TOKEN_PROGRAM_ID,
                 system_program: SYSTEM_PROGRAM_ID,
             }.to_account_metas(None),
-            data: crate::instruction::Make { deposit: 10, seed, receive: 10 }.data(),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 10, receive: 10, ..Default::default() } }.data(),
         };
         let tx = Transaction::new_signed_with_payer(
             &[make_ix],
@@ -106,13 +335,13 @@ mod tests {
             &[&maker],
             svm.latest_blockhash(),
         );
-        svm.send_transaction(tx).expect("Make failed");
+        let result = svm.send_transaction(tx).expect("Make failed");
+        // Compute ceilings leave headroom over the current baselines, raise them deliberately
+        assert_max_cu(&result, 60_000);
 
         // Verify escrow state
         let escrow_account = svm.get_account(&escrow).unwrap();
-        let escrow_data = crate::state::Escrow::try_deserialize(
-            &mut escrow_account.data.as_ref()
-        ).unwrap();
+        let escrow_data = read_escrow(&escrow_account);
         assert_eq!(escrow_data.seed, seed);
         assert_eq!(escrow_data.maker, maker.pubkey());
         assert_eq!(escrow_data.mint_a, mint_a);
@@ -131,19 +360,53 @@ mod tests {
                 taker_ata_b,
                 maker_ata_b,
                 escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault,
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
                 associated_token_program,
                 token_program: TOKEN_PROGRAM_ID,
                 system_program: SYSTEM_PROGRAM_ID,
             }.to_account_metas(None),
-            data: crate::instruction::Take.data(),
+            data: crate::instruction::Take { take_amount: 10, max_receive: u64::MAX }.data(),
         };
+        // Every account the take touches, so writes to anything unexpected show up in the diff
+        let take_keys: Vec<Pubkey> = take_ix.accounts.iter().map(|meta| meta.pubkey).collect();
+        let before = snapshot_accounts(&svm, &take_keys);
         let tx = Transaction::new_signed_with_payer(
             &[take_ix],
             Some(&taker.pubkey()),
             &[&taker],
             svm.latest_blockhash(),
         );
-        svm.send_transaction(tx).expect("Take failed");
+        let result = svm.send_transaction(tx).expect("Take failed");
+        assert_max_cu(&result, 120_000);
+
+        let mut changed = diff_accounts(&before, &snapshot_accounts(&svm, &take_keys));
+        changed.sort();
+        changed.dedup();
+        let fee_vault_a = associated_token::get_associated_token_address(&admin.pubkey(), &mint_a);
+        let mut expected = vec![
+            taker.pubkey(), // signature fee, the fee vault rents and the receipt rent
+            maker.pubkey(), // escrow and vault rent
+            taker_ata_a, taker_ata_b, maker_ata_b,
+            escrow, vault,
+            crate::client::maker_index_pda(&maker.pubkey()).0,
+            config, // take counters
+            fee_vault, fee_vault_a, // created on first take
+            crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+        ];
+        expected.sort();
+        assert_eq!(changed, expected, "Take wrote to an unexpected set of accounts");
 
         // Verify escrow is closed and tokens transferred
         assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after take");
@@ -151,7 +414,7 @@ mod tests {
         assert_eq!(get_token_balance(&svm, &maker_ata_b), 10, "Maker should have received mint_b tokens");
 
         // Make + Refund
-        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, PARTY_BALANCE).send().unwrap();
 
         let make_ix = Instruction {
             program_id: PROGRAM_ID,
@@ -160,11 +423,15 @@ mod tests {
                 mint_a, mint_b,
                 maker_ata_a,
                 escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
                 associated_token_program,
                 token_program: TOKEN_PROGRAM_ID,
                 system_program: SYSTEM_PROGRAM_ID,
             }.to_account_metas(None),
-            data: crate::instruction::Make { deposit: 100, seed, receive: 100 }.data(),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 100, receive: 100, ..Default::default() } }.data(),
         };
         let tx = Transaction::new_signed_with_payer(
             &[make_ix],
@@ -176,15 +443,22 @@ mod tests {
 
         let refund_ix = Instruction {
             program_id: PROGRAM_ID,
-            accounts: crate::accounts::Refund {
+            accounts: refund_metas(crate::accounts::Refund {
                 maker: maker.pubkey(),
+                refund_authority: None,
                 mint_a,
                 maker_ata_a,
+                refund_destination: None,
                 escrow, vault,
+                mint_a2: None, maker_ata_a2: None, vault_a2: None,
+                staged_take: crate::client::staged_take_pda(&escrow).0,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                associated_token_program: associated_token::ID,
                 token_program: TOKEN_PROGRAM_ID,
                 system_program: SYSTEM_PROGRAM_ID,
-            }.to_account_metas(None),
-            data: crate::instruction::Refund.data(),
+            }),
+            data: crate::instruction::Refund { close_maker_ata: false }.data(),
         };
         let tx = Transaction::new_signed_with_payer(
             &[refund_ix],
@@ -192,15 +466,9223 @@ mod tests {
             &[&maker],
             svm.latest_blockhash(),
         );
-        svm.send_transaction(tx).expect("Refund failed");
+        let result = svm.send_transaction(tx).expect("Refund failed");
+        assert_max_cu(&result, 40_000);
 
         assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after refund");
-        // After refund: maker had 1_000_000_000 (second mint) minus 100 deposited, plus the original
-        // 1_000_000_000 minus 10 from phase 1, returned. Net = 2_000_000_000 - 10.
+        // After refund: maker had PARTY_BALANCE (second mint) minus 100 deposited, plus the original
+        // PARTY_BALANCE minus 10 from phase 1, returned. Net = 2 * PARTY_BALANCE - 10.
         assert_eq!(
             get_token_balance(&svm, &maker_ata_a),
-            2_000_000_000 - 10,
+            2 * PARTY_BALANCE - 10,
             "Maker should have both mints minus the first deposit after refund"
         );
     }
+
+    #[test]
+    fn test_take_fails_after_expiry() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_a)
+            .owner(&taker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 42;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
+        // Make an escrow that expires in 60 seconds
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 10, receive: 10, expiry: now + 60, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow_account = svm.get_account(&escrow).unwrap();
+        let escrow_data = read_escrow(&escrow_account);
+        assert_eq!(escrow_data.expiry, now + 60);
+
+        // Advance past the expiry, take must be rejected
+        warp_to_timestamp(&mut svm, now + 61);
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a,
+                taker_ata_b,
+                maker_ata_b,
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault,
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 10, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::EscrowExpired);
+
+        // The maker can still recover the deposit
+        let refund_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: refund_metas(crate::accounts::Refund {
+                maker: maker.pubkey(),
+                refund_authority: None,
+                mint_a,
+                maker_ata_a,
+                refund_destination: None,
+                escrow, vault,
+                mint_a2: None, maker_ata_a2: None, vault_a2: None,
+                staged_take: crate::client::staged_take_pda(&escrow).0,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                associated_token_program: associated_token::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }),
+            data: crate::instruction::Refund { close_maker_ata: false }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[refund_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after refund");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000_000_000);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 0);
+    }
+    #[test]
+    fn test_reclaim_expired_by_third_party() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let cranker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&cranker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 7;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 25, receive: 10, expiry: now + 60, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let reclaim_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ReclaimExpired {
+                cranker: cranker.pubkey(),
+                maker: maker.pubkey(),
+                staged_take: crate::client::staged_take_pda(&escrow).0,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                mint_a,
+                maker_ata_a,
+                cranker_ata_a: associated_token::get_associated_token_address(&cranker.pubkey(), &mint_a),
+                escrow, vault,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::ReclaimExpired.data(),
+        };
+
+        // Not expired yet
+        let tx = Transaction::new_signed_with_payer(
+            std::slice::from_ref(&reclaim_ix),
+            Some(&cranker.pubkey()),
+            &[&cranker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::EscrowNotExpired);
+
+        warp_to_timestamp(&mut svm, now + 60);
+        svm.expire_blockhash();
+
+        let maker_lamports = svm.get_balance(&maker.pubkey()).unwrap();
+        let escrow_rent = svm.get_balance(&escrow).unwrap();
+        let vault_rent = svm.get_balance(&vault).unwrap();
+
+        let tx = Transaction::new_signed_with_payer(
+            &[reclaim_ix],
+            Some(&cranker.pubkey()),
+            &[&cranker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Reclaim failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after reclaim");
+        assert!(!open_escrows(&svm, &maker.pubkey()).contains(&escrow));
+        assert!(svm.get_account(&vault).is_none(), "Vault should be closed after reclaim");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000_000_000);
+        assert_eq!(
+            svm.get_balance(&maker.pubkey()).unwrap(),
+            maker_lamports + escrow_rent + vault_rent,
+            "Maker should receive the escrow and vault rent"
+        );
+    }
+    #[test]
+    fn test_partial_fills_by_two_takers() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker_one = Keypair::new();
+        let taker_two = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker_one.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker_two.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_one_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker_one, &mint_b)
+            .owner(&taker_one.pubkey()).send().unwrap();
+        let taker_two_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker_two, &mint_b)
+            .owner(&taker_two.pubkey()).send().unwrap();
+        // taker_ata_a is created on demand by Take
+        let taker_one_ata_a = associated_token::get_associated_token_address(&taker_one.pubkey(), &mint_a);
+        let taker_two_ata_a = associated_token::get_associated_token_address(&taker_two.pubkey(), &mint_a);
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_b, &taker_one_ata_b, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_b, &taker_two_ata_b, 1_000).send().unwrap();
+
+        let seed: u64 = 9;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
+        // Offer 100 of mint_a for 30 of mint_b
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 100, receive: 30, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let take_accounts = |taker: &Keypair, taker_ata_a: Pubkey, taker_ata_b: Pubkey| crate::accounts::Take {
+            taker: taker.pubkey(),
+            maker: maker.pubkey(),
+            mint_a, mint_b,
+            taker_ata_a,
+            taker_ata_b,
+            maker_ata_b,
+            escrow, vault,
+            mint_a2: None, taker_ata_a2: None, vault_a2: None,
+            royalty_ata_b: None,
+            maker_receive_account: None,
+            sol_unwrap: None,
+            oracle: None,
+            maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+            paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+            paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+            config,
+            fee_authority: admin.pubkey(),
+            fee_vault,
+            fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+            receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+            associated_token_program,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: SYSTEM_PROGRAM_ID,
+        }.to_account_metas(None);
+
+        // Taking more than the vault holds is rejected
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: take_accounts(&taker_one, taker_one_ata_a, taker_one_ata_b),
+            data: crate::instruction::Take { take_amount: 101, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker_one.pubkey()),
+            &[&taker_one],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::PartialFillTooLarge);
+
+        // First taker fills 40 and pays ceil(40 * 30 / 100) = 12
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: take_accounts(&taker_one, taker_one_ata_a, taker_one_ata_b),
+            data: crate::instruction::Take { take_amount: 40, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker_one.pubkey()),
+            &[&taker_one],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("First partial take failed");
+
+        let escrow_account = svm.get_account(&escrow).expect("Escrow should stay open after a partial fill");
+        let escrow_data = read_escrow(&escrow_account);
+        assert_eq!(escrow_data.receive, 18);
+        assert_eq!(get_token_balance(&svm, &vault), 60);
+        assert_eq!(get_token_balance(&svm, &taker_one_ata_a), 40);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 12);
+
+        // Second taker fills the remainder and pays what is left of receive
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: take_accounts(&taker_two, taker_two_ata_a, taker_two_ata_b),
+            data: crate::instruction::Take { take_amount: 60, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker_two.pubkey()),
+            &[&taker_two],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Second partial take failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed once the vault is empty");
+        assert!(svm.get_account(&vault).is_none(), "Vault should be closed once empty");
+        assert_eq!(get_token_balance(&svm, &taker_two_ata_a), 60);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 30);
+    }
+    #[test]
+    fn test_update_offer_changes_receive() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_a)
+            .owner(&taker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 11;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 10, receive: 10, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let update_ix = |new_receive: u64| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::UpdateOffer {
+                maker: maker.pubkey(),
+                escrow,
+            }.to_account_metas(None),
+            data: crate::instruction::UpdateOffer { new_receive }.data(),
+        };
+
+        // Zero is rejected
+        let tx = Transaction::new_signed_with_payer(
+            &[update_ix(0)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::InvalidAmount);
+
+        // Up, then down
+        for new_receive in [50, 25] {
+            let tx = Transaction::new_signed_with_payer(
+                &[update_ix(new_receive)],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Update offer failed");
+
+            let escrow_account = svm.get_account(&escrow).unwrap();
+            let escrow_data = read_escrow(&escrow_account);
+            assert_eq!(escrow_data.receive, new_receive);
+        }
+        assert_eq!(get_token_balance(&svm, &vault), 10, "Vault must be untouched by updates");
+
+        // Take pays the latest receive amount
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a,
+                taker_ata_b,
+                maker_ata_b,
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault,
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 10, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 25);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 10);
+    }
+    #[test]
+    fn test_top_up_adds_to_vault() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_a)
+            .owner(&taker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 5;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 10, receive: 20, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let top_up_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::TopUp {
+                maker: maker.pubkey(),
+                mint_a,
+                maker_ata_a,
+                escrow, vault,
+                token_program: TOKEN_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::TopUp { deposit: 40 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[top_up_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Top up failed");
+
+        assert_eq!(get_token_balance(&svm, &vault), 50);
+        let escrow_account = svm.get_account(&escrow).unwrap();
+        let escrow_data = read_escrow(&escrow_account);
+        assert_eq!(escrow_data.receive, 20, "Top up must not change receive");
+        assert_eq!(escrow_data.deposit, 50, "Top up should be recorded on the escrow");
+
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a,
+                taker_ata_b,
+                maker_ata_b,
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault,
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 50, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 50);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 20);
+    }
+    #[test]
+    fn test_allowed_taker_whitelist() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        let intruder = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&intruder.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_a)
+            .owner(&taker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        let intruder_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &intruder, &mint_a)
+            .owner(&intruder.pubkey()).send().unwrap();
+        let intruder_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &intruder, &mint_b)
+            .owner(&intruder.pubkey()).send().unwrap();
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_b, &taker_ata_b, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_b, &intruder_ata_b, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 77;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
+        // Private offer for `taker` only
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 10, receive: 10, allowed_taker: taker.pubkey(), ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let take_ix = |taker: &Keypair, taker_ata_a: Pubkey, taker_ata_b: Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a,
+                taker_ata_b,
+                maker_ata_b,
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault,
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 10, max_receive: u64::MAX }.data(),
+        };
+
+        // Anyone else is rejected
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix(&intruder, intruder_ata_a, intruder_ata_b)],
+            Some(&intruder.pubkey()),
+            &[&intruder],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::UnauthorizedTaker);
+        assert_eq!(get_token_balance(&svm, &vault), 10);
+
+        // The allowed taker goes through
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix(&taker, taker_ata_a, taker_ata_b)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Allowed taker should be able to take");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after take");
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 10);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 10);
+    }
+    #[test]
+    fn test_events_emitted() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_a)
+            .owner(&taker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 3;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 40, receive: 15, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        let meta = svm.send_transaction(tx).expect("Make failed");
+
+        let made = find_event::<crate::events::EscrowMade>(&meta.logs).expect("EscrowMade not emitted");
+        assert_eq!(made.escrow, escrow);
+        assert_eq!(made.deposit, 40);
+        assert_eq!(made.receive, 15);
+
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a,
+                taker_ata_b,
+                maker_ata_b,
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault,
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 40, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        let meta = svm.send_transaction(tx).expect("Take failed");
+
+        let taken = find_event::<crate::events::EscrowTaken>(&meta.logs).expect("EscrowTaken not emitted");
+        assert_eq!(taken.escrow, escrow);
+        assert_eq!(taken.maker, maker.pubkey());
+        assert_eq!(taken.taker, taker.pubkey());
+        assert_eq!(taken.mint_a, mint_a);
+        assert_eq!(taken.mint_b, mint_b);
+        assert_eq!(taken.seed, seed);
+        assert_eq!(taken.deposit, get_token_balance(&svm, &taker_ata_a));
+        assert_eq!(taken.receive, get_token_balance(&svm, &maker_ata_b));
+        assert_eq!(taken.deposit, 40);
+        assert_eq!(taken.receive, 15);
+    }
+    #[test]
+    fn test_protocol_fee_on_take() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        let admin = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        // Fees above 10% are rejected
+        let config = Pubkey::find_program_address(&[b"config"], &PROGRAM_ID).0;
+        let init_config_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::InitConfig {
+                authority: admin.pubkey(),
+                config,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::InitConfig { fee_bps: 1_001 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[init_config_ix],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::FeeTooHigh);
+
+        let config = init_config(&mut svm, &admin, 250);
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_a)
+            .owner(&taker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 8;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 100, receive: 1_000, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a,
+                taker_ata_b,
+                maker_ata_b,
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault,
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 100, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        // 2.5% of 1_000 goes to the fee vault
+        assert_eq!(get_token_balance(&svm, &fee_vault), 25);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 975);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000_000_000 - 1_000);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 100);
+    }
+    #[test]
+    fn test_take_with_sol() {
+        let mut svm = setup();
+        ensure_native_mint(&mut svm);
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        let admin = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = native_mint::ID;
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 1;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let receive = 2 * LAMPORTS_PER_SOL;
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 50, receive, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let maker_lamports = svm.get_balance(&maker.pubkey()).unwrap();
+        let taker_lamports = svm.get_balance(&taker.pubkey()).unwrap();
+        let escrow_rent = svm.get_balance(&escrow).unwrap();
+        let vault_rent = svm.get_balance(&vault).unwrap();
+
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::TakeWithSol {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                mint_a, mint_b,
+                taker_ata_a,
+                escrow, vault,
+                config,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                fee_authority: admin.pubkey(),
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::TakeWithSol { take_amount: 50, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take with SOL failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after take");
+        assert!(!open_escrows(&svm, &maker.pubkey()).contains(&escrow));
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 50);
+        // The maker also gets the escrow and vault rent back on close
+        assert_eq!(
+            svm.get_balance(&maker.pubkey()).unwrap(),
+            maker_lamports + receive + escrow_rent + vault_rent,
+        );
+        assert!(svm.get_balance(&taker.pubkey()).unwrap() < taker_lamports - receive);
+
+        let receipt_account = svm.get_account(&crate::client::receipt_pda(&escrow, &taker.pubkey()).0).expect("Receipt should outlive the escrow");
+        let receipt = crate::state::Receipt::try_deserialize(&mut receipt_account.data.as_slice()).unwrap();
+        assert_eq!((receipt.amount_a, receipt.amount_b, receipt.fills), (50, receive, 1));
+    }
+    #[test]
+    fn test_created_at_matches_clock() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 10;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        warp_to_timestamp(&mut svm, 1_710_000_000);
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 10, receive: 10, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow_account = svm.get_account(&escrow).unwrap();
+        assert_eq!(escrow_account.data.len(), 8 + crate::state::Escrow::INIT_SPACE);
+        let escrow_data = read_escrow(&escrow_account);
+        let clock = svm.get_sysvar::<Clock>();
+        assert_ne!(escrow_data.created_at, 0);
+        assert_eq!(escrow_data.created_at, clock.unix_timestamp);
+
+        warp_to_timestamp(&mut svm, 1_710_000_090);
+        assert_eq!(escrow_data.age(svm.get_sysvar::<Clock>().unix_timestamp), 90);
+    }
+    #[test]
+    fn test_make_rejects_same_mint() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 12;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a,
+                mint_b: mint_a,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_a).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 10, receive: 10, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::SameMint);
+
+        assert!(svm.get_account(&escrow).is_none(), "No escrow should be created");
+        assert!(svm.get_account(&vault).is_none(), "No vault should be created");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000_000_000);
+    }
+    #[test]
+    fn test_make_rejects_zero_deposit() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 13;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 0, receive: 10, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::InvalidAmount);
+
+        assert!(svm.get_account(&escrow).is_none(), "No escrow should be created");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000_000_000);
+    }
+
+    #[test]
+    fn test_make_rejects_zero_receive() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 13;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 10, receive: 0, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::InvalidAmount);
+
+        assert!(svm.get_account(&escrow).is_none(), "No escrow should be created");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000_000_000);
+    }
+    #[test]
+    fn test_staged_take_and_settle() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 14;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let staged_take = Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &PROGRAM_ID).0;
+        let take_vault = associated_token::get_associated_token_address(&staged_take, &mint_b);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 500, receive: 300, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let stage_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::StageTake {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_b,
+                taker_ata_b,
+                escrow,
+                staged_take,
+                take_vault,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::StageTake { take_window: 0 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[stage_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Stage take failed");
+
+        assert_eq!(get_token_balance(&svm, &take_vault), 300);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000_000_000 - 300);
+
+        let staged_account = svm.get_account(&staged_take).unwrap();
+        let staged_data = crate::state::StagedTake::try_deserialize(
+            &mut staged_account.data.as_ref()
+        ).unwrap();
+        assert_eq!(staged_data.escrow, escrow);
+        assert_eq!(staged_data.taker, taker.pubkey());
+        assert_eq!(staged_data.amount, 300);
+
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+        let maker_ata_b = associated_token::get_associated_token_address(&maker.pubkey(), &mint_b);
+
+        let settle_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Settle {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                mint_a, mint_b,
+                taker_ata_a,
+                maker_ata_b,
+                escrow, vault,
+                staged_take,
+                take_vault,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Settle.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[settle_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Settle failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after settle");
+        assert!(!open_escrows(&svm, &maker.pubkey()).contains(&escrow));
+        assert!(svm.get_account(&staged_take).is_none(), "Stage should be closed after settle");
+        assert!(svm.get_account(&take_vault).is_none(), "Take vault should be closed after settle");
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 500);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 300);
+    }
+
+    #[test]
+    fn test_staged_take_cancel() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 14;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let staged_take = Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &PROGRAM_ID).0;
+        let take_vault = associated_token::get_associated_token_address(&staged_take, &mint_b);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 500, receive: 300, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let stage_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::StageTake {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_b,
+                taker_ata_b,
+                escrow,
+                staged_take,
+                take_vault,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::StageTake { take_window: 0 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[stage_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Stage take failed");
+
+        assert_eq!(get_token_balance(&svm, &take_vault), 300);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000_000_000 - 300);
+
+        let taker_lamports = svm.get_balance(&taker.pubkey()).unwrap();
+        let stage_rent = svm.get_balance(&staged_take).unwrap();
+        let take_vault_rent = svm.get_balance(&take_vault).unwrap();
+
+        let cancel_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::CancelStage {
+                caller: taker.pubkey(),
+                taker: taker.pubkey(),
+                mint_b,
+                taker_ata_b,
+                staged_take,
+                escrow,
+                take_vault,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::CancelStage.data(),
+        };
+        // Maker pays the transaction fee so the taker's lamport delta is just the rent
+        let tx = Transaction::new_signed_with_payer(
+            &[cancel_ix],
+            Some(&maker.pubkey()),
+            &[&maker, &taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Cancel stage failed");
+
+        assert!(svm.get_account(&staged_take).is_none(), "Stage should be closed after cancel");
+        assert!(svm.get_account(&take_vault).is_none(), "Take vault should be closed after cancel");
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000_000_000);
+        assert_eq!(
+            svm.get_balance(&taker.pubkey()).unwrap(),
+            taker_lamports + stage_rent + take_vault_rent,
+        );
+        // The escrow itself is untouched
+        assert_eq!(get_token_balance(&svm, &vault), 500);
+    }
+    #[test]
+    fn test_arbiter_forced_settle() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        let arbiter = Keypair::new();
+        let admin = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&arbiter.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        let maker_ata_b = associated_token::get_associated_token_address(&maker.pubkey(), &mint_b);
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 15;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let staged_take = Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &PROGRAM_ID).0;
+        let take_vault = associated_token::get_associated_token_address(&staged_take, &mint_b);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 200, receive: 80, arbiter: arbiter.pubkey(), ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let stage_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::StageTake {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_b,
+                taker_ata_b,
+                escrow,
+                staged_take,
+                take_vault,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::StageTake { take_window: 0 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[stage_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Stage take failed");
+
+        let release_ix = |signer: &Keypair, decision: crate::instructions::ArbiterDecision| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ArbiterRelease {
+                arbiter: signer.pubkey(),
+                maker: maker.pubkey(),
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                taker: taker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                maker_ata_b,
+                taker_ata_a,
+                taker_ata_b,
+                escrow, vault,
+                staged_take,
+                take_vault,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::ArbiterRelease { decision }.data(),
+        };
+
+        // Only the named arbiter may release
+        let tx = Transaction::new_signed_with_payer(
+            &[release_ix(&taker, crate::instructions::ArbiterDecision::Settle)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::UnauthorizedArbiter);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[release_ix(&arbiter, crate::instructions::ArbiterDecision::Settle)],
+            Some(&arbiter.pubkey()),
+            &[&arbiter],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Arbiter settle failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed");
+        assert!(!open_escrows(&svm, &maker.pubkey()).contains(&escrow));
+        assert!(svm.get_account(&staged_take).is_none(), "Stage should be closed");
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 200);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 80);
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000_000_000 - 200);
+    }
+
+    #[test]
+    fn test_arbiter_forced_refund() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        let arbiter = Keypair::new();
+        let admin = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&arbiter.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        let maker_ata_b = associated_token::get_associated_token_address(&maker.pubkey(), &mint_b);
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 15;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let staged_take = Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &PROGRAM_ID).0;
+        let take_vault = associated_token::get_associated_token_address(&staged_take, &mint_b);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 200, receive: 80, arbiter: arbiter.pubkey(), ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let stage_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::StageTake {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_b,
+                taker_ata_b,
+                escrow,
+                staged_take,
+                take_vault,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::StageTake { take_window: 0 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[stage_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Stage take failed");
+
+        let release_ix = |signer: &Keypair, decision: crate::instructions::ArbiterDecision| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ArbiterRelease {
+                arbiter: signer.pubkey(),
+                maker: maker.pubkey(),
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                taker: taker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                maker_ata_b,
+                taker_ata_a,
+                taker_ata_b,
+                escrow, vault,
+                staged_take,
+                take_vault,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::ArbiterRelease { decision }.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[release_ix(&arbiter, crate::instructions::ArbiterDecision::Refund)],
+            Some(&arbiter.pubkey()),
+            &[&arbiter],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Arbiter refund failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed");
+        assert!(!open_escrows(&svm, &maker.pubkey()).contains(&escrow));
+        assert!(svm.get_account(&staged_take).is_none(), "Stage should be closed");
+        assert!(svm.get_account(&take_vault).is_none(), "Take vault should be closed");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000_000_000);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000_000_000);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 0);
+    }
+    #[test]
+    fn test_pause_blocks_make_and_take_but_not_refund() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        let admin = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_a)
+            .owner(&taker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000_000_000).send().unwrap();
+
+        let seed: u64 = 16;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        // Only the config authority can pause
+        assert_error(
+            set_paused(&mut svm, &maker, config, true),
+            anchor_lang::error::ErrorCode::ConstraintHasOne,
+        );
+        set_paused(&mut svm, &admin, config, true).expect("Pause failed");
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 10, receive: 10, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            std::slice::from_ref(&make_ix),
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::ProgramPaused);
+
+        set_paused(&mut svm, &admin, config, false).expect("Unpause failed");
+        // The rejected make was recorded, so it needs a fresh blockhash to be resent
+        svm.expire_blockhash();
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        set_paused(&mut svm, &admin, config, true).expect("Pause failed");
+
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a,
+                taker_ata_b,
+                maker_ata_b,
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault,
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 10, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::ProgramPaused);
+
+        let refund_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: refund_metas(crate::accounts::Refund {
+                maker: maker.pubkey(),
+                refund_authority: None,
+                mint_a,
+                maker_ata_a,
+                refund_destination: None,
+                escrow, vault,
+                mint_a2: None, maker_ata_a2: None, vault_a2: None,
+                staged_take: crate::client::staged_take_pda(&escrow).0,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                associated_token_program: associated_token::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }),
+            data: crate::instruction::Refund { close_maker_ata: false }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[refund_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund should work while paused");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after refund");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000_000_000);
+    }
+    #[test]
+    fn test_dutch_auction_price_decays() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 100).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let seed: u64 = 21;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+
+        let make_accounts = crate::accounts::Make {
+            maker: maker.pubkey(),
+            mint_a, mint_b,
+            maker_ata_a,
+            escrow, vault,
+            maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+            paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+            paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+            config,
+            associated_token_program,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: SYSTEM_PROGRAM_ID,
+        }.to_account_metas(None);
+
+        // A floor above the starting price is rejected
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: make_accounts.clone(),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 100, receive: 1_000, floor_receive: 1_001, decay_end: now + 100, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::InvalidAuction);
+
+        // Price decays from 1000 to 400 of mint_b over 100 seconds
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: make_accounts,
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 100, receive: 1_000, floor_receive: 400, decay_end: now + 100, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let take_accounts = crate::accounts::Take {
+            taker: taker.pubkey(),
+            maker: maker.pubkey(),
+            mint_a, mint_b,
+            taker_ata_a,
+            taker_ata_b,
+            maker_ata_b,
+            escrow, vault,
+            mint_a2: None, taker_ata_a2: None, vault_a2: None,
+            royalty_ata_b: None,
+            maker_receive_account: None,
+            sol_unwrap: None,
+            oracle: None,
+            maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+            paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+            paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+            config,
+            fee_authority: admin.pubkey(),
+            fee_vault,
+            fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+            receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+            associated_token_program,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: SYSTEM_PROGRAM_ID,
+        }.to_account_metas(None);
+
+        // A quarter of the way through the price is 850, so half the vault costs 425
+        warp_to_timestamp(&mut svm, now + 25);
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: take_accounts.clone(),
+            data: crate::instruction::Take { take_amount: 50, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("First take failed");
+
+        let escrow_account = svm.get_account(&escrow).unwrap();
+        let escrow_data = read_escrow(&escrow_account);
+        assert_eq!(escrow_data.receive, 500, "Half of the starting price should remain");
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 425);
+
+        // Past decay_end the rest goes at the floor: 50 * 400 / 100 = 200
+        warp_to_timestamp(&mut svm, now + 500);
+        svm.expire_blockhash();
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: take_accounts,
+            data: crate::instruction::Take { take_amount: 50, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Second take failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed once the vault is empty");
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 625);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 100);
+    }
+    #[test]
+    fn test_take_many_is_atomic() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        let other_taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_a)
+            .owner(&taker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        CreateAssociatedTokenAccount::new(&mut svm, &admin, &mint_b)
+            .owner(&admin.pubkey()).send().unwrap();
+
+        // Escrow 2 is reserved for another taker
+        let offers = [(1u64, 100, 40, Pubkey::default()), (2, 200, 70, other_taker.pubkey()), (3, 300, 90, Pubkey::default())];
+        let mut escrows = vec![];
+        for (seed, deposit, receive, allowed_taker) in offers {
+            let escrow = Pubkey::find_program_address(
+                &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+                &PROGRAM_ID,
+            ).0;
+            let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+            let make_ix = Instruction {
+                program_id: PROGRAM_ID,
+                accounts: crate::accounts::Make {
+                    maker: maker.pubkey(),
+                    mint_a, mint_b,
+                    maker_ata_a,
+                    escrow, vault,
+                    maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                    paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                    paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                    config,
+                    associated_token_program,
+                    token_program: TOKEN_PROGRAM_ID,
+                    system_program: SYSTEM_PROGRAM_ID,
+                }.to_account_metas(None),
+                data: crate::instruction::Make { args: MakeArgs { seed, deposit, receive, allowed_taker, ..Default::default() } }.data(),
+            };
+            let tx = Transaction::new_signed_with_payer(
+                &[make_ix],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+            escrows.push((escrow, vault));
+        }
+
+        let take_many_ix = |batch: &[(Pubkey, Pubkey)]| {
+            let mut accounts = crate::accounts::TakeMany {
+                taker: taker.pubkey(),
+                config,
+                fee_authority: admin.pubkey(),
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None);
+            for (escrow, _) in batch {
+                accounts.extend(take_many_group(&maker.pubkey(), &taker.pubkey(), &admin.pubkey(), &mint_a, &mint_b, escrow));
+            }
+            Instruction {
+                program_id: PROGRAM_ID,
+                accounts,
+                data: crate::instruction::TakeMany { max_receives: vec![u64::MAX; batch.len()] }.data(),
+            }
+        };
+
+        // One bad escrow fails the whole batch
+        let tx = Transaction::new_signed_with_payer(
+            &[take_many_ix(&[escrows[0], escrows[1]])],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::UnauthorizedTaker);
+        assert!(svm.get_account(&escrows[0].0).is_some(), "Escrow 1 should survive the failed batch");
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 0);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 0);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[take_many_ix(&[escrows[0], escrows[2]])],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("TakeMany failed");
+
+        for (escrow, vault) in [escrows[0], escrows[2]] {
+            assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed");
+            assert!(svm.get_account(&vault).is_none(), "Vault should be closed");
+        }
+        assert!(svm.get_account(&escrows[1].0).is_some(), "Escrow 2 was not part of the batch");
+        assert_eq!(open_escrows(&svm, &maker.pubkey()), vec![escrows[1].0]);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 400);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 130);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 870);
+    }
+    #[test]
+    fn test_refund_and_take_return_rent_to_maker() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        let payer = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&payer.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_a)
+            .owner(&taker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        let fee_vault = CreateAssociatedTokenAccount::new(&mut svm, &admin, &mint_b)
+            .owner(&admin.pubkey()).send().unwrap();
+
+        let escrow_rent = svm.minimum_balance_for_rent_exemption(8 + crate::state::Escrow::INIT_SPACE);
+        let vault_rent = svm.minimum_balance_for_rent_exemption(spl_token::state::Account::LEN);
+
+        let make = |svm: &mut LiteSVM, seed: u64| {
+            let escrow = Pubkey::find_program_address(
+                &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+                &PROGRAM_ID,
+            ).0;
+            let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+            let make_ix = Instruction {
+                program_id: PROGRAM_ID,
+                accounts: crate::accounts::Make {
+                    maker: maker.pubkey(),
+                    mint_a, mint_b,
+                    maker_ata_a,
+                    escrow, vault,
+                    maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                    paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                    paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                    config,
+                    associated_token_program,
+                    token_program: TOKEN_PROGRAM_ID,
+                    system_program: SYSTEM_PROGRAM_ID,
+                }.to_account_metas(None),
+                data: crate::instruction::Make { args: MakeArgs { seed, deposit: 10, receive: 10, ..Default::default() } }.data(),
+            };
+            let tx = Transaction::new_signed_with_payer(
+                &[make_ix],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+            assert_eq!(svm.get_balance(&escrow).unwrap(), escrow_rent);
+            assert_eq!(svm.get_balance(&vault).unwrap(), vault_rent);
+            (escrow, vault)
+        };
+
+        // Refund, with a separate fee payer so the maker's delta is rent only
+        let (escrow, vault) = make(&mut svm, 1);
+        let maker_lamports = svm.get_balance(&maker.pubkey()).unwrap();
+        let refund_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: refund_metas(crate::accounts::Refund {
+                maker: maker.pubkey(),
+                refund_authority: None,
+                mint_a,
+                maker_ata_a,
+                refund_destination: None,
+                escrow, vault,
+                mint_a2: None, maker_ata_a2: None, vault_a2: None,
+                staged_take: crate::client::staged_take_pda(&escrow).0,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                associated_token_program: associated_token::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }),
+            data: crate::instruction::Refund { close_maker_ata: false }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[refund_ix],
+            Some(&payer.pubkey()),
+            &[&payer, &maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after refund");
+        assert!(svm.get_account(&vault).is_none(), "Vault should be closed after refund");
+        assert_eq!(
+            svm.get_balance(&maker.pubkey()).unwrap(),
+            maker_lamports + escrow_rent + vault_rent,
+            "Maker should get the escrow and vault rent back on refund"
+        );
+
+        // Take, where the taker pays the fee
+        let (escrow, vault) = make(&mut svm, 2);
+        let maker_lamports = svm.get_balance(&maker.pubkey()).unwrap();
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a,
+                taker_ata_b,
+                maker_ata_b,
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault,
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 10, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after take");
+        assert!(svm.get_account(&vault).is_none(), "Vault should be closed after take");
+        assert_eq!(
+            svm.get_balance(&maker.pubkey()).unwrap(),
+            maker_lamports + escrow_rent + vault_rent,
+            "Maker should get the escrow and vault rent back on take"
+        );
+    }
+    #[test]
+    fn test_lifecycle_with_client_helpers() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        assert_eq!(client::config_pda().0, config);
+
+        // Make then Take
+        let seed = 1;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let (escrow, bump) = client::escrow_pda(&maker.pubkey(), seed);
+        let vault = client::vault_address(&escrow, &mint_a);
+        let escrow_account = svm.get_account(&escrow).unwrap();
+        let escrow_data = read_escrow(&escrow_account);
+        assert_eq!(escrow_data.bump, bump);
+        assert_eq!(get_token_balance(&svm, &vault), 100);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 100, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after take");
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+        let maker_ata_b = associated_token::get_associated_token_address(&maker.pubkey(), &mint_b);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 100);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 40);
+
+        // Make then Refund
+        let seed = 2;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 300, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 600);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::refund_ix(&maker.pubkey(), &mint_a, seed)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund failed");
+
+        assert!(svm.get_account(&client::escrow_pda(&maker.pubkey(), seed).0).is_none(), "Escrow should be closed after refund");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 900);
+    }
+    #[test]
+    fn test_zero_copy_escrow_round_trips() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        let arbiter = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+
+        let seed = 42;
+        let args = MakeArgs {
+            seed,
+            deposit: 100,
+            receive: 80,
+            expiry: now + 1_000,
+            allowed_taker: taker.pubkey(),
+            arbiter: arbiter.pubkey(),
+            floor_receive: 60,
+            decay_end: now + 500,
+            receive_decimals: Some(6),
+            memo: [0; 32],
+            min_fill: 0,
+            all_or_nothing: false,
+            refund_cooldown: 300,
+            nonce: 0,
+            royalty_recipient: Pubkey::default(),
+            royalty_bps: 0,
+            namespace: [0; 8],
+            accepted_receive_mints: [Pubkey::default(); 4],
+            taker_tip: 0,
+            unwrap_sol: false,
+            settlement_hook: Pubkey::default(),
+            oracle: Pubkey::default(),
+            oracle_max_staleness: 0,
+            oracle_min_price: 0,
+            oracle_max_price: 0,
+            refund_authority: Pubkey::default(),
+            rounding: Default::default(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[crate::client::make_ix(&maker.pubkey(), &mint_a, &mint_b, args)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = crate::client::escrow_pda(&maker.pubkey(), seed);
+        let escrow_account = svm.get_account(&escrow.0).unwrap();
+        assert_eq!(std::mem::size_of::<crate::state::Escrow>(), crate::state::Escrow::INIT_SPACE);
+        assert_eq!(escrow_account.data.len(), 8 + crate::state::Escrow::INIT_SPACE);
+
+        let escrow_data = read_escrow(&escrow_account);
+        assert_eq!(escrow_data.seed, seed);
+        assert_eq!(escrow_data.maker, maker.pubkey());
+        assert_eq!(escrow_data.mint_a, mint_a);
+        assert_eq!(escrow_data.mint_b, mint_b);
+        assert_eq!(escrow_data.receive, 80);
+        assert_eq!(escrow_data.created_at, now);
+        assert_eq!(escrow_data.expiry, now + 1_000);
+        assert_eq!(escrow_data.allowed_taker, taker.pubkey());
+        assert_eq!(escrow_data.arbiter, arbiter.pubkey());
+        assert_eq!(escrow_data.start_receive, 80);
+        assert_eq!(escrow_data.floor_receive, 60);
+        assert_eq!(escrow_data.decay_end, now + 500);
+        assert_eq!(escrow_data.bump, escrow.1);
+        assert_eq!(escrow_data.receive_decimals, 6);
+        assert_eq!(escrow_data.refund_cooldown, 300);
+
+        // Take loads the escrow in place instead of Borsh-deserializing it
+        let tx = Transaction::new_signed_with_payer(
+            &[crate::client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 40, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        let meta = svm.send_transaction(tx).expect("Take failed");
+        assert_max_cu(&meta, 120_000);
+
+        let escrow_data = read_escrow(&svm.get_account(&escrow.0).unwrap());
+        assert_eq!(escrow_data.receive, 48, "The partial fill should be written back in place");
+        assert_eq!(escrow_data.seed, seed);
+        assert_eq!(escrow_data.arbiter, arbiter.pubkey());
+    }
+
+    #[test]
+    fn test_take_fails_when_vault_is_short() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let seed = 5;
+        let tx = Transaction::new_signed_with_payer(
+            &[crate::client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 50, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = crate::client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = crate::client::vault_address(&escrow, &mint_a);
+        assert_eq!(read_escrow(&svm.get_account(&escrow).unwrap()).deposit, 100);
+
+        // Drain part of the vault behind the program's back
+        let mut vault_account = svm.get_account(&vault).unwrap();
+        let mut token_account = spl_token::state::Account::unpack(&vault_account.data).unwrap();
+        token_account.amount = 60;
+        spl_token::state::Account::pack(token_account, &mut vault_account.data).unwrap();
+        svm.set_account(vault, vault_account).unwrap();
+
+        let tx = Transaction::new_signed_with_payer(
+            &[crate::client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 60, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::InsufficientVaultBalance);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000, "Taker should not have paid anything");
+        assert_eq!(get_token_balance(&svm, &vault), 60);
+    }
+    #[test]
+    fn test_token_2022_make_and_take() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let token_program = spl_token_2022::ID;
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .token_program_id(&token_program)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .token_program_id(&token_program)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).token_program_id(&token_program).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).token_program_id(&token_program).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).token_program_id(&token_program).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).token_program_id(&token_program).send().unwrap();
+
+        // Everything derived against Token-2022, including the vault
+        let ata = |owner: &Pubkey, mint: &Pubkey| {
+            associated_token::get_associated_token_address_with_program_id(owner, mint, &token_program)
+        };
+        let seed: u64 = 2022;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = ata(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 100, receive: 30, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        assert_eq!(svm.get_account(&vault).unwrap().owner, token_program, "Vault should live under Token-2022");
+        assert_eq!(get_token_balance(&svm, &vault), 100);
+
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a: ata(&taker.pubkey(), &mint_a),
+                taker_ata_b,
+                maker_ata_b: ata(&maker.pubkey(), &mint_b),
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault: ata(&admin.pubkey(), &mint_b),
+                fee_vault_a: ata(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 100, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after take");
+        assert!(svm.get_account(&vault).is_none(), "Vault should be closed after take");
+        assert_eq!(get_token_balance(&svm, &ata(&taker.pubkey(), &mint_a)), 100);
+        assert_eq!(get_token_balance(&svm, &ata(&maker.pubkey(), &mint_b)), 30);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 970);
+    }
+    #[test]
+    fn test_take_grosses_up_token_2022_transfer_fee() {
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let token_program = spl_token_2022::ID;
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .token_program_id(&token_program)
+            .send()
+            .unwrap();
+
+        // mint_b charges a 1% transfer fee
+        let mint_b = create_transfer_fee_mint(&mut svm, &taker, 100);
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).token_program_id(&token_program).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).token_program_id(&token_program).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).token_program_id(&token_program).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 10_000).token_program_id(&token_program).send().unwrap();
+
+        let ata = |owner: &Pubkey, mint: &Pubkey| {
+            associated_token::get_associated_token_address_with_program_id(owner, mint, &token_program)
+        };
+        let seed: u64 = 100;
+        let escrow = Pubkey::find_program_address(
+            &[b"escrow", maker.pubkey().as_ref(), &seed.to_le_bytes()],
+            &PROGRAM_ID,
+        ).0;
+        let vault = ata(&escrow, &mint_a);
+        let maker_ata_b = ata(&maker.pubkey(), &mint_b);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 100, receive: 1_000, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a: ata(&taker.pubkey(), &mint_a),
+                taker_ata_b,
+                maker_ata_b,
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault: ata(&admin.pubkey(), &mint_b),
+                fee_vault_a: ata(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 100, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        // 1011 sent, 11 withheld by the mint, 1000 lands with the maker
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 1_000, "Maker should net exactly receive");
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 10_000 - 1_011);
+    }
+    #[test]
+    fn test_take_with_wrong_mint_returns_invalid_mint() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let other_mint = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_other = CreateAssociatedTokenAccount::new(&mut svm, &taker, &other_mint)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &other_mint, &taker_ata_other, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let seed = 24;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        // Paying with a mint the maker never asked for is rejected by the escrow's has_one
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &other_mint, seed, &admin.pubkey(), 100, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::InvalidMint);
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        assert_eq!(get_token_balance(&svm, &client::vault_address(&escrow, &mint_a)), 100);
+        assert_eq!(get_token_balance(&svm, &taker_ata_other), 1_000);
+    }
+    #[test]
+    fn test_receive_decimals_checked_on_make_and_take() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        // The maker quotes receive in 9 decimals but mint_b has 6, so Make refuses
+        let seed = 25;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 40, receive_decimals: Some(9), ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::DecimalsMismatch);
+
+        // With the right decimals the escrow records them and Take checks mint_b against them
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 40, receive_decimals: Some(6), ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let escrow_data = read_escrow(&svm.get_account(&escrow).unwrap());
+        assert_eq!(escrow_data.receive_decimals, 6);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 100, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after take");
+        let maker_ata_b = associated_token::get_associated_token_address(&maker.pubkey(), &mint_b);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 40);
+    }
+    #[test]
+    fn test_cancel_escrow_with_and_without_stage() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let cancel_ix = |escrow: Pubkey, vault: Pubkey, staged_take: Pubkey, take_vault: Option<Pubkey>| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::CancelEscrow {
+                maker: maker.pubkey(),
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                mint_a,
+                maker_ata_a,
+                escrow, vault,
+                staged_take,
+                taker: take_vault.map(|_| taker.pubkey()),
+                mint_b: take_vault.map(|_| mint_b),
+                taker_ata_b: take_vault.map(|_| taker_ata_b),
+                take_vault,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::CancelEscrow.data(),
+        };
+
+        // No stage: cancel behaves like Refund
+        let seed = 26;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = client::vault_address(&escrow, &mint_a);
+        let staged_take = Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &PROGRAM_ID).0;
+
+        let tx = Transaction::new_signed_with_payer(
+            &[cancel_ix(escrow, vault, staged_take, None)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Cancel without stage failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after cancel");
+        assert!(svm.get_account(&vault).is_none(), "Vault should be closed after cancel");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000);
+
+        // Staged take: the taker's mint_b must come back in the same call
+        let seed = 27;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = client::vault_address(&escrow, &mint_a);
+        let staged_take = Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &PROGRAM_ID).0;
+        let take_vault = associated_token::get_associated_token_address(&staged_take, &mint_b);
+
+        let stage_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::StageTake {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_b,
+                taker_ata_b,
+                escrow,
+                staged_take,
+                take_vault,
+                config,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::StageTake { take_window: 0 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[stage_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Stage take failed");
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 960);
+
+        // Leaving out the taker side accounts would strand the stage
+        let tx = Transaction::new_signed_with_payer(
+            &[cancel_ix(escrow, vault, staged_take, None)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::StagedTakeActive);
+
+        // So would a plain Refund, which has no way to return the taker's mint_b
+        let tx = Transaction::new_signed_with_payer(
+            &[client::refund_ix(&maker.pubkey(), &mint_a, seed)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::StagedTakeActive);
+
+        let taker_lamports = svm.get_balance(&taker.pubkey()).unwrap();
+        let stage_rent = svm.get_balance(&staged_take).unwrap();
+        let take_vault_rent = svm.get_balance(&take_vault).unwrap();
+
+        let tx = Transaction::new_signed_with_payer(
+            &[cancel_ix(escrow, vault, staged_take, Some(take_vault))],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Cancel with stage failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after cancel");
+        assert!(!open_escrows(&svm, &maker.pubkey()).contains(&escrow));
+        assert!(svm.get_account(&staged_take).is_none(), "Stage should be closed after cancel");
+        assert!(svm.get_account(&take_vault).is_none(), "Take vault should be closed after cancel");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000);
+        assert_eq!(
+            svm.get_balance(&taker.pubkey()).unwrap(),
+            taker_lamports + stage_rent + take_vault_rent,
+        );
+
+        // Both cancels count as refunds
+        let config_account = svm.get_account(&config).unwrap();
+        let config_state = crate::state::Config::try_deserialize(&mut config_account.data.as_slice()).unwrap();
+        assert_eq!(config_state.total_refunds, 2);
+    }
+    // Adding a field to Escrow without updating this sum fails here before it under-allocates in Make
+    #[test]
+    fn test_escrow_init_space_matches_fields() {
+        use crate::state::{Config, Escrow, Receipt, StagedTake};
+
+        let expected = 1 // version
+            + 7 // _version_padding
+            + 8 // seed
+            + 32 * 4 // maker, seed_maker, mint_a, mint_b
+            + 8 // receive
+            + 8 // deposit
+            + 8 // created_at
+            + 8 // expiry
+            + 32 // allowed_taker
+            + 32 // arbiter
+            + 8 // start_receive
+            + 8 // floor_receive
+            + 8 // decay_end
+            + 32 // mint_a2
+            + 8 // deposit_a2
+            + 8 // min_fill
+            + 32 // memo
+            + 1 // bump
+            + 1 // receive_decimals
+            + 1 // all_or_nothing
+            + 5 // _padding
+            + 8 // refund_cooldown
+            + 8 // nonce
+            + 32 // royalty_recipient
+            + 2 // royalty_bps
+            + 6 // _royalty_padding
+            + 8 // namespace
+            + 32 * 4 // accepted_receive_mints
+            + 8 // taker_tip
+            + 1 // status
+            + 1 // status_before_stage
+            + 1 // unwrap_sol
+            + 1 // rounding
+            + 4 // _status_padding
+            + 32 // settlement_hook
+            + 32 // oracle
+            + 8 // oracle_max_staleness
+            + 8 // oracle_min_price
+            + 8 // oracle_max_price
+            + 32; // refund_authority
+        assert_eq!(Escrow::INIT_SPACE, expected);
+        assert_eq!(std::mem::size_of::<Escrow>(), Escrow::INIT_SPACE);
+
+        assert_eq!(StagedTake::INIT_SPACE, 32 + 32 + 8 + 8 + 1);
+        assert_eq!(Receipt::INIT_SPACE, 32 * 5 + 8 + 8 + 4 + 8 + 8 + 8 + 1);
+        assert_eq!(Config::INIT_SPACE, 32 + 2 + 2 + 2 + 1 + 1 + 8 + 2 + 8 * 3 + (4 + 32 * Config::MAX_ALLOWED_MINTS) + (4 + 34 * Config::MAX_FEE_TIERS) + 1);
+    }
+    #[test]
+    fn test_basket_take_releases_both_vaults() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_a2 = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(9)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_a2 = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a2)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_a2 = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_a2)
+            .owner(&taker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a2, &maker_ata_a2, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let seed = 28;
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = client::vault_address(&escrow, &mint_a);
+        let vault_a2 = client::vault_address(&escrow, &mint_a2);
+
+        let make_basket_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::MakeBasket {
+                maker: maker.pubkey(),
+                mint_a, mint_a2, mint_b,
+                maker_ata_a, maker_ata_a2,
+                escrow, vault, vault_a2,
+                maker_index: client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                paused_mint_a2: client::paused_mint_pda(&mint_a2).0,
+                paused_mint_b: client::paused_mint_pda(&mint_b).0,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::MakeBasket {
+                args: crate::instructions::MakeBasketArgs { seed, deposit: 100, deposit_a2: 250, receive: 40, ..Default::default() },
+            }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_basket_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make basket failed");
+
+        let escrow_data = read_escrow(&svm.get_account(&escrow).unwrap());
+        assert_eq!(escrow_data.mint_a2, mint_a2);
+        assert_eq!(escrow_data.deposit_a2, 250);
+        assert_eq!(get_token_balance(&svm, &vault), 100);
+        assert_eq!(get_token_balance(&svm, &vault_a2), 250);
+
+        let take_ix = |take_amount: u64, basket: bool| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a: associated_token::get_associated_token_address(&taker.pubkey(), &mint_a),
+                taker_ata_b,
+                maker_ata_b: associated_token::get_associated_token_address(&maker.pubkey(), &mint_b),
+                escrow, vault,
+                mint_a2: basket.then_some(mint_a2),
+                taker_ata_a2: basket.then_some(taker_ata_a2),
+                vault_a2: basket.then_some(vault_a2),
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault: associated_token::get_associated_token_address(&admin.pubkey(), &mint_b),
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount, max_receive: u64::MAX }.data(),
+        };
+
+        // Without the second vault the basket can't be released
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix(100, false)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::BasketAccountsMissing);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix(50, true)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::BasketPartialFill);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix(100, true)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Basket take failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after take");
+        assert!(svm.get_account(&vault).is_none(), "Vault should be closed after take");
+        assert!(svm.get_account(&vault_a2).is_none(), "Second vault should be closed after take");
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+        let maker_ata_b = associated_token::get_associated_token_address(&maker.pubkey(), &mint_b);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 100);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a2), 250);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 40);
+    }
+    #[test]
+    fn test_make_stores_memo() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        // Short references are zero padded to the fixed 32 bytes
+        let mut memo = [0u8; 32];
+        memo[..13].copy_from_slice(b"order-#000129");
+
+        let seed = 29;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 40, memo, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let escrow_data = read_escrow(&svm.get_account(&escrow).unwrap());
+        assert_eq!(escrow_data.memo, memo);
+        assert_eq!(&escrow_data.memo[..13], b"order-#000129");
+        assert!(escrow_data.memo[13..].iter().all(|&byte| byte == 0));
+        // The fields around the memo are unaffected by it
+        assert_eq!(escrow_data.deposit_a2, 0);
+        assert_eq!(escrow_data.receive_decimals, 6);
+    }
+    #[test]
+    fn test_transfer_maker_hands_off_refund() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let new_maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&new_maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let new_maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &new_maker, &mint_a)
+            .owner(&new_maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let seed = 30;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = client::vault_address(&escrow, &mint_a);
+
+        let transfer_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::TransferMaker {
+                maker: maker.pubkey(),
+                escrow,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                new_maker_index: crate::client::maker_index_pda(&new_maker.pubkey()).0,
+                config: crate::client::config_pda().0,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::TransferMaker { new_maker: new_maker.pubkey() }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[transfer_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Transfer maker failed");
+
+        // Same PDA, new owner
+        let escrow_data = read_escrow(&svm.get_account(&escrow).unwrap());
+        assert_eq!(escrow_data.maker, new_maker.pubkey());
+        assert_eq!(escrow_data.seed_maker, maker.pubkey());
+        assert!(!open_escrows(&svm, &maker.pubkey()).contains(&escrow));
+        assert_eq!(open_escrows(&svm, &new_maker.pubkey()), vec![escrow]);
+
+        // The original maker can no longer refund
+        let tx = Transaction::new_signed_with_payer(
+            &[client::refund_ix(&maker.pubkey(), &mint_a, seed)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::InvalidMaker);
+
+        let refund_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: refund_metas(crate::accounts::Refund {
+                maker: new_maker.pubkey(),
+                refund_authority: None,
+                mint_a,
+                maker_ata_a: new_maker_ata_a,
+                refund_destination: None,
+                escrow, vault,
+                mint_a2: None, maker_ata_a2: None, vault_a2: None,
+                staged_take: crate::client::staged_take_pda(&escrow).0,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                associated_token_program: associated_token::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }),
+            data: crate::instruction::Refund { close_maker_ata: false }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[refund_ix],
+            Some(&new_maker.pubkey()),
+            &[&new_maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund by new maker failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after refund");
+        assert!(open_escrows(&svm, &new_maker.pubkey()).is_empty());
+        assert_eq!(get_token_balance(&svm, &new_maker_ata_a), 100);
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 900);
+    }
+    #[test]
+    fn test_min_fill_and_all_or_nothing() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let make = |svm: &mut LiteSVM, args: MakeArgs| {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, args)],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+        };
+        let take = |svm: &mut LiteSVM, seed: u64, take_amount: u64| {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), take_amount, u64::MAX)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        // min_fill rejects dust but still allows fills at or above it
+        make(&mut svm, MakeArgs { seed: 31, deposit: 100, receive: 100, min_fill: 30, ..Default::default() });
+        assert_error(take(&mut svm, 31, 10), crate::error::EscrowError::FillTooSmall);
+        take(&mut svm, 31, 30).expect("Take at min_fill failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 31).0;
+        assert_eq!(get_token_balance(&svm, &client::vault_address(&escrow, &mint_a)), 70);
+
+        // all_or_nothing only accepts the whole vault
+        make(&mut svm, MakeArgs { seed: 32, deposit: 100, receive: 100, all_or_nothing: true, ..Default::default() });
+        let escrow = client::escrow_pda(&maker.pubkey(), 32).0;
+        assert_eq!(read_escrow(&svm.get_account(&escrow).unwrap()).all_or_nothing, 1);
+
+        assert_error(take(&mut svm, 32, 50), crate::error::EscrowError::PartialFillNotAllowed);
+        take(&mut svm, 32, 100).expect("Full take failed");
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after a full take");
+    }
+    #[test]
+    fn test_effective_receive_over_time() {
+        use crate::{pricing::{effective_receive, protocol_fee}, state::Escrow};
+
+        // 1_000 receive decaying to 600 between t=1_000 and t=2_000
+        let escrow = Escrow {
+            receive: 1_000,
+            start_receive: 1_000,
+            floor_receive: 600,
+            created_at: 1_000,
+            decay_end: 2_000,
+            ..bytemuck::Zeroable::zeroed()
+        };
+
+        assert_eq!(effective_receive(&escrow, 500).unwrap(), 1_000, "Before creation the start price holds");
+        assert_eq!(effective_receive(&escrow, 1_000).unwrap(), 1_000);
+        assert_eq!(effective_receive(&escrow, 1_250).unwrap(), 900);
+        assert_eq!(effective_receive(&escrow, 1_500).unwrap(), 800);
+        assert_eq!(effective_receive(&escrow, 1_999).unwrap(), 601);
+        assert_eq!(effective_receive(&escrow, 2_000).unwrap(), 600);
+        assert_eq!(effective_receive(&escrow, 9_000).unwrap(), 600, "After decay_end the floor holds");
+
+        // After a partial fill the price scales with what is left, rounded up for the maker
+        let half_filled = Escrow { receive: 333, ..escrow };
+        assert_eq!(effective_receive(&half_filled, 1_500).unwrap(), 267);
+
+        // Fixed price escrows ignore the clock
+        let fixed = Escrow { decay_end: 0, ..escrow };
+        assert_eq!(effective_receive(&fixed, 0).unwrap(), 1_000);
+        assert_eq!(effective_receive(&fixed, 9_000).unwrap(), 1_000);
+
+        // The fee is carved out of what the taker pays
+        assert_eq!(protocol_fee(effective_receive(&escrow, 1_500).unwrap(), 250).unwrap(), 20);
+        assert_eq!(protocol_fee(effective_receive(&escrow, 1_500).unwrap(), 0).unwrap(), 0);
+        assert_eq!(protocol_fee(999, 1).unwrap(), 0, "Fees round down");
+    }
+    #[test]
+    fn test_take_rejects_look_alike_escrow() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let seed = 33;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        // A program owned copy of the escrow at an address that is not its PDA
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let look_alike = Pubkey::new_unique();
+        svm.set_account(look_alike, svm.get_account(&escrow).unwrap()).unwrap();
+
+        let mut take_ix = client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 100, u64::MAX);
+        for meta in take_ix.accounts.iter_mut().filter(|meta| meta.pubkey == escrow) {
+            meta.pubkey = look_alike;
+        }
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), anchor_lang::error::ErrorCode::ConstraintSeeds);
+
+        assert_eq!(get_token_balance(&svm, &client::vault_address(&escrow, &mint_a)), 100);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000);
+    }
+    #[test]
+    fn test_take_lifecycle_after_state_first_reorder() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 100);
+
+        let seed = 34;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 200, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = client::vault_address(&escrow, &mint_a);
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+        let maker_ata_b = associated_token::get_associated_token_address(&maker.pubkey(), &mint_b);
+        let fee_vault = associated_token::get_associated_token_address(&admin.pubkey(), &mint_b);
+
+        // Partial take: the escrow is written before the transfers and stays consistent with them
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 40, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Partial take failed");
+
+        let escrow_data = read_escrow(&svm.get_account(&escrow).unwrap());
+        assert_eq!(escrow_data.receive, 120);
+        assert_eq!(escrow_data.deposit, 60);
+        assert_eq!(get_token_balance(&svm, &vault), 60);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 40);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 80);
+        assert_eq!(get_token_balance(&svm, &fee_vault), 0);
+
+        // Final take closes everything
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 60, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Final take failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after the final take");
+        assert!(svm.get_account(&vault).is_none(), "Vault should be closed after the final take");
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 100);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b) + get_token_balance(&svm, &fee_vault), 200);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 800);
+    }
+    #[test]
+    fn test_receive_mint_allowlist() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let stable = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let other = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let add_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::AddAllowedMint {
+                authority: admin.pubkey(),
+                config,
+            }.to_account_metas(None),
+            data: crate::instruction::AddAllowedMint { mint: stable }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[add_ix],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Add allowed mint failed");
+
+        let config_data = crate::state::Config::try_deserialize(
+            &mut svm.get_account(&config).unwrap().data.as_slice()
+        ).unwrap();
+        assert_eq!(config_data.allowed_receive_mints, vec![stable]);
+
+        let make = |svm: &mut LiteSVM, seed: u64, mint_b: &Pubkey| {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, mint_b, MakeArgs { seed, deposit: 100, receive: 40, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        assert_error(make(&mut svm, 35, &other), crate::error::EscrowError::MintNotAllowed);
+        make(&mut svm, 35, &stable).expect("Make with an allowlisted mint failed");
+
+        // Emptying the allowlist lets any mint_b through again
+        let remove_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::RemoveAllowedMint {
+                authority: admin.pubkey(),
+                config,
+            }.to_account_metas(None),
+            data: crate::instruction::RemoveAllowedMint { mint: stable }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[remove_ix],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Remove allowed mint failed");
+
+        make(&mut svm, 36, &other).expect("Make after clearing the allowlist failed");
+    }
+    #[test]
+    fn test_config_counts_makes_takes_and_refunds() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let read_config = |svm: &LiteSVM| crate::state::Config::try_deserialize(
+            &mut svm.get_account(&config).unwrap().data.as_slice()
+        ).unwrap();
+        let config_data = read_config(&svm);
+        assert_eq!((config_data.total_makes, config_data.total_takes, config_data.total_refunds), (0, 0, 0));
+
+        // Make + Take
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 100, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), 100, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        // Make + Refund
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 2, deposit: 100, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::refund_ix(&maker.pubkey(), &mint_a, 2)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund failed");
+
+        let config_data = read_config(&svm);
+        assert_eq!(config_data.total_makes, 2);
+        assert_eq!(config_data.total_takes, 1);
+        assert_eq!(config_data.total_refunds, 1);
+    }
+    #[test]
+    fn test_take_withholds_taker_fee_in_mint_a() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        // 2.5% of the mint_a leg
+        let set_taker_fee_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::SetTakerFee {
+                authority: admin.pubkey(),
+                config,
+            }.to_account_metas(None),
+            data: crate::instruction::SetTakerFee { taker_fee_bps: 250 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[set_taker_fee_ix],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Set taker fee failed");
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 1_000, receive: 400, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), 1_000, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx).expect("Take failed");
+
+        let taker_fee = 25;
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+        let fee_vault_a = associated_token::get_associated_token_address(&admin.pubkey(), &mint_a);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 1_000 - taker_fee);
+        assert_eq!(get_token_balance(&svm, &fee_vault_a), taker_fee);
+
+        let taken: crate::events::EscrowTaken = find_event(&result.logs).expect("EscrowTaken not emitted");
+        assert_eq!(taken.deposit, 1_000);
+        assert_eq!(taken.taker_fee, taker_fee);
+
+        // TakeMany withholds the same fee
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 2, deposit: 1_000, receive: 400, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 2).0;
+        let mut accounts = crate::accounts::TakeMany {
+            taker: taker.pubkey(),
+            config,
+            fee_authority: admin.pubkey(),
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: SYSTEM_PROGRAM_ID,
+        }.to_account_metas(None);
+        accounts.extend(take_many_group(&maker.pubkey(), &taker.pubkey(), &admin.pubkey(), &mint_a, &mint_b, &escrow));
+        let take_many_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts,
+            data: crate::instruction::TakeMany { max_receives: vec![u64::MAX] }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_many_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx).expect("TakeMany failed");
+
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 2 * (1_000 - taker_fee));
+        assert_eq!(get_token_balance(&svm, &fee_vault_a), 2 * taker_fee);
+        let taken: crate::events::EscrowTaken = find_event(&result.logs).expect("EscrowTaken not emitted");
+        assert_eq!(taken.taker_fee, taker_fee);
+
+        let receipt_account = svm.get_account(&client::receipt_pda(&escrow, &taker.pubkey()).0).expect("TakeMany should leave a receipt");
+        let receipt = crate::state::Receipt::try_deserialize(&mut receipt_account.data.as_slice()).unwrap();
+        assert_eq!(receipt.escrow, escrow);
+        assert_eq!(receipt.maker, maker.pubkey());
+        assert_eq!((receipt.amount_a, receipt.amount_b, receipt.fills), (1_000 - taker_fee, 400, 1));
+
+        // So does TakeWithSol
+        ensure_native_mint(&mut svm);
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &native_mint::ID, MakeArgs { seed: 3, deposit: 1_000, receive: LAMPORTS_PER_SOL, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 3).0;
+        let take_with_sol_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::TakeWithSol {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                maker_index: client::maker_index_pda(&maker.pubkey()).0,
+                mint_a,
+                mint_b: native_mint::ID,
+                taker_ata_a,
+                escrow,
+                vault: client::vault_address(&escrow, &mint_a),
+                config,
+                paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: client::paused_mint_pda(&native_mint::ID).0,
+                fee_authority: admin.pubkey(),
+                fee_vault_a,
+                receipt: client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program: spl_associated_token_account::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::TakeWithSol { take_amount: 1_000, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_with_sol_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx).expect("TakeWithSol failed");
+
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 3 * (1_000 - taker_fee));
+        assert_eq!(get_token_balance(&svm, &fee_vault_a), 3 * taker_fee);
+        let taken: crate::events::EscrowTaken = find_event(&result.logs).expect("EscrowTaken not emitted");
+        assert_eq!(taken.taker_fee, taker_fee);
+    }
+    #[test]
+    fn test_refund_to_alternate_destination() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        // A second mint_a account the maker owns, outside the ATA
+        let destination = CreateAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 400, receive: 100, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        let refund_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: refund_metas(crate::accounts::Refund {
+                maker: maker.pubkey(),
+                refund_authority: None,
+                mint_a,
+                maker_ata_a,
+                refund_destination: Some(destination),
+                escrow,
+                vault: client::vault_address(&escrow, &mint_a),
+                mint_a2: None, maker_ata_a2: None, vault_a2: None,
+                staged_take: crate::client::staged_take_pda(&escrow).0,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                associated_token_program: associated_token::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }),
+            data: crate::instruction::Refund { close_maker_ata: false }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[refund_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund failed");
+
+        assert_eq!(get_token_balance(&svm, &destination), 400);
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 600);
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after refund");
+    }
+    #[test]
+    fn test_pricing_overflow_returns_error() {
+        use crate::{error::EscrowError, pricing::{effective_receive, protocol_fee}, state::{Config, Escrow}};
+
+        let overflow: anchor_lang::error::Error = EscrowError::MathOverflow.into();
+
+        // The largest fee the config allows still fits
+        assert_eq!(protocol_fee(u64::MAX, Config::MAX_FEE_BPS).unwrap(), u64::MAX / 10);
+        // A fee above 100% of u64::MAX does not, and errors instead of truncating
+        assert_eq!(protocol_fee(u64::MAX, u16::MAX).unwrap_err(), overflow);
+
+        // Pro rata fills on u64::MAX sized escrows stay in range
+        let escrow = Escrow {
+            receive: u64::MAX,
+            start_receive: u64::MAX,
+            floor_receive: u64::MAX / 2,
+            created_at: 0,
+            decay_end: 1_000,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        assert_eq!(effective_receive(&escrow, 0).unwrap(), u64::MAX);
+        assert_eq!(escrow.fill(u64::MAX / 2, u64::MAX, 0).unwrap(), (u64::MAX / 2, u64::MAX / 2));
+
+        // A floor above the start price would underflow the decay
+        let inverted = Escrow { floor_receive: u64::MAX, start_receive: 1, ..escrow };
+        assert_eq!(effective_receive(&inverted, 500).unwrap_err(), overflow);
+    }
+    #[test]
+    fn test_load_escrow_before_and_after_take() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let load = |svm: &LiteSVM, key: &Pubkey| svm.get_account(key).and_then(|account| client::load_escrow(&account.data));
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 100, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        let escrow_data = load(&svm, &escrow).expect("Escrow should load after make");
+        assert_eq!(escrow_data.maker, maker.pubkey());
+        assert_eq!(escrow_data.mint_a, mint_a);
+        assert_eq!(escrow_data.deposit, 100);
+        assert_eq!(escrow_data.receive, 40);
+
+        // Other account types are rejected by the discriminator
+        assert!(load(&svm, &config).is_none());
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), 100, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        assert!(load(&svm, &escrow).is_none(), "Escrow should not load once closed");
+    }
+    #[test]
+    fn test_split_escrow_into_two_takeable_halves() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 100, receive: 50, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        // Move 40 of the 100 into a second escrow under seed 2
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        let new_escrow = client::escrow_pda(&maker.pubkey(), 2).0;
+        let split_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::SplitEscrow {
+                maker: maker.pubkey(),
+                mint_a,
+                escrow,
+                vault: client::vault_address(&escrow, &mint_a),
+                staged_take: Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &PROGRAM_ID).0,
+                new_escrow,
+                new_vault: client::vault_address(&new_escrow, &mint_a),
+                maker_index: client::maker_index_pda(&maker.pubkey()).0,
+                config: client::config_pda().0,
+                associated_token_program: spl_associated_token_account::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::SplitEscrow { new_seed: 2, amount: 40 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[split_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Split failed");
+
+        let original = read_escrow(&svm.get_account(&escrow).unwrap());
+        let split = read_escrow(&svm.get_account(&new_escrow).unwrap());
+        assert_eq!((original.deposit, original.receive), (60, 30));
+        assert_eq!((split.deposit, split.receive), (40, 20));
+        assert_eq!(split.maker, maker.pubkey());
+        assert_eq!(split.mint_b, mint_b);
+        assert_eq!(get_token_balance(&svm, &client::vault_address(&escrow, &mint_a)), 60);
+        assert_eq!(get_token_balance(&svm, &client::vault_address(&new_escrow, &mint_a)), 40);
+
+        // Each half is taken on its own for its share of the original receive
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+        let maker_ata_b = associated_token::get_associated_token_address(&maker.pubkey(), &mint_b);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), 60, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take of the original failed");
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 60);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 30);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 2, &admin.pubkey(), 40, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take of the split failed");
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 100);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 50);
+        assert!(svm.get_account(&escrow).is_none());
+        assert!(svm.get_account(&new_escrow).is_none());
+    }
+    #[test]
+    fn test_merge_escrows() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let other_mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        for (seed, deposit, receive, mint_b) in [(1, 60, 30, mint_b), (2, 40, 20, mint_b), (3, 10, 5, other_mint_b)] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit, receive, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+        }
+
+        let merge = |svm: &mut LiteSVM, seed: u64, other_seed: u64| {
+            let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+            let other = client::escrow_pda(&maker.pubkey(), other_seed).0;
+            let merge_ix = Instruction {
+                program_id: PROGRAM_ID,
+                accounts: crate::accounts::MergeEscrows {
+                    maker: maker.pubkey(),
+                    maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                    mint_a,
+                    escrow,
+                    vault: client::vault_address(&escrow, &mint_a),
+                    other,
+                    other_vault: client::vault_address(&other, &mint_a),
+                    staged_take: Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &PROGRAM_ID).0,
+                    other_staged_take: Pubkey::find_program_address(&[b"stage", other.as_ref()], &PROGRAM_ID).0,
+                    token_program: TOKEN_PROGRAM_ID,
+                    system_program: SYSTEM_PROGRAM_ID,
+                }.to_account_metas(None),
+                data: crate::instruction::MergeEscrows.data(),
+            };
+            let tx = Transaction::new_signed_with_payer(
+                &[merge_ix],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        // Different mint_b
+        assert_error(merge(&mut svm, 1, 3), crate::error::EscrowError::MergeMismatch);
+
+        merge(&mut svm, 1, 2).expect("Merge failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        let other = client::escrow_pda(&maker.pubkey(), 2).0;
+        let merged = read_escrow(&svm.get_account(&escrow).unwrap());
+        assert_eq!(merged.deposit, 100);
+        assert_eq!(merged.receive, 50);
+        assert_eq!(get_token_balance(&svm, &client::vault_address(&escrow, &mint_a)), 100);
+        assert!(svm.get_account(&other).is_none(), "The merged escrow should be closed");
+        assert!(!open_escrows(&svm, &maker.pubkey()).contains(&other));
+        assert!(open_escrows(&svm, &maker.pubkey()).contains(&escrow));
+        assert!(svm.get_account(&client::vault_address(&other, &mint_a)).is_none(), "The merged vault should be closed");
+
+        // A cooldown escrow can't be folded into a plain one and refunded straight away
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 4, deposit: 10, receive: 5, refund_cooldown: 3_600, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+        assert_error(merge(&mut svm, 1, 4), crate::error::EscrowError::MergeMismatch);
+
+        let refund = |svm: &mut LiteSVM, seed: u64| {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::refund_ix(&maker.pubkey(), &mint_a, seed)],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+        refund(&mut svm, 1).expect("Refund of the plain escrow failed");
+        assert_error(refund(&mut svm, 4), crate::error::EscrowError::CooldownActive);
+        assert_eq!(get_token_balance(&svm, &client::vault_address(&client::escrow_pda(&maker.pubkey(), 4).0, &mint_a)), 10);
+    }
+    #[test]
+    fn test_reclaim_expired_pays_cranker_bounty() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let cranker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&cranker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        // 1% of the vault to the cranker
+        let set_cranker_bps_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::SetCrankerBps {
+                authority: admin.pubkey(),
+                config,
+            }.to_account_metas(None),
+            data: crate::instruction::SetCrankerBps { cranker_bps: 100 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[set_cranker_bps_ix],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Set cranker bps failed");
+
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 500, receive: 100, expiry: now + 60, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        warp_to_timestamp(&mut svm, now + 60);
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        let cranker_ata_a = associated_token::get_associated_token_address(&cranker.pubkey(), &mint_a);
+        let reclaim_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ReclaimExpired {
+                cranker: cranker.pubkey(),
+                maker: maker.pubkey(),
+                staged_take: crate::client::staged_take_pda(&escrow).0,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                mint_a,
+                maker_ata_a,
+                cranker_ata_a,
+                escrow,
+                vault: client::vault_address(&escrow, &mint_a),
+                config,
+                associated_token_program: spl_associated_token_account::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::ReclaimExpired.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[reclaim_ix],
+            Some(&cranker.pubkey()),
+            &[&cranker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Reclaim failed");
+
+        assert_eq!(get_token_balance(&svm, &cranker_ata_a), 5);
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000 - 5);
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after reclaim");
+        let config_data = crate::state::Config::try_deserialize(&mut svm.get_account(&config).unwrap().data.as_slice()).unwrap();
+        assert_eq!(config_data.total_refunds, 1, "A reclaim counts as a refund");
+        assert!(!open_escrows(&svm, &maker.pubkey()).contains(&escrow));
+    }
+    #[test]
+    fn test_make_reuses_existing_vault() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let stranger = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&stranger.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        let vault = client::vault_address(&escrow, &mint_a);
+        let make = |svm: &mut LiteSVM| {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 100, receive: 50, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+        let refund = |svm: &mut LiteSVM| {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::refund_ix(&maker.pubkey(), &mint_a, 1)],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        make(&mut svm).expect("First make failed");
+        refund(&mut svm).expect("Refund failed");
+        assert!(svm.get_account(&escrow).is_none());
+
+        // Someone else opens the vault ATA before the seed is reused
+        CreateAssociatedTokenAccount::new(&mut svm, &stranger, &mint_a)
+            .owner(&escrow).send().unwrap();
+        assert_eq!(get_token_balance(&svm, &vault), 0);
+
+        svm.expire_blockhash();
+        make(&mut svm).expect("Make over an existing vault failed");
+        assert_eq!(get_token_balance(&svm, &vault), 100);
+        assert_eq!(read_escrow(&svm.get_account(&escrow).unwrap()).deposit, 100);
+
+        refund(&mut svm).expect("Second refund failed");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000);
+    }
+    #[test]
+    fn test_take_rejects_taker_owned_maker_ata_b() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 100, receive: 50, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        // Point the maker's proceeds back at the taker's own mint_b account
+        let mut take_ix = client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), 100, u64::MAX);
+        let maker_ata_b = associated_token::get_associated_token_address(&maker.pubkey(), &mint_b);
+        for meta in take_ix.accounts.iter_mut().filter(|meta| meta.pubkey == maker_ata_b) {
+            meta.pubkey = taker_ata_b;
+        }
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        // The associated token check on maker_ata_b runs before the explicit InvalidTokenAccount constraint
+        assert_error(svm.send_transaction(tx), anchor_lang::error::ErrorCode::ConstraintTokenOwner);
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        assert_eq!(get_token_balance(&svm, &client::vault_address(&escrow, &mint_a)), 100);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000);
+    }
+    #[test]
+    fn test_quote_matches_take() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 250);
+
+        // Dutch auction so the quote depends on the clock
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs {
+                seed: 1,
+                deposit: 100,
+                receive: 400,
+                floor_receive: 200,
+                decay_end: now + 100,
+                ..Default::default()
+            })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        warp_to_timestamp(&mut svm, now + 30);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::quote_ix(&maker.pubkey(), &mint_a, 1, 60)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx).expect("Quote failed");
+        let quote: crate::events::EscrowQuote = find_event(&result.logs).expect("EscrowQuote not emitted");
+
+        // Nothing moved
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        assert_eq!(get_token_balance(&svm, &client::vault_address(&escrow, &mint_a)), 100);
+        assert_eq!(read_escrow(&svm.get_account(&escrow).unwrap()).receive, 400);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), 60, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx).expect("Take failed");
+        let taken: crate::events::EscrowTaken = find_event(&result.logs).expect("EscrowTaken not emitted");
+
+        assert_eq!(quote.escrow, escrow);
+        assert_eq!(quote.deposit, taken.deposit);
+        assert_eq!(quote.receive, taken.receive);
+        assert_eq!(quote.fee, taken.fee);
+        assert_eq!(quote.taker_fee, taken.taker_fee);
+        // 30% of the way from 400 to 200 is 340, 60 of 100 is 204
+        assert_eq!(quote.receive, 204);
+    }
+    #[test]
+    fn test_take_and_refund_return_escrow_and_vault_rent_to_maker() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        for seed in [1, 2] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 50, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+        }
+
+        let rent_of = |svm: &LiteSVM, seed: u64| {
+            let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+            let vault = client::vault_address(&escrow, &mint_a);
+            (escrow, vault, svm.get_balance(&escrow).unwrap() + svm.get_balance(&vault).unwrap())
+        };
+
+        // Take: the taker pays the fees and any ATA rent, the maker only gains
+        let (escrow, vault, rent) = rent_of(&svm, 1);
+        let maker_lamports = svm.get_balance(&maker.pubkey()).unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), 100, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+        assert_eq!(svm.get_balance(&maker.pubkey()).unwrap(), maker_lamports + rent);
+        assert!(svm.get_account(&escrow).is_none_or(|account| account.lamports == 0));
+        assert!(svm.get_account(&vault).is_none_or(|account| account.lamports == 0));
+
+        // Refund: the taker pays the transaction fee so the maker's delta is only rent
+        let (escrow, vault, rent) = rent_of(&svm, 2);
+        let maker_lamports = svm.get_balance(&maker.pubkey()).unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[client::refund_ix(&maker.pubkey(), &mint_a, 2)],
+            Some(&taker.pubkey()),
+            &[&taker, &maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund failed");
+        assert_eq!(svm.get_balance(&maker.pubkey()).unwrap(), maker_lamports + rent);
+        assert!(svm.get_account(&escrow).is_none_or(|account| account.lamports == 0));
+        assert!(svm.get_account(&vault).is_none_or(|account| account.lamports == 0));
+    }
+    #[test]
+    fn test_migrate_v1_escrow() {
+        use crate::state::Escrow;
+
+        let mut svm = setup();
+
+        let payer = Keypair::new();
+        svm.airdrop(&payer.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        // A v1 account is the current body without the 8 byte version header or any field from refund_cooldown on
+        let maker = Pubkey::new_unique();
+        let original = Escrow {
+            seed: 9,
+            maker,
+            seed_maker: maker,
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            receive: 40,
+            deposit: 100,
+            memo: [7; 32],
+            bump: 254,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        let mut v1_data = <Escrow as anchor_lang::Discriminator>::DISCRIMINATOR.to_vec();
+        v1_data.extend_from_slice(&bytemuck::bytes_of(&original)[8..8 + Escrow::V1_LEN]);
+        assert_eq!(v1_data.len(), 8 + Escrow::V1_LEN);
+
+        let escrow = Pubkey::new_unique();
+        svm.set_account(escrow, Account {
+            lamports: svm.minimum_balance_for_rent_exemption(v1_data.len()),
+            data: v1_data,
+            owner: PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        }).unwrap();
+
+        let migrate_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Migrate {
+                payer: payer.pubkey(),
+                escrow,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Migrate.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            std::slice::from_ref(&migrate_ix),
+            Some(&payer.pubkey()),
+            &[&payer],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Migrate failed");
+
+        let account = svm.get_account(&escrow).unwrap();
+        assert_eq!(account.data.len(), 8 + Escrow::INIT_SPACE);
+        assert!(account.lamports >= svm.minimum_balance_for_rent_exemption(account.data.len()));
+
+        let migrated = read_escrow(&account);
+        assert_eq!(migrated.version, Escrow::CURRENT_VERSION);
+        assert_eq!(migrated._version_padding, [0; 7]);
+        assert_eq!(migrated.seed, 9);
+        assert_eq!(migrated.maker, maker);
+        assert_eq!(migrated.mint_a, original.mint_a);
+        assert_eq!(migrated.receive, 40);
+        assert_eq!(migrated.deposit, 100);
+        assert_eq!(migrated.memo, [7; 32]);
+        assert_eq!(migrated.bump, 254);
+
+        // Already current
+        svm.expire_blockhash();
+        let tx = Transaction::new_signed_with_payer(
+            &[migrate_ix],
+            Some(&payer.pubkey()),
+            &[&payer],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::UnsupportedVersion);
+    }
+    #[test]
+    fn test_take_sets_return_data() {
+        use {crate::client, anchor_lang::AnchorDeserialize};
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 100, receive: 50, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        // A partial take and then the closing take
+        for (take_amount, expected) in [(40, (40, 20)), (60, (60, 30))] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), take_amount, u64::MAX)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            let result = svm.send_transaction(tx).expect("Take failed");
+
+            assert_eq!(result.return_data.program_id, PROGRAM_ID);
+            let (amount_a_out, amount_b_in) = <(u64, u64)>::try_from_slice(&result.return_data.data).unwrap();
+            assert_eq!((amount_a_out, amount_b_in), expected);
+        }
+    }
+    #[test]
+    fn test_take_rejects_price_above_max_receive() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 100, receive: 50, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        // The taker quotes 50 and caps the take there
+        let tx = Transaction::new_signed_with_payer(
+            &[client::quote_ix(&maker.pubkey(), &mint_a, 1, 100)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx).expect("Quote failed");
+        let quote: crate::events::EscrowQuote = find_event(&result.logs).expect("EscrowQuote not emitted");
+        assert_eq!(quote.receive, 50);
+
+        // The maker reprices before the take lands
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        let update_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::UpdateOffer {
+                maker: maker.pubkey(),
+                escrow,
+            }.to_account_metas(None),
+            data: crate::instruction::UpdateOffer { new_receive: 80 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[update_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Update offer failed");
+
+        let take = |svm: &mut LiteSVM, max_receive: u64| {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), 100, max_receive)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        assert_error(take(&mut svm, quote.receive), crate::error::EscrowError::SlippageExceeded);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000);
+
+        take(&mut svm, 80).expect("Take at the new price failed");
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000 - 80);
+
+        // TakeMany caps each escrow in the batch the same way
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 2, deposit: 100, receive: 80, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let take_many = |svm: &mut LiteSVM, max_receive: u64| {
+            let mut accounts = crate::accounts::TakeMany {
+                taker: taker.pubkey(),
+                config: client::config_pda().0,
+                fee_authority: admin.pubkey(),
+                associated_token_program: spl_associated_token_account::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None);
+            accounts.extend(take_many_group(&maker.pubkey(), &taker.pubkey(), &admin.pubkey(), &mint_a, &mint_b, &client::escrow_pda(&maker.pubkey(), 2).0));
+            let tx = Transaction::new_signed_with_payer(
+                &[Instruction { program_id: PROGRAM_ID, accounts, data: crate::instruction::TakeMany { max_receives: vec![max_receive] }.data() }],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+        assert_error(take_many(&mut svm, 50), crate::error::EscrowError::SlippageExceeded);
+        take_many(&mut svm, 80).expect("TakeMany at the escrow's price failed");
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000 - 160);
+
+        // And so does TakeWithSol, in lamports
+        ensure_native_mint(&mut svm);
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &native_mint::ID, MakeArgs { seed: 3, deposit: 100, receive: LAMPORTS_PER_SOL, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 3).0;
+        let take_with_sol = |svm: &mut LiteSVM, max_receive: u64| {
+            let ix = Instruction {
+                program_id: PROGRAM_ID,
+                accounts: crate::accounts::TakeWithSol {
+                    taker: taker.pubkey(),
+                    maker: maker.pubkey(),
+                    maker_index: client::maker_index_pda(&maker.pubkey()).0,
+                    mint_a,
+                    mint_b: native_mint::ID,
+                    taker_ata_a: associated_token::get_associated_token_address(&taker.pubkey(), &mint_a),
+                    escrow,
+                    vault: client::vault_address(&escrow, &mint_a),
+                    config: client::config_pda().0,
+                    paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                    paused_mint_b: client::paused_mint_pda(&native_mint::ID).0,
+                    fee_authority: admin.pubkey(),
+                    fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                    receipt: client::receipt_pda(&escrow, &taker.pubkey()).0,
+                    associated_token_program: spl_associated_token_account::ID,
+                    token_program: TOKEN_PROGRAM_ID,
+                    system_program: SYSTEM_PROGRAM_ID,
+                }.to_account_metas(None),
+                data: crate::instruction::TakeWithSol { take_amount: 100, max_receive }.data(),
+            };
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+        assert_error(take_with_sol(&mut svm, LAMPORTS_PER_SOL - 1), crate::error::EscrowError::SlippageExceeded);
+        take_with_sol(&mut svm, LAMPORTS_PER_SOL).expect("TakeWithSol at the escrow's price failed");
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after take");
+    }
+    #[test]
+    fn test_base_unit_conversions() {
+        use crate::client::{from_base_units, make_ix, make_ix_ui, to_base_units};
+
+        // 6 decimals
+        assert_eq!(to_base_units(1.0, 6), 1_000_000);
+        assert_eq!(to_base_units(1.1, 6), 1_100_000, "1.1 is not exact in binary but rounds back");
+        assert_eq!(to_base_units(0.000_000_4, 6), 0, "Below half a unit rounds down");
+        assert_eq!(to_base_units(0.000_000_5, 6), 1, "Half a unit rounds up");
+        assert_eq!(to_base_units(123.456_789, 6), 123_456_789);
+        assert_eq!(from_base_units(1_100_000, 6), 1.1);
+        assert_eq!(from_base_units(1, 6), 0.000_001);
+
+        // 9 decimals
+        assert_eq!(to_base_units(1.0, 9), 1_000_000_000);
+        assert_eq!(to_base_units(0.3, 9), 300_000_000);
+        assert_eq!(to_base_units(2.000_000_000_4, 9), 2_000_000_000);
+        assert_eq!(to_base_units(2.000_000_000_6, 9), 2_000_000_001);
+        assert_eq!(from_base_units(300_000_000, 9), 0.3);
+        assert_eq!(to_base_units(from_base_units(123_456_789_012, 9), 9), 123_456_789_012);
+
+        assert_eq!(to_base_units(-1.0, 6), 0);
+
+        // The whole-token builder encodes the same amounts as the base unit one
+        let (maker, mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let ui = make_ix_ui(&maker, &mint_a, 9, &mint_b, 6, 1.5, 0.25, MakeArgs { seed: 3, ..Default::default() });
+        let raw = make_ix(&maker, &mint_a, &mint_b, MakeArgs {
+            seed: 3,
+            deposit: 1_500_000_000,
+            receive: 250_000,
+            receive_decimals: Some(6),
+            ..Default::default()
+        });
+        assert_eq!(ui, raw);
+    }
+    #[test]
+    fn test_maker_index_tracks_open_escrows() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        for seed in [1, 2] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 50, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+        }
+
+        let open_escrows = |svm: &LiteSVM| {
+            let account = svm.get_account(&client::maker_index_pda(&maker.pubkey()).0).unwrap();
+            let index = crate::state::MakerIndex::try_deserialize(&mut account.data.as_slice()).unwrap();
+            assert_eq!(index.maker, maker.pubkey());
+            index.open_escrows
+        };
+
+        let escrow_1 = client::escrow_pda(&maker.pubkey(), 1).0;
+        let escrow_2 = client::escrow_pda(&maker.pubkey(), 2).0;
+        assert_eq!(open_escrows(&svm), vec![escrow_1, escrow_2]);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), 100, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+        assert_eq!(open_escrows(&svm), vec![escrow_2]);
+    }
+    #[test]
+    fn test_take_verifies_received_amounts() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        // Both receiving accounts start non-empty, so the check has to work on deltas
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_a)
+            .owner(&taker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &taker_ata_a, 3).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &maker_ata_b, 7).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 100);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 1_000, receive: 500, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        // Take reloads taker_ata_a and maker_ata_b after each leg and fails with
+        // TransferVerificationFailed if either grew by less than was sent, so a token
+        // program that shorted or skipped a transfer can't settle the escrow
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), 400, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        // 200 mint_b owed, 1% protocol fee
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 3 + 400);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 7 + 198);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000 - 200);
+    }
+    #[test]
+    fn test_make_builder_picks_free_seeds() {
+        use crate::client::{self, MakeBuilder};
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        // Both builders start at the same seed, the second has to step past the first escrow
+        let mut made = vec![];
+        for _ in 0..2 {
+            let (seed, make_ix) = MakeBuilder::new(&maker.pubkey(), &mint_a, &mint_b)
+                .seed(123)
+                .deposit(100)
+                .receive(50)
+                .build(|escrow| svm.get_account(escrow).is_some());
+            let tx = Transaction::new_signed_with_payer(
+                &[make_ix],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+            made.push(seed);
+        }
+        assert_eq!(made, vec![123, 124]);
+
+        let (escrow_1, vault_1) = client::derive_for_seed(&maker.pubkey(), &mint_a, made[0]);
+        let (escrow_2, vault_2) = client::derive_for_seed(&maker.pubkey(), &mint_a, made[1]);
+        assert_ne!(escrow_1, escrow_2);
+        assert_eq!(get_token_balance(&svm, &vault_1), 100);
+        assert_eq!(get_token_balance(&svm, &vault_2), 100);
+
+        assert_ne!(client::random_seed(), client::random_seed());
+    }
+    #[test]
+    fn test_refund_waits_for_cooldown() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 100, receive: 50, refund_cooldown: 600, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 900);
+
+        let refund = |svm: &mut LiteSVM| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[client::refund_ix(&maker.pubkey(), &mint_a, 1)],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        assert_error(refund(&mut svm), crate::error::EscrowError::CooldownActive);
+
+        warp_to_timestamp(&mut svm, now + 599);
+        assert_error(refund(&mut svm), crate::error::EscrowError::CooldownActive);
+
+        warp_to_timestamp(&mut svm, now + 600);
+        refund(&mut svm).expect("Refund after the cooldown failed");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000);
+    }
+    #[test]
+    fn test_nonce_separates_escrows_with_the_same_seed() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        // Same maker and seed, only the nonce differs
+        for nonce in [1, 2] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 7, nonce, deposit: 100, receive: 50, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+        }
+
+        let escrow_1 = client::escrow_pda_with_nonce(&maker.pubkey(), 7, 1).0;
+        let escrow_2 = client::escrow_pda_with_nonce(&maker.pubkey(), 7, 2).0;
+        assert_ne!(escrow_1, escrow_2);
+        assert_ne!(escrow_1, client::escrow_pda(&maker.pubkey(), 7).0);
+        assert_eq!(read_escrow(&svm.get_account(&escrow_1).unwrap()).nonce, 1);
+        assert_eq!(read_escrow(&svm.get_account(&escrow_2).unwrap()).nonce, 2);
+        let vault_1 = client::vault_address(&escrow_1, &mint_a);
+        let vault_2 = client::vault_address(&escrow_2, &mint_a);
+
+        // Take the first
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a: associated_token::get_associated_token_address(&taker.pubkey(), &mint_a),
+                taker_ata_b,
+                maker_ata_b: associated_token::get_associated_token_address(&maker.pubkey(), &mint_b),
+                escrow: escrow_1, vault: vault_1,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault: associated_token::get_associated_token_address(&admin.pubkey(), &mint_b),
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow_1, &taker.pubkey()).0,
+                associated_token_program: associated_token::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 100, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+        assert!(svm.get_account(&escrow_1).is_none_or(|account| account.lamports == 0));
+        assert_eq!(get_token_balance(&svm, &vault_2), 100, "The other nonce is untouched");
+
+        // Refund the second
+        let refund_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: refund_metas(crate::accounts::Refund {
+                maker: maker.pubkey(),
+                refund_authority: None,
+                mint_a,
+                maker_ata_a,
+                refund_destination: None,
+                escrow: escrow_2, vault: vault_2,
+                mint_a2: None, maker_ata_a2: None, vault_a2: None,
+                staged_take: crate::client::staged_take_pda(&escrow_2).0,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                associated_token_program: associated_token::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }),
+            data: crate::instruction::Refund { close_maker_ata: false }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[refund_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund failed");
+        assert!(svm.get_account(&escrow_2).is_none_or(|account| account.lamports == 0));
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 900);
+    }
+    #[test]
+    fn test_royalty_paid_to_original_maker_after_transfer() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let new_maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&new_maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let royalty_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 100);
+
+        // 5% of the maker's proceeds stay with the creator after a resale
+        let seed = 31;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs {
+                seed,
+                deposit: 100,
+                receive: 400,
+                royalty_recipient: maker.pubkey(),
+                royalty_bps: 500,
+                ..Default::default()
+            })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = client::vault_address(&escrow, &mint_a);
+
+        let transfer_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::TransferMaker {
+                maker: maker.pubkey(),
+                escrow,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                new_maker_index: crate::client::maker_index_pda(&new_maker.pubkey()).0,
+                config: crate::client::config_pda().0,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::TransferMaker { new_maker: new_maker.pubkey() }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[transfer_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Transfer maker failed");
+
+        let take_ix = |royalty_ata_b: Option<Pubkey>| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: new_maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a: associated_token::get_associated_token_address(&taker.pubkey(), &mint_a),
+                taker_ata_b,
+                maker_ata_b: associated_token::get_associated_token_address(&new_maker.pubkey(), &mint_b),
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault: associated_token::get_associated_token_address(&admin.pubkey(), &mint_b),
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program: associated_token::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 100, max_receive: u64::MAX }.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix(None)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::RoyaltyAccountMissing);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix(Some(royalty_ata_b))],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        // 400 owed, 4 protocol fee, 5% of the remaining 396 rounded down
+        let royalty = 19;
+        let new_maker_ata_b = associated_token::get_associated_token_address(&new_maker.pubkey(), &mint_b);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000 - 400);
+        assert_eq!(get_token_balance(&svm, &royalty_ata_b), royalty);
+        assert_eq!(get_token_balance(&svm, &new_maker_ata_b), 396 - royalty);
+    }
+    #[test]
+    fn test_take_deadline_blocks_settle_and_opens_cancel() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        let cranker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&cranker.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+
+        // The escrow itself never expires, only the stage does
+        let seed = 15;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 500, receive: 300, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = client::vault_address(&escrow, &mint_a);
+        let staged_take = Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &PROGRAM_ID).0;
+        let take_vault = associated_token::get_associated_token_address(&staged_take, &mint_b);
+
+        let stage_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::StageTake {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_b,
+                taker_ata_b,
+                escrow,
+                staged_take,
+                take_vault,
+                config,
+                associated_token_program: associated_token::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::StageTake { take_window: 60 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[stage_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Stage take failed");
+
+        let staged_data = crate::state::StagedTake::try_deserialize(
+            &mut svm.get_account(&staged_take).unwrap().data.as_slice()
+        ).unwrap();
+        assert_eq!(staged_data.take_deadline, now + 60);
+
+        let cancel_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::CancelStage {
+                caller: cranker.pubkey(),
+                taker: taker.pubkey(),
+                mint_b,
+                taker_ata_b,
+                staged_take,
+                escrow,
+                take_vault,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::CancelStage.data(),
+        };
+        let cancel = |svm: &mut LiteSVM| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                std::slice::from_ref(&cancel_ix),
+                Some(&cranker.pubkey()),
+                &[&cranker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        // Within the window only the taker may cancel
+        assert_error(cancel(&mut svm), crate::error::EscrowError::TakeDeadlineActive);
+
+        warp_to_timestamp(&mut svm, now + 61);
+
+        let settle_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Settle {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                mint_a, mint_b,
+                taker_ata_a: associated_token::get_associated_token_address(&taker.pubkey(), &mint_a),
+                maker_ata_b: associated_token::get_associated_token_address(&maker.pubkey(), &mint_b),
+                escrow, vault,
+                staged_take,
+                take_vault,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault: associated_token::get_associated_token_address(&admin.pubkey(), &mint_b),
+                associated_token_program: associated_token::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Settle.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[settle_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::TakeDeadlinePassed);
+
+        cancel(&mut svm).expect("Cancel after the deadline failed");
+        assert!(svm.get_account(&staged_take).is_none_or(|account| account.lamports == 0));
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 1_000);
+        assert_eq!(get_token_balance(&svm, &vault), 500, "The escrow stays open");
+    }
+    #[test]
+    fn test_closed_escrow_is_gone_and_rent_goes_to_receiver() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let payer = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&payer.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let make = |svm: &mut LiteSVM| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 100, receive: 50, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+        };
+        make(&mut svm);
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        let escrow_rent = svm.get_balance(&escrow).unwrap();
+        let vault_rent = svm.get_balance(&client::vault_address(&escrow, &mint_a)).unwrap();
+        let maker_lamports = svm.get_balance(&maker.pubkey()).unwrap();
+
+        // Someone else pays for the transaction, the maker is still the rent receiver
+        let tx = Transaction::new_signed_with_payer(
+            &[client::refund_ix(&maker.pubkey(), &mint_a, 1)],
+            Some(&payer.pubkey()),
+            &[&payer, &maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund failed");
+
+        assert!(svm.get_account(&escrow).is_none_or(|account| account.lamports == 0 && account.data.is_empty() && account.owner == SYSTEM_PROGRAM_ID));
+        assert_eq!(svm.get_balance(&maker.pubkey()).unwrap(), maker_lamports + escrow_rent + vault_rent);
+
+        // Nothing is left behind, so the same address can be made again
+        make(&mut svm);
+        assert_eq!(read_escrow(&svm.get_account(&escrow).unwrap()).deposit, 100);
+    }
+    #[test]
+    fn test_strict_mode_rejects_freezable_deposit_mint() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .freeze_authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let set_strict_mints = |svm: &mut LiteSVM, reject_freezable_mints: bool| {
+            svm.expire_blockhash();
+            let set_strict_mints_ix = Instruction {
+                program_id: PROGRAM_ID,
+                accounts: crate::accounts::SetStrictMints {
+                    authority: admin.pubkey(),
+                    config,
+                }.to_account_metas(None),
+                data: crate::instruction::SetStrictMints { reject_freezable_mints }.data(),
+            };
+            let tx = Transaction::new_signed_with_payer(
+                &[set_strict_mints_ix],
+                Some(&admin.pubkey()),
+                &[&admin],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Set strict mints failed");
+        };
+        let make = |svm: &mut LiteSVM| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 100, receive: 50, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        set_strict_mints(&mut svm, true);
+        assert_error(make(&mut svm), crate::error::EscrowError::FreezableMint);
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000);
+
+        // Off by default, and switching it back off lets the same mint through
+        set_strict_mints(&mut svm, false);
+        make(&mut svm).expect("Make with a freezable mint outside strict mode failed");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 900);
+    }
+    #[test]
+    fn test_refund_many_refunds_expired_escrows() {
+        use {crate::client, anchor_lang::AnchorDeserialize};
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+
+        // Escrow 4 outlives the warp below
+        let offers = [(1u64, 100, now + 60), (2, 200, now + 60), (3, 300, now + 60), (4, 400, now + 3_600)];
+        let mut escrows = vec![];
+        for (seed, deposit, expiry) in offers {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit, receive: 10, expiry, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+            let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+            escrows.push((escrow, associated_token::get_associated_token_address(&escrow, &mint_a)));
+        }
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 0);
+
+        warp_to_timestamp(&mut svm, now + 120);
+
+        let refund_many_ix = |fail_fast: bool| {
+            let mut accounts = crate::accounts::RefundMany {
+                maker: maker.pubkey(),
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None);
+            for (escrow, vault) in &escrows {
+                accounts.extend([
+                    AccountMeta::new_readonly(mint_a, false),
+                    AccountMeta::new(*escrow, false),
+                    AccountMeta::new(*vault, false),
+                    AccountMeta::new(maker_ata_a, false),
+                    AccountMeta::new_readonly(client::staged_take_pda(escrow).0, false),
+                ]);
+            }
+            Instruction {
+                program_id: PROGRAM_ID,
+                accounts,
+                data: crate::instruction::RefundMany { fail_fast }.data(),
+            }
+        };
+
+        // Failing fast stops on the live escrow and rolls back the rest
+        let tx = Transaction::new_signed_with_payer(
+            &[refund_many_ix(true)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::EscrowNotExpired);
+        assert!(svm.get_account(&escrows[0].0).is_some(), "Escrow 1 should survive the failed batch");
+
+        let tx = Transaction::new_signed_with_payer(
+            &[refund_many_ix(false)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx).expect("RefundMany failed");
+
+        assert_eq!(result.return_data.program_id, PROGRAM_ID);
+        assert_eq!(u64::try_from_slice(&result.return_data.data).unwrap(), 3);
+        for (escrow, vault) in &escrows[..3] {
+            assert!(svm.get_account(escrow).is_none(), "Escrow should be closed");
+            assert!(svm.get_account(vault).is_none(), "Vault should be closed");
+        }
+        assert!(svm.get_account(&escrows[3].0).is_some(), "Unexpired escrow should be skipped");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 600);
+    }
+    #[test]
+    fn test_partial_take_emits_remaining_amounts() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 100, receive: 50, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), 40, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx).expect("Take failed");
+
+        let partial: crate::events::EscrowPartiallyFilled = find_event(&result.logs).expect("EscrowPartiallyFilled not emitted");
+        assert_eq!(partial.escrow, escrow);
+        assert_eq!(partial.taker, taker.pubkey());
+        assert_eq!(partial.filled, 40);
+        assert_eq!(partial.remaining_deposit, get_token_balance(&svm, &vault));
+        assert_eq!(partial.remaining_deposit, 60);
+        assert_eq!(partial.remaining_receive, 30);
+        assert!(find_event::<crate::events::EscrowTaken>(&result.logs).is_some(), "EscrowTaken not emitted");
+
+        // The closing take only reports EscrowTaken
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, 1, &admin.pubkey(), 60, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx).expect("Take failed");
+
+        assert!(find_event::<crate::events::EscrowPartiallyFilled>(&result.logs).is_none(), "Full fill should not report a partial fill");
+        assert!(find_event::<crate::events::EscrowTaken>(&result.logs).is_some(), "EscrowTaken not emitted");
+    }
+    #[test]
+    fn test_admin_force_refund() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        // Not expired and still inside its refund cooldown
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 400, receive: 10, expiry: now + 3_600, refund_cooldown: 3_600, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        let vault = associated_token::get_associated_token_address(&escrow, &mint_a);
+        let force_refund_ix = |authority: &Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::AdminForceRefund {
+                authority: *authority,
+                maker: maker.pubkey(),
+                mint_a,
+                maker_ata_a,
+                escrow, vault,
+                staged_take: Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &PROGRAM_ID).0,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::AdminForceRefund.data(),
+        };
+
+        // Only the config authority can force a refund
+        let intruder = Keypair::new();
+        svm.airdrop(&intruder.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[force_refund_ix(&intruder.pubkey())],
+            Some(&intruder.pubkey()),
+            &[&intruder],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), anchor_lang::error::ErrorCode::ConstraintHasOne);
+
+        let maker_lamports = svm.get_account(&maker.pubkey()).unwrap().lamports;
+        let tx = Transaction::new_signed_with_payer(
+            &[force_refund_ix(&admin.pubkey())],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("AdminForceRefund failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed");
+        assert!(svm.get_account(&vault).is_none(), "Vault should be closed");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000);
+        assert!(svm.get_account(&maker.pubkey()).unwrap().lamports > maker_lamports, "Rent should go back to the maker");
+    }
+    #[test]
+    fn test_paused_mint_blocks_make_and_take() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_c = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        for mint in [mint_b, mint_c] {
+            let taker_ata = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint)
+                .owner(&taker.pubkey()).send().unwrap();
+            MintTo::new(&mut svm, &taker, &mint, &taker_ata, 1_000).send().unwrap();
+        }
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        // Escrow 1 asks for mint_b, escrow 2 for mint_c
+        for (seed, mint) in [(1u64, mint_b), (2, mint_c)] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint, MakeArgs { seed, deposit: 100, receive: 50, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+        }
+
+        let set_mint_paused = |svm: &mut LiteSVM, mint: Pubkey, paused: bool| {
+            let ix = Instruction {
+                program_id: PROGRAM_ID,
+                accounts: crate::accounts::SetMintPaused {
+                    authority: admin.pubkey(),
+                    config,
+                    mint,
+                    paused_mint: client::paused_mint_pda(&mint).0,
+                    system_program: SYSTEM_PROGRAM_ID,
+                }.to_account_metas(None),
+                data: crate::instruction::SetMintPaused { paused }.data(),
+            };
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&admin.pubkey()),
+                &[&admin],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("SetMintPaused failed");
+        };
+        set_mint_paused(&mut svm, mint_b, true);
+
+        let take = |svm: &mut LiteSVM, seed: u64, mint: Pubkey| {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint, seed, &admin.pubkey(), 100, u64::MAX)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        assert_error(take(&mut svm, 1, mint_b), crate::error::EscrowError::MintPaused);
+
+        let mut accounts = crate::accounts::TakeMany {
+            taker: taker.pubkey(),
+            config,
+            fee_authority: admin.pubkey(),
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: SYSTEM_PROGRAM_ID,
+        }.to_account_metas(None);
+        accounts.extend(take_many_group(&maker.pubkey(), &taker.pubkey(), &admin.pubkey(), &mint_a, &mint_b, &client::escrow_pda(&maker.pubkey(), 1).0));
+        let tx = Transaction::new_signed_with_payer(
+            &[Instruction { program_id: PROGRAM_ID, accounts, data: crate::instruction::TakeMany { max_receives: vec![u64::MAX] }.data() }],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::MintPaused);
+
+        // A new escrow asking for the paused mint is rejected too
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 3, deposit: 100, receive: 50, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::MintPaused);
+
+        // Escrows on other mints keep working
+        take(&mut svm, 2, mint_c).expect("Take on an unpaused mint failed");
+        assert!(svm.get_account(&client::escrow_pda(&maker.pubkey(), 2).0).is_none(), "Escrow 2 should be closed");
+
+        set_mint_paused(&mut svm, mint_b, false);
+        take(&mut svm, 1, mint_b).expect("Take after unpausing failed");
+        assert!(svm.get_account(&client::escrow_pda(&maker.pubkey(), 1).0).is_none(), "Escrow 1 should be closed");
+    }
+    #[test]
+    fn test_fee_tier_overrides_default_fee() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let tiered = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let untiered = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        for mint in [tiered, untiered] {
+            let taker_ata = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint)
+                .owner(&taker.pubkey()).send().unwrap();
+            MintTo::new(&mut svm, &taker, &mint, &taker_ata, 10_000).send().unwrap();
+        }
+
+        // 1% by default, 5% for the tiered mint
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 100);
+
+        let set_fee_tier_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::SetFeeTier {
+                authority: admin.pubkey(),
+                config,
+            }.to_account_metas(None),
+            data: crate::instruction::SetFeeTier { mint: tiered, fee_bps: 500 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[set_fee_tier_ix],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("SetFeeTier failed");
+
+        let read_config = |svm: &LiteSVM| crate::state::Config::try_deserialize(
+            &mut svm.get_account(&config).unwrap().data.as_slice()
+        ).unwrap();
+        assert_eq!(read_config(&svm).fee_bps_for(&tiered), 500);
+        assert_eq!(read_config(&svm).fee_bps_for(&untiered), 100);
+
+        for (seed, mint) in [(1u64, tiered), (2, untiered)] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint, MakeArgs { seed, deposit: 100, receive: 1_000, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint, seed, &admin.pubkey(), 100, u64::MAX)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Take failed");
+        }
+
+        let fee_vault = |mint: &Pubkey| associated_token::get_associated_token_address(&admin.pubkey(), mint);
+        assert_eq!(get_token_balance(&svm, &fee_vault(&tiered)), 50);
+        assert_eq!(get_token_balance(&svm, &fee_vault(&untiered)), 10);
+        assert_eq!(get_token_balance(&svm, &associated_token::get_associated_token_address(&maker.pubkey(), &tiered)), 950);
+        assert_eq!(get_token_balance(&svm, &associated_token::get_associated_token_address(&maker.pubkey(), &untiered)), 990);
+
+        // Clearing the tier falls back to the default fee
+        let clear_fee_tier_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::ClearFeeTier {
+                authority: admin.pubkey(),
+                config,
+            }.to_account_metas(None),
+            data: crate::instruction::ClearFeeTier { mint: tiered }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[clear_fee_tier_ix],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("ClearFeeTier failed");
+        assert_eq!(read_config(&svm).fee_bps_for(&tiered), 100);
+    }
+    #[test]
+    fn test_admin_rescues_stray_tokens() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let stranger = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&stranger.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let stray_mint = CreateMint::new(&mut svm, &stranger)
+            .authority(&stranger.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 400, receive: 100, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        let vault = client::vault_address(&escrow, &mint_a);
+
+        // Someone sends unrelated tokens to the escrow PDA
+        let stray_account = CreateAssociatedTokenAccount::new(&mut svm, &stranger, &stray_mint)
+            .owner(&escrow).send().unwrap();
+        MintTo::new(&mut svm, &stranger, &stray_mint, &stray_account, 250).send().unwrap();
+        let recipient = CreateAssociatedTokenAccount::new(&mut svm, &stranger, &stray_mint)
+            .owner(&stranger.pubkey()).send().unwrap();
+
+        let rescue_ix = |mint: Pubkey, account: Pubkey, recipient: Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::AdminRescue {
+                authority: admin.pubkey(),
+                config,
+                escrow,
+                stray_mint: mint,
+                stray_account: account,
+                recipient,
+                token_program: TOKEN_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::AdminRescue.data(),
+        };
+
+        // The vault itself can't be swept
+        let tx = Transaction::new_signed_with_payer(
+            &[rescue_ix(mint_a, vault, maker_ata_a)],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::RescueDepositMint);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[rescue_ix(stray_mint, stray_account, recipient)],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("AdminRescue failed");
+
+        assert_eq!(get_token_balance(&svm, &stray_account), 0);
+        assert_eq!(get_token_balance(&svm, &recipient), 250);
+        assert_eq!(get_token_balance(&svm, &vault), 400);
+    }
+    #[test]
+    fn test_make_rejects_maker_short_on_rent() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        // The funder pays for the mints and the maker's ATA, so the maker's balance is only rent and fees
+        let funder = Keypair::new();
+        let maker = Keypair::new();
+        svm.airdrop(&funder.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &funder)
+            .authority(&funder.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &funder)
+            .authority(&funder.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &funder, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &funder, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let fee = 5_000;
+        let needed = svm.minimum_balance_for_rent_exemption(8 + crate::state::Escrow::INIT_SPACE)
+            + svm.minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+            + svm.minimum_balance_for_rent_exemption(8 + crate::state::MakerIndex::INIT_SPACE);
+        svm.airdrop(&maker.pubkey(), needed - 1 + fee).unwrap();
+
+        let make = |svm: &mut LiteSVM| {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 100, receive: 50, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        assert_error(make(&mut svm), crate::error::EscrowError::InsufficientRent);
+        assert!(svm.get_account(&client::escrow_pda(&maker.pubkey(), 1).0).is_none(), "No account should be created");
+
+        // Topping up to exactly the rent is enough
+        let balance = svm.get_account(&maker.pubkey()).unwrap().lamports;
+        svm.airdrop(&maker.pubkey(), needed + fee - balance).unwrap();
+        svm.expire_blockhash();
+        make(&mut svm).expect("Make failed");
+    }
+    #[test]
+    fn test_same_seed_escrows_in_separate_namespaces() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        // The default book and two named ones, all with the same maker and seed
+        let namespaces = [[0; 8], *b"book-one", *b"book-two"];
+        let escrows: Vec<Pubkey> = namespaces
+            .iter()
+            .map(|namespace| client::escrow_pda_in_namespace(&maker.pubkey(), 1, 0, namespace).0)
+            .collect();
+        assert_eq!(escrows[0], client::escrow_pda(&maker.pubkey(), 1).0, "The default namespace keeps the old address");
+        assert_ne!(escrows[1], escrows[0]);
+        assert_ne!(escrows[2], escrows[0]);
+        assert_ne!(escrows[2], escrows[1]);
+
+        for (namespace, deposit) in namespaces.into_iter().zip([100, 200, 300]) {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit, receive: 10, namespace, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+        }
+
+        for ((escrow, namespace), deposit) in escrows.iter().zip(namespaces).zip([100, 200, 300]) {
+            let escrow_data = read_escrow(&svm.get_account(escrow).expect("Escrow should exist"));
+            assert_eq!(escrow_data.namespace, namespace);
+            assert_eq!(escrow_data.seed, 1);
+            assert_eq!(get_token_balance(&svm, &client::vault_address(escrow, &mint_a)), deposit);
+        }
+    }
+    #[test]
+    fn test_refund_rejects_look_alike_token_program() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        // A deployed program that would re-enter Refund if the vault transfer were ever sent to it
+        let mock_token_program = Pubkey::new_unique();
+        let so_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../target/deploy/anchor_escrow.so");
+        svm.add_program(mock_token_program, &std::fs::read(so_path).expect("Failed to read program SO file"));
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 1, deposit: 400, receive: 100, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let mut refund_ix = client::refund_ix(&maker.pubkey(), &mint_a, 1);
+        for meta in refund_ix.accounts.iter_mut().filter(|meta| meta.pubkey == TOKEN_PROGRAM_ID) {
+            meta.pubkey = mock_token_program;
+        }
+        let tx = Transaction::new_signed_with_payer(
+            &[refund_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), anchor_lang::error::ErrorCode::InvalidProgramId);
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 1).0;
+        assert!(svm.get_account(&escrow).is_some(), "Escrow should be untouched");
+        assert_eq!(get_token_balance(&svm, &client::vault_address(&escrow, &mint_a)), 400);
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 600);
+    }
+    #[test]
+    fn test_set_config_authority_rotates_admin() {
+        let mut svm = setup();
+
+        let admin = Keypair::new();
+        let new_admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&new_admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let set_config_authority = |svm: &mut LiteSVM, authority: &Keypair, new_authority: Pubkey| {
+            let ix = Instruction {
+                program_id: PROGRAM_ID,
+                accounts: crate::accounts::SetConfigAuthority {
+                    authority: authority.pubkey(),
+                    config,
+                }.to_account_metas(None),
+                data: crate::instruction::SetConfigAuthority { new_authority }.data(),
+            };
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&authority.pubkey()),
+                &[authority],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        assert_error(
+            set_config_authority(&mut svm, &admin, Pubkey::default()),
+            crate::error::EscrowError::InvalidAuthority,
+        );
+        assert_error(
+            set_config_authority(&mut svm, &new_admin, new_admin.pubkey()),
+            anchor_lang::error::ErrorCode::ConstraintHasOne,
+        );
+
+        set_config_authority(&mut svm, &admin, new_admin.pubkey()).expect("SetConfigAuthority failed");
+        let config_data = crate::state::Config::try_deserialize(
+            &mut svm.get_account(&config).unwrap().data.as_slice()
+        ).unwrap();
+        assert_eq!(config_data.authority, new_admin.pubkey());
+
+        // The old key is locked out, the new one can act
+        assert_error(
+            set_paused(&mut svm, &admin, config, true),
+            anchor_lang::error::ErrorCode::ConstraintHasOne,
+        );
+        set_paused(&mut svm, &new_admin, config, true).expect("Pause by the new authority failed");
+    }
+    #[test]
+    fn test_take_accepts_any_listed_receive_mint() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        // usdc is mint_b, usdt is the listed alternative, rogue is never listed
+        let [usdc, usdt, rogue] = [0; 3].map(|_| {
+            CreateMint::new(&mut svm, &taker)
+                .authority(&taker.pubkey())
+                .decimals(6)
+                .send()
+                .unwrap()
+        });
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        let [taker_ata_usdc, taker_ata_usdt, taker_ata_rogue] = [usdc, usdt, rogue].map(|mint| {
+            let ata = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint)
+                .owner(&taker.pubkey()).send().unwrap();
+            MintTo::new(&mut svm, &taker, &mint, &ata, 1_000).send().unwrap();
+            ata
+        });
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let accepted_receive_mints = [usdt, Pubkey::default(), Pubkey::default(), Pubkey::default()];
+        for seed in [1, 2] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &usdc, MakeArgs { seed, deposit: 100, receive: 100, accepted_receive_mints, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+        }
+        let escrow_1 = client::escrow_pda(&maker.pubkey(), 1).0;
+        let escrow_2 = client::escrow_pda(&maker.pubkey(), 2).0;
+        assert_eq!(read_escrow(&svm.get_account(&escrow_1).unwrap()).accepted_receive_mints, accepted_receive_mints);
+
+        // Escrow 1 is filled half in mint_b and half in the alternative
+        for (mint, amount) in [(usdc, 40), (usdt, 60)] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint, 1, &admin.pubkey(), amount, u64::MAX)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Take failed");
+        }
+        assert!(svm.get_account(&escrow_1).is_none(), "Escrow should be closed after the last take");
+        assert_eq!(get_token_balance(&svm, &taker_ata_usdc), 960);
+        assert_eq!(get_token_balance(&svm, &taker_ata_usdt), 940);
+
+        // An unlisted mint is rejected the same way a wrong mint_b always was
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &rogue, 2, &admin.pubkey(), 100, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::InvalidMint);
+        assert_eq!(get_token_balance(&svm, &taker_ata_rogue), 1_000);
+
+        // Escrow 2 is filled entirely in the alternative
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &usdt, 2, &admin.pubkey(), 100, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+        assert!(svm.get_account(&escrow_2).is_none(), "Escrow should be closed after the last take");
+        assert_eq!(get_token_balance(&svm, &taker_ata_usdt), 840);
+        let maker_ata_usdt = associated_token::get_associated_token_address(&maker.pubkey(), &usdt);
+        assert_eq!(get_token_balance(&svm, &maker_ata_usdt), 160);
+    }
+    #[test]
+    fn test_take_signs_with_stored_canonical_bump() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let seed = 74;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 50, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        // The bump Take and Refund sign with is the canonical one, not a re-derived one
+        let (escrow, bump) = client::escrow_pda(&maker.pubkey(), seed);
+        assert_eq!(read_escrow(&svm.get_account(&escrow).unwrap()).bump, bump);
+
+        // A partial take and then the rest both sign the vault transfer with it
+        for amount in [40, 60] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), amount, u64::MAX)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            let result = svm.send_transaction(tx).expect("Take failed");
+            // Same ceiling as the lifecycle test, a re-derived bump would eat into it
+            assert_max_cu(&result, 120_000);
+        }
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after the last take");
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 100);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 950);
+    }
+    #[test]
+    fn test_random_partial_fills_keep_accounting_invariants() {
+        use crate::client;
+
+        // Seeded xorshift so a failing case replays exactly
+        let mut state: u64 = 0x5eed_f111;
+        let mut next = |low: u64, high: u64| -> u64 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            low + state % (high - low + 1)
+        };
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 100 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 100 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 10_000_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+        let maker_ata_b = associated_token::get_associated_token_address(&maker.pubkey(), &mint_b);
+        let balance = |svm: &LiteSVM, ata: &Pubkey| svm.get_account(ata).map_or(0, |_| get_token_balance(svm, ata));
+
+        for seed in 0..8 {
+            // receive >= deposit keeps every rounded-up fill at least 1 base unit
+            let deposit = next(1, 10_000);
+            let receive = next(deposit, 10 * deposit);
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit, receive, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+
+            let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+            let vault = client::vault_address(&escrow, &mint_a);
+            let taker_a_before = balance(&svm, &taker_ata_a);
+            let maker_b_before = balance(&svm, &maker_ata_b);
+
+            let mut steps = 0;
+            while svm.get_account(&escrow).is_some() {
+                let remaining = get_token_balance(&svm, &vault);
+                // The sixth step takes the rest so each escrow closes
+                steps += 1;
+                let amount = if steps == 6 { remaining } else { next(1, remaining) };
+
+                let tx = Transaction::new_signed_with_payer(
+                    &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), amount, u64::MAX)],
+                    Some(&taker.pubkey()),
+                    &[&taker],
+                    svm.latest_blockhash(),
+                );
+                svm.send_transaction(tx).unwrap_or_else(|err| {
+                    panic!("Take of {amount} failed for deposit {deposit} receive {receive}: {:#?}", err.meta.logs)
+                });
+                svm.expire_blockhash();
+
+                let mint_a_out = balance(&svm, &taker_ata_a) - taker_a_before;
+                let mint_b_in = balance(&svm, &maker_ata_b) - maker_b_before;
+                let vault_left = svm.get_account(&escrow).map_or(0, |_| get_token_balance(&svm, &vault));
+
+                assert!(mint_a_out <= deposit, "Vault went negative: {mint_a_out} out of {deposit}");
+                assert_eq!(mint_a_out + vault_left, deposit, "mint_a drifted for deposit {deposit} receive {receive}");
+                assert!(mint_b_in <= receive, "Maker got {mint_b_in} for receive {receive}");
+            }
+
+            // Once the escrow closes the maker is paid exactly what was asked
+            assert_eq!(balance(&svm, &maker_ata_b) - maker_b_before, receive);
+        }
+    }
+    #[test]
+    fn test_refund_closes_empty_maker_ata_when_asked() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        let destination = CreateAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let refund_ix = |seed: u64, refund_destination: Option<Pubkey>| {
+            let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+            Instruction {
+                program_id: PROGRAM_ID,
+                accounts: refund_metas(crate::accounts::Refund {
+                    maker: maker.pubkey(),
+                    refund_authority: None,
+                    mint_a,
+                    maker_ata_a,
+                    refund_destination,
+                    escrow,
+                    vault: client::vault_address(&escrow, &mint_a),
+                    mint_a2: None, maker_ata_a2: None, vault_a2: None,
+                    staged_take: crate::client::staged_take_pda(&escrow).0,
+                    maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                    config,
+                    associated_token_program: associated_token::ID,
+                    token_program: TOKEN_PROGRAM_ID,
+                    system_program: SYSTEM_PROGRAM_ID,
+                }),
+                data: crate::instruction::Refund { close_maker_ata: true }.data(),
+            }
+        };
+
+        for seed in [1, 2] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 1_000, receive: 100, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+
+            // Refunding into the ATA refills it, so the flag leaves it open
+            if seed == 1 {
+                let tx = Transaction::new_signed_with_payer(
+                    &[refund_ix(seed, None)],
+                    Some(&maker.pubkey()),
+                    &[&maker],
+                    svm.latest_blockhash(),
+                );
+                svm.send_transaction(tx).expect("Refund failed");
+                assert_eq!(get_token_balance(&svm, &maker_ata_a), 1_000);
+            }
+        }
+
+        // Refunding elsewhere leaves the ATA empty, so it is closed into the maker
+        let ata_rent = svm.get_account(&maker_ata_a).unwrap().lamports;
+        let escrow = client::escrow_pda(&maker.pubkey(), 2).0;
+        let escrow_rent = svm.get_account(&escrow).unwrap().lamports;
+        let vault_rent = svm.get_account(&client::vault_address(&escrow, &mint_a)).unwrap().lamports;
+        let maker_before = svm.get_balance(&maker.pubkey()).unwrap();
+
+        let tx = Transaction::new_signed_with_payer(
+            &[refund_ix(2, Some(destination))],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund failed");
+
+        assert!(svm.get_account(&maker_ata_a).is_none(), "maker_ata_a should be closed");
+        assert_eq!(get_token_balance(&svm, &destination), 1_000);
+        assert_eq!(
+            svm.get_balance(&maker.pubkey()).unwrap(),
+            maker_before + ata_rent + escrow_rent + vault_rent - 5_000 // signature fee
+        );
+    }
+    #[test]
+    fn test_token_2022_take_with_client_compute_budget() {
+        use crate::client;
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let token_program = spl_token_2022::ID;
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .token_program_id(&token_program)
+            .send()
+            .unwrap();
+
+        // mint_b charges a 1% transfer fee
+        let mint_b = create_transfer_fee_mint(&mut svm, &taker, 100);
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).token_program_id(&token_program).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).token_program_id(&token_program).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).token_program_id(&token_program).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 10_000).token_program_id(&token_program).send().unwrap();
+
+        let ata = |owner: &Pubkey, mint: &Pubkey| {
+            associated_token::get_associated_token_address_with_program_id(owner, mint, &token_program)
+        };
+        let seed: u64 = 77;
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = ata(&escrow, &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        // A protocol fee adds the fee vault transfer on top of the transfer fee gross up
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 100);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 100, receive: 1_000, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &client::with_compute_budget(&[make_ix], client::DEFAULT_COMPUTE_UNITS_PER_IX),
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a: ata(&taker.pubkey(), &mint_a),
+                taker_ata_b,
+                maker_ata_b: ata(&maker.pubkey(), &mint_b),
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault: ata(&admin.pubkey(), &mint_b),
+                fee_vault_a: ata(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 40, max_receive: u64::MAX }.data(),
+        };
+        let instructions = client::with_compute_budget(&[take_ix], client::DEFAULT_COMPUTE_UNITS_PER_IX);
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(instructions[0].program_id, solana_sdk_ids::compute_budget::ID);
+
+        let tx = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        let result = svm.send_transaction(tx).expect("Take failed");
+        assert_max_cu(&result, client::DEFAULT_COMPUTE_UNITS_PER_IX as u64);
+
+        assert_eq!(get_token_balance(&svm, &ata(&taker.pubkey(), &mint_a)), 40);
+        assert_eq!(get_token_balance(&svm, &vault), 60);
+
+        // The limit scales with the instruction count but never passes the transaction maximum
+        let many = client::with_compute_budget(&vec![instructions[1].clone(); 10], client::DEFAULT_COMPUTE_UNITS_PER_IX);
+        assert_eq!(many[0], solana_compute_budget_interface::ComputeBudgetInstruction::set_compute_unit_limit(client::MAX_COMPUTE_UNIT_LIMIT));
+    }
+    #[test]
+    fn test_take_pays_taker_tip_on_top_of_deposit() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let seed = 78;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 50, taker_tip: 10, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        // The maker funds the tip along with the deposit
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = client::vault_address(&escrow, &mint_a);
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 890);
+        assert_eq!(get_token_balance(&svm, &vault), 110);
+        assert_eq!(read_escrow(&svm.get_account(&escrow).unwrap()).taker_tip, 10);
+
+        // A partial take earns its share of the tip, the take that empties the escrow gets the rest
+        let taker_ata_a = associated_token::get_associated_token_address(&taker.pubkey(), &mint_a);
+        for (amount, received, tip_left) in [(40, 44, 6), (60, 110, 0)] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), amount, u64::MAX)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Take failed");
+
+            assert_eq!(get_token_balance(&svm, &taker_ata_a), received);
+            if tip_left > 0 {
+                assert_eq!(read_escrow(&svm.get_account(&escrow).unwrap()).taker_tip, tip_left);
+                assert_eq!(get_token_balance(&svm, &vault), 60 + tip_left);
+            }
+        }
+
+        // The tip costs the taker nothing, they pay receive for the deposit alone
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 950);
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after the last take");
+    }
+    #[test]
+    fn test_escrow_status_transitions() {
+        use crate::{client, state::EscrowStatus};
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let seed = 79;
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let status = |svm: &LiteSVM| client::escrow_status(&svm.get_account(&escrow).map(|account| account.data).unwrap_or_default());
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 50, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+        assert_eq!(status(&svm), EscrowStatus::Open);
+
+        let take = |svm: &mut LiteSVM, amount: u64| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), amount, u64::MAX)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Take failed");
+        };
+
+        take(&mut svm, 40);
+        assert_eq!(status(&svm), EscrowStatus::PartiallyFilled);
+
+        // A stage takes over the status until it is cancelled, then the partial fill shows again
+        let staged_take = Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &PROGRAM_ID).0;
+        let take_vault = associated_token::get_associated_token_address(&staged_take, &mint_b);
+        let stage_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::StageTake {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_b,
+                taker_ata_b,
+                escrow,
+                staged_take,
+                take_vault,
+                config,
+                associated_token_program: associated_token::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::StageTake { take_window: 0 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[stage_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Stage take failed");
+        assert_eq!(status(&svm), EscrowStatus::Staged);
+
+        // The staged taker paid the price at stage time, so neither a take nor a reprice may land under them
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 10, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::EscrowStaged);
+
+        let update_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::UpdateOffer { maker: maker.pubkey(), escrow }.to_account_metas(None),
+            data: crate::instruction::UpdateOffer { new_receive: 1 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[update_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::EscrowStaged);
+
+        let cancel_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::CancelStage {
+                caller: taker.pubkey(),
+                taker: taker.pubkey(),
+                mint_b,
+                taker_ata_b,
+                staged_take,
+                escrow,
+                take_vault,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::CancelStage.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[cancel_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Cancel stage failed");
+        assert_eq!(status(&svm), EscrowStatus::PartiallyFilled);
+
+        take(&mut svm, 60);
+        assert_eq!(status(&svm), EscrowStatus::Closed);
+    }
+    #[test]
+    fn test_refund_recreates_closed_maker_ata() {
+        use crate::client;
+
+        let mut svm = setup();
+        let Parties { maker, mint_a, mint_b, maker_ata_a, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        // Deposit everything so the ATA is empty and can be closed
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed: 81, deposit: PARTY_BALANCE, receive: 10, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let close_ix = spl_token::instruction::close_account(&TOKEN_PROGRAM_ID, &maker_ata_a, &maker.pubkey(), &maker.pubkey(), &[]).unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[close_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Closing maker_ata_a failed");
+        assert!(svm.get_account(&maker_ata_a).is_none());
+
+        let escrow = client::escrow_pda(&maker.pubkey(), 81).0;
+        let escrow_rent = svm.get_account(&escrow).unwrap().lamports;
+        let vault_rent = svm.get_account(&client::vault_address(&escrow, &mint_a)).unwrap().lamports;
+        let maker_before = svm.get_balance(&maker.pubkey()).unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[client::refund_ix(&maker.pubkey(), &mint_a, 81)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund failed");
+
+        // The maker pays the new ATA's rent
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), PARTY_BALANCE);
+        let ata_rent = svm.get_account(&maker_ata_a).unwrap().lamports;
+        assert_eq!(
+            svm.get_balance(&maker.pubkey()).unwrap(),
+            maker_before + escrow_rent + vault_rent - ata_rent - 5_000 // signature fee
+        );
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after refund");
+    }
+    #[test]
+    fn test_make_enforces_max_lifetime() {
+        use crate::client;
+
+        let mut svm = setup();
+        let Parties { maker, mint_a, mint_b, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+
+        let set_max_lifetime_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::SetMaxLifetime {
+                authority: admin.pubkey(),
+                config,
+            }.to_account_metas(None),
+            data: crate::instruction::SetMaxLifetime { max_lifetime: 3_600 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[set_max_lifetime_ix],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Set max lifetime failed");
+
+        let make = |svm: &mut LiteSVM, seed: u64, expiry: i64| {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 10, expiry, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        // One second past the cap, and never expiring, are both too long
+        assert_error(make(&mut svm, 1, now + 3_601), crate::error::EscrowError::LifetimeTooLong);
+        assert_error(make(&mut svm, 2, 0), crate::error::EscrowError::LifetimeTooLong);
+
+        make(&mut svm, 3, now + 3_600).expect("Make at the cap failed");
+        let escrow = read_escrow(&svm.get_account(&client::escrow_pda(&maker.pubkey(), 3).0).unwrap());
+        assert_eq!(escrow.expiry, now + 3_600);
+    }
+
+    #[test]
+    fn test_make_many_posts_three_escrows() {
+        let mut svm = setup();
+        let parties = setup_parties(&mut svm);
+        let maker = &parties.maker;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let orders = vec![
+            crate::MakeOrder { seed: 1, deposit: 100, receive: 40 },
+            crate::MakeOrder { seed: 2, deposit: 200, receive: 70 },
+            crate::MakeOrder { seed: 3, deposit: 300, receive: 90 },
+        ];
+
+        let mut accounts = crate::accounts::MakeMany {
+            maker: maker.pubkey(),
+            maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+            config,
+            associated_token_program: spl_associated_token_account::ID,
+            token_program: TOKEN_PROGRAM_ID,
+            system_program: SYSTEM_PROGRAM_ID,
+        }.to_account_metas(None);
+        let mut escrows = vec![];
+        for order in &orders {
+            let escrow = Pubkey::find_program_address(
+                &[b"escrow", maker.pubkey().as_ref(), &order.seed.to_le_bytes()],
+                &PROGRAM_ID,
+            ).0;
+            let vault = associated_token::get_associated_token_address(&escrow, &parties.mint_a);
+            accounts.extend([
+                AccountMeta::new_readonly(parties.mint_a, false),
+                AccountMeta::new_readonly(parties.mint_b, false),
+                AccountMeta::new(parties.maker_ata_a, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(crate::client::paused_mint_pda(&parties.mint_a).0, false),
+                AccountMeta::new_readonly(crate::client::paused_mint_pda(&parties.mint_b).0, false),
+            ]);
+            escrows.push((escrow, vault));
+        }
+
+        let make_many_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts,
+            data: crate::instruction::MakeMany { orders: orders.clone() }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_many_ix],
+            Some(&maker.pubkey()),
+            &[maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("MakeMany failed");
+
+        for (order, (escrow, vault)) in orders.iter().zip(&escrows) {
+            let state = read_escrow(&svm.get_account(escrow).unwrap());
+            assert_eq!(state.seed, order.seed);
+            assert_eq!(state.maker, maker.pubkey());
+            assert_eq!(state.mint_a, parties.mint_a);
+            assert_eq!(state.mint_b, parties.mint_b);
+            assert_eq!(state.deposit, order.deposit);
+            assert_eq!(state.receive, order.receive);
+            assert_eq!(state.status(), crate::state::EscrowStatus::Open);
+            assert_eq!(get_token_balance(&svm, vault), order.deposit);
+        }
+        assert_eq!(get_token_balance(&svm, &parties.maker_ata_a), PARTY_BALANCE - 600);
+
+        let index_account = svm.get_account(&crate::client::maker_index_pda(&maker.pubkey()).0).unwrap();
+        let index = crate::state::MakerIndex::try_deserialize(&mut index_account.data.as_slice()).unwrap();
+        assert_eq!(index.open_escrows, escrows.iter().map(|(escrow, _)| *escrow).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_take_unwraps_sol_for_maker() {
+        let mut svm = setup();
+        ensure_native_mint(&mut svm);
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        let admin = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = native_mint::ID;
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000_000_000).send().unwrap();
+
+        // Wrap SOL for the taker
+        let receive = 2 * LAMPORTS_PER_SOL;
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        let tx = Transaction::new_signed_with_payer(
+            &[
+                solana_system_interface::instruction::transfer(&taker.pubkey(), &taker_ata_b, receive),
+                spl_token::instruction::sync_native(&TOKEN_PROGRAM_ID, &taker_ata_b).unwrap(),
+            ],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Wrapping SOL failed");
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), receive);
+
+        let seed: u64 = 1;
+        let args = MakeArgs { seed, deposit: 50, receive, unwrap_sol: true, ..Default::default() };
+        let tx = Transaction::new_signed_with_payer(
+            &[crate::client::make_ix(&maker.pubkey(), &mint_a, &mint_b, args)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = crate::client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = crate::client::vault_address(&escrow, &mint_a);
+        let maker_ata_b = associated_token::get_associated_token_address(&maker.pubkey(), &mint_b);
+        let sol_unwrap = associated_token::get_associated_token_address(&escrow, &mint_b);
+        assert_eq!(read_escrow(&svm.get_account(&escrow).unwrap()).unwrap_sol, 1);
+
+        let maker_lamports = svm.get_balance(&maker.pubkey()).unwrap();
+        let escrow_rent = svm.get_balance(&escrow).unwrap();
+        let vault_rent = svm.get_balance(&vault).unwrap();
+
+        // take_ix passes the escrow's wSOL ATA as sol_unwrap whenever mint_b is wSOL
+        let take_ix = crate::client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 50, u64::MAX);
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take failed");
+
+        // The maker is paid in lamports and keeps an empty wSOL ATA
+        assert_eq!(
+            svm.get_balance(&maker.pubkey()).unwrap(),
+            maker_lamports + receive + escrow_rent + vault_rent,
+        );
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 0);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), 0);
+        assert!(svm.get_account(&sol_unwrap).is_none(), "Unwrap account should be closed");
+        assert_eq!(get_token_balance(&svm, &associated_token::get_associated_token_address(&taker.pubkey(), &mint_a)), 50);
+    }
+
+    #[test]
+    fn test_price_ratio_across_decimals() {
+        use crate::client::price_ratio;
+
+        // 1 mint_a for 2 mint_b, even though the raw amounts differ by the decimal gap
+        assert_eq!(price_ratio(1_000_000, 6, 2_000_000, 6), 2.0);
+        assert_eq!(price_ratio(1_000_000, 6, 2_000_000_000, 9), 2.0);
+        assert_eq!(price_ratio(1_000_000_000, 9, 2_000_000, 6), 2.0);
+        assert_eq!(price_ratio(100, 2, 2, 0), 2.0);
+
+        // Fractional rates
+        assert_eq!(price_ratio(4_000_000_000, 9, 1_000_000, 6), 0.25);
+        assert_eq!(price_ratio(3, 0, 1_500_000_000, 9), 0.5);
+        assert!((price_ratio(1_500_000, 6, 250_000, 6) - 1.0 / 6.0).abs() < 1e-12);
+
+        // Agrees with dividing whole-token amounts
+        let (deposit, receive) = (123_456_789u64, 987_654_321u64);
+        let expected = (receive as f64 / 1e9) / (deposit as f64 / 1e6);
+        assert!((price_ratio(deposit, 6, receive, 9) - expected).abs() < 1e-12);
+
+        assert!(price_ratio(0, 6, 1, 6).is_infinite());
+    }
+
+    #[test]
+    fn test_is_escrow_checks_discriminator() {
+        use crate::client::{is_escrow, ESCROW_DISCRIMINATOR};
+
+        let mut svm = setup();
+        let Parties { maker, mint_a, mint_b, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let seed: u64 = 1;
+        let tx = Transaction::new_signed_with_payer(
+            &[crate::client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 10, receive: 10, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = svm.get_account(&crate::client::escrow_pda(&maker.pubkey(), seed).0).unwrap();
+        assert_eq!(&escrow.data[..8], ESCROW_DISCRIMINATOR);
+        assert!(is_escrow(&escrow.data));
+
+        let mint = svm.get_account(&mint_a).unwrap();
+        assert!(!is_escrow(&mint.data));
+        assert!(!is_escrow(&[]));
+    }
+
+    #[test]
+    fn test_make_rejects_non_canonical_bump() {
+        let mut svm = setup();
+        let Parties { maker, mint_a, mint_b, maker_ata_a, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        // The first bump below the canonical one that still lands off the curve
+        let seed: u64 = 5;
+        let seed_bytes = seed.to_le_bytes();
+        let (canonical, canonical_bump) = crate::client::escrow_pda(&maker.pubkey(), seed);
+        let (escrow, bump) = (0..canonical_bump)
+            .rev()
+            .find_map(|bump| {
+                Pubkey::create_program_address(&[b"escrow", maker.pubkey().as_ref(), &seed_bytes, &[bump]], &PROGRAM_ID)
+                    .ok()
+                    .map(|address| (address, bump))
+            })
+            .expect("No non-canonical bump found");
+        assert_ne!(escrow, canonical);
+        assert!(bump < canonical_bump);
+
+        let make_ix = |escrow: Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow,
+                vault: associated_token::get_associated_token_address(&escrow, &mint_a),
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program: spl_associated_token_account::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 10, receive: 10, ..Default::default() } }.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix(escrow)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), anchor_lang::error::ErrorCode::ConstraintSeeds);
+        assert!(svm.get_account(&escrow).is_none());
+
+        // The canonical address works and stores the canonical bump
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix(canonical)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make at the canonical address failed");
+        assert_eq!(read_escrow(&svm.get_account(&canonical).unwrap()).bump, canonical_bump);
+    }
+
+    #[test]
+    fn test_take_pays_maker_receive_account() {
+        let mut svm = setup();
+        let Parties { maker, taker, mint_a, mint_b, maker_ata_b, taker_ata_a, taker_ata_b, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        // A second maker-owned mint_b account that isn't the ATA
+        let treasury = CreateAccount::new(&mut svm, &maker, &mint_b)
+            .owner(&maker.pubkey()).send().unwrap();
+        // And one owned by the taker, which the maker has no say over
+        let foreign = CreateAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        // The taker can name the maker as delegate of their own account, then revoke it after the take
+        let delegated = CreateAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        Approve::new(&mut svm, &taker, &maker.pubkey(), &delegated, u64::MAX).send().unwrap();
+
+        let seed: u64 = 1;
+        let tx = Transaction::new_signed_with_payer(
+            &[crate::client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = crate::client::escrow_pda(&maker.pubkey(), seed).0;
+        let take_ix = |maker_receive_account: Pubkey| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a,
+                taker_ata_b,
+                maker_ata_b,
+                escrow,
+                vault: crate::client::vault_address(&escrow, &mint_a),
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: Some(maker_receive_account),
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config: crate::client::config_pda().0,
+                fee_authority: admin.pubkey(),
+                fee_vault: associated_token::get_associated_token_address(&admin.pubkey(), &mint_b),
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program: spl_associated_token_account::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 100, max_receive: u64::MAX }.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix(foreign)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::InvalidTokenAccount);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix(delegated)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::InvalidTokenAccount);
+        assert_eq!(get_token_balance(&svm, &delegated), 0);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix(treasury)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take into the treasury failed");
+
+        assert_eq!(get_token_balance(&svm, &treasury), 40);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 0);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), PARTY_BALANCE - 40);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 100);
+    }
+
+    #[test]
+    fn test_make_enforces_max_open_per_maker() {
+        use crate::client;
+
+        let mut svm = setup();
+        let Parties { maker, mint_a, mint_b, maker_ata_a, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let set_max_open_ix = |max_open_per_maker: u16| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::SetMaxOpenPerMaker {
+                authority: admin.pubkey(),
+                config,
+            }.to_account_metas(None),
+            data: crate::instruction::SetMaxOpenPerMaker { max_open_per_maker }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[set_max_open_ix(crate::state::MakerIndex::MAX_OPEN_ESCROWS as u16 + 1)],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::InvalidOpenEscrowLimit);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[set_max_open_ix(2)],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Set max open per maker failed");
+
+        let make = |svm: &mut LiteSVM, seed: u64| {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 10, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        make(&mut svm, 1).expect("First make failed");
+        make(&mut svm, 2).expect("Second make failed");
+        assert_error(make(&mut svm, 3), crate::error::EscrowError::TooManyOpenEscrows);
+
+        // Refunding one frees its slot
+        let tx = Transaction::new_signed_with_payer(
+            &[client::refund_ix(&maker.pubkey(), &mint_a, 1)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund failed");
+
+        svm.expire_blockhash();
+        make(&mut svm, 3).expect("Make after the refund failed");
+        assert!(svm.get_account(&client::escrow_pda(&maker.pubkey(), 3).0).is_some());
+
+        // Splitting and basket escrows count against the same limit
+        let escrow = client::escrow_pda(&maker.pubkey(), 3).0;
+        let new_escrow = client::escrow_pda(&maker.pubkey(), 4).0;
+        let split_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::SplitEscrow {
+                maker: maker.pubkey(),
+                mint_a,
+                escrow,
+                vault: client::vault_address(&escrow, &mint_a),
+                staged_take: Pubkey::find_program_address(&[b"stage", escrow.as_ref()], &PROGRAM_ID).0,
+                new_escrow,
+                new_vault: client::vault_address(&new_escrow, &mint_a),
+                maker_index: client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                associated_token_program: spl_associated_token_account::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::SplitEscrow { new_seed: 4, amount: 40 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[split_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::TooManyOpenEscrows);
+
+        let mint_a2 = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let maker_ata_a2 = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a2)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a2, &maker_ata_a2, 1_000).send().unwrap();
+
+        let basket = client::escrow_pda(&maker.pubkey(), 5).0;
+        let make_basket_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::MakeBasket {
+                maker: maker.pubkey(),
+                mint_a, mint_a2, mint_b,
+                maker_ata_a, maker_ata_a2,
+                escrow: basket,
+                vault: client::vault_address(&basket, &mint_a),
+                vault_a2: client::vault_address(&basket, &mint_a2),
+                maker_index: client::maker_index_pda(&maker.pubkey()).0,
+                config,
+                paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                paused_mint_a2: client::paused_mint_pda(&mint_a2).0,
+                paused_mint_b: client::paused_mint_pda(&mint_b).0,
+                associated_token_program: spl_associated_token_account::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::MakeBasket {
+                args: crate::instructions::MakeBasketArgs { seed: 5, deposit: 100, deposit_a2: 100, receive: 10, ..Default::default() },
+            }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_basket_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::TooManyOpenEscrows);
+    }
+
+    #[test]
+    fn test_take_on_closed_escrow_reports_closed() {
+        use crate::client;
+
+        let mut svm = setup();
+        let Parties { maker, taker, mint_a, mint_b, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let seed: u64 = 1;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 10, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        // Two takers race for the whole escrow, the second lands after the first closed it
+        let other_taker = Keypair::new();
+        svm.airdrop(&other_taker.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let take = |svm: &mut LiteSVM, taker: &Keypair| {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 100, u64::MAX)],
+                Some(&taker.pubkey()),
+                &[taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        take(&mut svm, &taker).expect("First take failed");
+        let second = take(&mut svm, &other_taker);
+
+        let escrow = svm.get_account(&client::escrow_pda(&maker.pubkey(), seed).0);
+        let code = match &second.as_ref().expect_err("Second take should fail").err {
+            TransactionError::InstructionError(_, InstructionError::Custom(code)) => *code,
+            err => panic!("Expected a custom error, got {err:?}"),
+        };
+        assert!(client::is_already_closed_error(code, escrow.as_ref().map(|account| account.data.as_slice())));
+        assert_error(second, anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram);
+
+        // The same error with the escrow still open, or another error, is something else
+        let open: crate::state::Escrow = bytemuck::Zeroable::zeroed();
+        let open_data = [client::ESCROW_DISCRIMINATOR, bytemuck::bytes_of(&open)].concat();
+        assert!(!client::is_already_closed_error(code, Some(&open_data)));
+        assert!(!client::is_already_closed_error(crate::error::EscrowError::EscrowExpired.into(), None));
+    }
+
+    #[test]
+    fn test_take_prices_from_oracle() {
+        use crate::state::OraclePrice;
+
+        let mut svm = setup();
+        let Parties { maker, taker, mint_a, mint_b, maker_ata_b, taker_ata_a, taker_ata_b, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+
+        // A mock feed: any owner, the OraclePrice bytes at the start of the data
+        let feed = Pubkey::new_unique();
+        let set_feed = |svm: &mut LiteSVM, price: u64, publish_time: i64| {
+            let data = bytemuck::bytes_of(&OraclePrice { price, publish_time, decimals: 2, _padding: [0; 7] }).to_vec();
+            svm.set_account(feed, Account {
+                lamports: svm.minimum_balance_for_rent_exemption(data.len()),
+                data,
+                owner: Pubkey::new_unique(),
+                executable: false,
+                rent_epoch: 0,
+            }).unwrap();
+        };
+        set_feed(&mut svm, 45, now);
+
+        let seed: u64 = 1;
+        let args = MakeArgs {
+            seed,
+            deposit: 100,
+            receive: 80,
+            oracle: feed,
+            oracle_max_staleness: 60,
+            oracle_min_price: 10,
+            oracle_max_price: 100,
+            ..Default::default()
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[crate::client::make_ix(&maker.pubkey(), &mint_a, &mint_b, args)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = crate::client::escrow_pda(&maker.pubkey(), seed).0;
+        let take_ix = |oracle: Option<Pubkey>| Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a,
+                taker_ata_b,
+                maker_ata_b,
+                escrow,
+                vault: crate::client::vault_address(&escrow, &mint_a),
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle,
+                maker_index: crate::client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: crate::client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: crate::client::paused_mint_pda(&mint_b).0,
+                config: crate::client::config_pda().0,
+                fee_authority: admin.pubkey(),
+                fee_vault: associated_token::get_associated_token_address(&admin.pubkey(), &mint_b),
+                fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                receipt: crate::client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program: spl_associated_token_account::ID,
+                token_program: TOKEN_PROGRAM_ID,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 100, max_receive: u64::MAX }.data(),
+        };
+        let send = |svm: &mut LiteSVM, oracle: Option<Pubkey>| {
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[take_ix(oracle)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        assert_error(send(&mut svm, None), crate::error::EscrowError::OracleAccountMissing);
+        assert_error(send(&mut svm, Some(Pubkey::new_unique())), crate::error::EscrowError::InvalidOracle);
+
+        // Past the staleness window
+        warp_to_timestamp(&mut svm, now + 61);
+        assert_error(send(&mut svm, Some(feed)), crate::error::EscrowError::OracleStale);
+
+        // Fresh but outside the maker's bounds
+        set_feed(&mut svm, 200, now + 61);
+        assert_error(send(&mut svm, Some(feed)), crate::error::EscrowError::OraclePriceOutOfBounds);
+
+        // 100 mint_a at 0.45 mint_b each, not the escrow's fixed receive of 80
+        set_feed(&mut svm, 45, now + 61);
+        send(&mut svm, Some(feed)).expect("Take at the oracle price failed");
+
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 45);
+        assert_eq!(get_token_balance(&svm, &taker_ata_b), PARTY_BALANCE - 45);
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 100);
+    }
+
+    // The .so under test has to be built with the debug feature too
+    #[cfg(feature = "debug")]
+    #[test]
+    fn test_debug_logs_pdas() {
+        use crate::client;
+
+        let mut svm = setup();
+        let Parties { maker, taker, mint_a, mint_b, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let seed: u64 = 1;
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let expected = format!("Program log: escrow: {escrow}, vault: {}", client::vault_address(&escrow, &mint_a));
+        let assert_logged = |logs: &[String]| {
+            assert!(logs.contains(&expected), "Missing {expected:?} in logs: {logs:#?}");
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_logged(&svm.send_transaction(tx).expect("Make failed").logs);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 50, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert_logged(&svm.send_transaction(tx).expect("Take failed").logs);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::refund_ix(&maker.pubkey(), &mint_a, seed)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        assert_logged(&svm.send_transaction(tx).expect("Refund failed").logs);
+    }
+
+    #[test]
+    fn test_make_whole_stores_base_units() {
+        use crate::client;
+
+        let mut svm = setup();
+        let Parties { maker, mint_a, mint_b, maker_ata_a, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        // Both mints have 6 decimals; args.deposit and args.receive are ignored
+        let seed: u64 = 1;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_whole_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 1, receive: 1, ..Default::default() }, 3, 2)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("MakeWhole failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let escrow_data = read_escrow(&svm.get_account(&escrow).unwrap());
+        assert_eq!(escrow_data.deposit, 3_000_000);
+        assert_eq!(escrow_data.receive, 2_000_000);
+        assert_eq!(escrow_data.start_receive, 2_000_000);
+        assert_eq!(get_token_balance(&svm, &client::vault_address(&escrow, &mint_a)), 3_000_000);
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), PARTY_BALANCE - 3_000_000);
+
+        // Scaling that overflows a u64 fails instead of wrapping
+        assert!(crate::pricing::whole_to_base_units(u64::MAX, 6).is_err());
+        assert_eq!(crate::pricing::whole_to_base_units(5, 0).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_refund_closes_escrow_with_empty_vault() {
+        use crate::client;
+
+        let mut svm = setup();
+        let Parties { maker, taker, mint_a, mint_b, maker_ata_a, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let seed: u64 = 1;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 50, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 40, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Partial take failed");
+
+        // Drain the rest of the vault without the escrow being closed
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = client::vault_address(&escrow, &mint_a);
+        let mut vault_account = svm.get_account(&vault).unwrap();
+        let mut token_account = spl_token::state::Account::unpack(&vault_account.data).unwrap();
+        token_account.amount = 0;
+        spl_token::state::Account::pack(token_account, &mut vault_account.data).unwrap();
+        svm.set_account(vault, vault_account).unwrap();
+
+        let rent = svm.get_account(&escrow).unwrap().lamports + svm.get_account(&vault).unwrap().lamports;
+        let maker_lamports = svm.get_account(&maker.pubkey()).unwrap().lamports;
+        let maker_balance_a = get_token_balance(&svm, &maker_ata_a);
+
+        let tx = Transaction::new_signed_with_payer(
+            &[client::refund_ix(&maker.pubkey(), &mint_a, seed)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund of the empty escrow failed");
+
+        assert!(svm.get_account(&escrow).is_none_or(|account| account.lamports == 0), "Escrow should be closed");
+        assert!(svm.get_account(&vault).is_none_or(|account| account.lamports == 0), "Vault should be closed");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), maker_balance_a);
+        // Both rents come back, less the transaction fee
+        assert!(svm.get_account(&maker.pubkey()).unwrap().lamports >= maker_lamports + rent - 5_000);
+    }
+
+    #[test]
+    fn test_refund_authority_can_refund() {
+        use crate::client;
+
+        let mut svm = setup();
+        let Parties { maker, mint_a, mint_b, maker_ata_a, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let treasury = Keypair::new();
+        let intruder = Keypair::new();
+        svm.airdrop(&treasury.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&intruder.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let seed: u64 = 1;
+        let args = MakeArgs { seed, deposit: 100, receive: 40, refund_authority: treasury.pubkey(), ..Default::default() };
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, args)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        assert_eq!(read_escrow(&svm.get_account(&escrow).unwrap()).refund_authority, treasury.pubkey());
+
+        // A key the escrow doesn't name can't stand in for the maker
+        let tx = Transaction::new_signed_with_payer(
+            &[client::refund_ix_with_authority(&maker.pubkey(), Some(&intruder.pubkey()), &mint_a, seed)],
+            Some(&intruder.pubkey()),
+            &[&intruder],
+            svm.latest_blockhash(),
+        );
+        assert_error(svm.send_transaction(tx), crate::error::EscrowError::RefundNotAuthorized);
+
+        // The treasury can, without the maker signing, and the deposit still goes back to the maker
+        let tx = Transaction::new_signed_with_payer(
+            &[client::refund_ix_with_authority(&maker.pubkey(), Some(&treasury.pubkey()), &mint_a, seed)],
+            Some(&treasury.pubkey()),
+            &[&treasury],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Refund by the refund authority failed");
+
+        assert!(svm.get_account(&escrow).is_none_or(|account| account.lamports == 0), "Escrow should be closed");
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), PARTY_BALANCE);
+    }
+
+    #[test]
+    fn test_take_leaves_receipt() {
+        use crate::{client, state::Receipt};
+
+        let mut svm = setup();
+        let Parties { maker, taker, mint_a, mint_b, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        let now = 1_700_000_000;
+        warp_to_timestamp(&mut svm, now);
+
+        let seed: u64 = 1;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 100, receive: 40, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let receipt = client::receipt_pda(&escrow, &taker.pubkey()).0;
+
+        // A partial fill, then the rest, which closes the escrow
+        for (take_amount, at) in [(25, now), (75, now + 60)] {
+            warp_to_timestamp(&mut svm, at);
+            svm.expire_blockhash();
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), take_amount, u64::MAX)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Take failed");
+        }
+        assert!(svm.get_account(&escrow).is_none_or(|account| account.lamports == 0), "Escrow should be closed");
+
+        let receipt_account = svm.get_account(&receipt).expect("Receipt should outlive the escrow");
+        assert_eq!(receipt_account.owner, PROGRAM_ID);
+        let receipt = Receipt::try_deserialize(&mut receipt_account.data.as_slice()).unwrap();
+        assert_eq!(receipt.escrow, escrow);
+        assert_eq!(receipt.maker, maker.pubkey());
+        assert_eq!(receipt.taker, taker.pubkey());
+        assert_eq!(receipt.mint_a, mint_a);
+        assert_eq!(receipt.mint_b, mint_b);
+        assert_eq!(receipt.amount_a, 100);
+        assert_eq!(receipt.amount_b, 40);
+        assert_eq!(receipt.fills, 2);
+        assert_eq!(receipt.first_fill_at, now);
+        assert_eq!(receipt.last_fill_at, now + 60);
+        assert_eq!(receipt.created_at, now);
+
+        // The same address made again starts a new receipt instead of adding to the old escrow's fills
+        warp_to_timestamp(&mut svm, now + 120);
+        svm.expire_blockhash();
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 50, receive: 30, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Remake failed");
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 50, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take of the remade escrow failed");
+
+        let receipt_account = svm.get_account(&client::receipt_pda(&escrow, &taker.pubkey()).0).unwrap();
+        let receipt = Receipt::try_deserialize(&mut receipt_account.data.as_slice()).unwrap();
+        assert_eq!((receipt.amount_a, receipt.amount_b, receipt.fills), (50, 30, 1));
+        assert_eq!(receipt.first_fill_at, now + 120);
+        assert_eq!(receipt.created_at, now + 120);
+    }
+
+    #[test]
+    fn test_partial_fill_rounding_modes() {
+        use crate::{error::EscrowError, state::{Escrow, OraclePrice, RoundingMode}};
+
+        // 1 of 3 on a receive of 10 is 3.33 mint_b
+        let escrow = Escrow { receive: 10, start_receive: 10, ..bytemuck::Zeroable::zeroed() };
+        assert_eq!(escrow.rounding(), RoundingMode::MakerFavored);
+        assert_eq!(escrow.fill(1, 3, 0).unwrap(), (4, 4));
+
+        let taker_favored = Escrow { rounding: RoundingMode::TakerFavored as u8, ..escrow };
+        assert_eq!(taker_favored.fill(1, 3, 0).unwrap(), (3, 3));
+
+        // Even splits and full takes are the same either way
+        assert_eq!(escrow.fill(3, 3, 0).unwrap(), taker_favored.fill(3, 3, 0).unwrap());
+        let even = Escrow { receive: 9, start_receive: 9, ..escrow };
+        assert_eq!(even.fill(1, 3, 0).unwrap(), Escrow { rounding: RoundingMode::TakerFavored as u8, ..even }.fill(1, 3, 0).unwrap());
+
+        // Rounding a fill down to nothing is rejected rather than given away
+        let dust: anchor_lang::error::Error = EscrowError::FillTooSmall.into();
+        assert_eq!(taker_favored.fill(1, 100, 0).unwrap_err(), dust);
+        assert_eq!(escrow.fill(1, 100, 0).unwrap(), (1, 1));
+
+        // Oracle pricing follows the same direction: 3 at 1.05 is 3.15 mint_b
+        let feed = OraclePrice { price: 105, publish_time: 0, decimals: 2, _padding: [0; 7] };
+        assert_eq!(escrow.oracle_receive(&feed, 3, 0).unwrap(), 4);
+        assert_eq!(taker_favored.oracle_receive(&feed, 3, 0).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_take_rounds_taker_favored() {
+        use crate::{client, state::RoundingMode};
+
+        let mut svm = setup();
+        let Parties { maker, taker, mint_a, mint_b, maker_ata_b, .. } = setup_parties(&mut svm);
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm, &admin, 0);
+
+        for (seed, rounding, paid) in [(1u64, RoundingMode::MakerFavored, 4), (2, RoundingMode::TakerFavored, 3)] {
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, MakeArgs { seed, deposit: 3, receive: 10, rounding, ..Default::default() })],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+
+            let maker_before = get_token_balance(&svm, &maker_ata_b);
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), 1, u64::MAX)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Partial take failed");
+
+            assert_eq!(get_token_balance(&svm, &maker_ata_b) - maker_before, paid, "{rounding:?}");
+        }
+    }
+
+    #[test]
+    fn test_lifecycle_with_nonstandard_decimals() {
+        use crate::client;
+
+        for decimals in [0u8, 9] {
+            let mut svm = setup();
+            let Parties { maker, taker, mint_a, mint_b, maker_ata_a, maker_ata_b, taker_ata_a, taker_ata_b } = setup_parties_with_decimals(&mut svm, decimals);
+            let unit = 10u64.pow(decimals as u32);
+
+            // Top both sides up so every party holds at least 10 whole tokens
+            MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 10 * unit).send().unwrap();
+            MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 10 * unit).send().unwrap();
+            let maker_start_a = get_token_balance(&svm, &maker_ata_a);
+            let taker_start_b = get_token_balance(&svm, &taker_ata_b);
+
+            let admin = Keypair::new();
+            svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+            init_config(&mut svm, &admin, 0);
+
+            // 3 whole mint_a for 2 whole mint_b, scaled on chain
+            let seed: u64 = 1;
+            let args = MakeArgs { seed, receive_decimals: Some(decimals), ..Default::default() };
+            let tx = Transaction::new_signed_with_payer(
+                &[client::make_whole_ix(&maker.pubkey(), &mint_a, &mint_b, args, 3, 2)],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Make failed");
+
+            let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+            let vault = client::vault_address(&escrow, &mint_a);
+            let escrow_data = read_escrow(&svm.get_account(&escrow).unwrap());
+            assert_eq!(escrow_data.deposit, 3 * unit, "decimals {decimals}");
+            assert_eq!(escrow_data.receive, 2 * unit, "decimals {decimals}");
+            assert_eq!(escrow_data.receive_decimals, decimals);
+            assert_eq!(get_token_balance(&svm, &vault), 3 * unit);
+            assert_eq!(get_token_balance(&svm, &maker_ata_a), maker_start_a - 3 * unit);
+
+            // A third of the vault costs a third of receive, rounded up for the maker
+            let paid = (2 * unit).div_ceil(3);
+            let tx = Transaction::new_signed_with_payer(
+                &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), unit, u64::MAX)],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Take failed");
+
+            assert_eq!(get_token_balance(&svm, &vault), 2 * unit, "decimals {decimals}");
+            assert_eq!(get_token_balance(&svm, &taker_ata_a), unit);
+            assert_eq!(get_token_balance(&svm, &maker_ata_b), paid);
+            assert_eq!(get_token_balance(&svm, &taker_ata_b), taker_start_b - paid);
+
+            let tx = Transaction::new_signed_with_payer(
+                &[client::refund_ix(&maker.pubkey(), &mint_a, seed)],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("Refund failed");
+
+            assert!(svm.get_account(&escrow).is_none_or(|account| account.lamports == 0), "Escrow should be closed");
+            assert!(svm.get_account(&vault).is_none_or(|account| account.lamports == 0), "Vault should be closed");
+            assert_eq!(get_token_balance(&svm, &maker_ata_a), maker_start_a - unit, "decimals {decimals}");
+            assert_eq!(get_token_balance(&svm, &maker_ata_b), paid);
+        }
+    }
+
+    #[test]
+    fn test_top_up_records_what_the_vault_received() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let token_program = spl_token_2022::ID;
+
+        // mint_a charges a 1% transfer fee
+        let mint_a = create_transfer_fee_mint(&mut svm, &maker, 100);
+        let mint_b = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .token_program_id(&token_program)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).token_program_id(&token_program).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).token_program_id(&token_program).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 10_000).token_program_id(&token_program).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_b, &taker_ata_b, 10_000).token_program_id(&token_program).send().unwrap();
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let seed: u64 = 1;
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = associated_token::get_associated_token_address_with_program_id(&escrow, &mint_a, &token_program);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program: spl_associated_token_account::ID,
+                token_program,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 100, receive: 10, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+        let deposit_before = read_escrow(&svm.get_account(&escrow).unwrap()).deposit;
+        let vault_before = get_token_balance(&svm, &vault);
+
+        let top_up_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::TopUp {
+                maker: maker.pubkey(),
+                mint_a,
+                maker_ata_a,
+                escrow, vault,
+                token_program,
+            }.to_account_metas(None),
+            data: crate::instruction::TopUp { deposit: 1_000 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[top_up_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Top up failed");
+
+        // 1000 sent, 10 withheld by the mint, 990 lands in the vault
+        assert_eq!(get_token_balance(&svm, &vault), vault_before + 990);
+        assert_eq!(read_escrow(&svm.get_account(&escrow).unwrap()).deposit, deposit_before + 990);
+
+        // The recorded deposit matches the vault, so the whole thing can still be taken
+        let ata = |owner: &Pubkey, mint: &Pubkey| {
+            associated_token::get_associated_token_address_with_program_id(owner, mint, &token_program)
+        };
+        let take_amount = get_token_balance(&svm, &vault);
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a: ata(&taker.pubkey(), &mint_a),
+                taker_ata_b,
+                maker_ata_b: ata(&maker.pubkey(), &mint_b),
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault: ata(&admin.pubkey(), &mint_b),
+                fee_vault_a: ata(&admin.pubkey(), &mint_a),
+                receipt: client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program: spl_associated_token_account::ID,
+                token_program,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take of the topped up escrow failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after a full take");
+    }
+    #[test]
+    fn test_make_with_transfer_fee_mint_can_be_taken_in_full() {
+        use crate::client;
+
+        let mut svm = setup();
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let token_program = spl_token_2022::ID;
+
+        // mint_a charges a 1% transfer fee
+        let mint_a = create_transfer_fee_mint(&mut svm, &maker, 100);
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .token_program_id(&token_program)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).token_program_id(&token_program).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).token_program_id(&token_program).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 10_000).token_program_id(&token_program).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 10_000).token_program_id(&token_program).send().unwrap();
+
+        let ata = |owner: &Pubkey, mint: &Pubkey| {
+            associated_token::get_associated_token_address_with_program_id(owner, mint, &token_program)
+        };
+        let seed: u64 = 1;
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let vault = ata(&escrow, &mint_a);
+        let maker_ata_b = ata(&maker.pubkey(), &mint_b);
+        let taker_ata_a = ata(&taker.pubkey(), &mint_a);
+        let associated_token_program = spl_associated_token_account::ID;
+
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let make_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Make {
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                maker_ata_a,
+                escrow, vault,
+                maker_index: client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: client::paused_mint_pda(&mint_b).0,
+                config,
+                associated_token_program,
+                token_program,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Make { args: MakeArgs { seed, deposit: 1_000, receive: 500, ..Default::default() } }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_ix],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        // 1000 sent, 10 withheld by the mint, the escrow only promises the 990 that landed
+        assert_eq!(get_token_balance(&svm, &vault), 990);
+        assert_eq!(read_escrow(&svm.get_account(&escrow).unwrap()).deposit, 990);
+
+        let take_ix = Instruction {
+            program_id: PROGRAM_ID,
+            accounts: crate::accounts::Take {
+                taker: taker.pubkey(),
+                maker: maker.pubkey(),
+                mint_a, mint_b,
+                taker_ata_a,
+                taker_ata_b,
+                maker_ata_b,
+                escrow, vault,
+                mint_a2: None, taker_ata_a2: None, vault_a2: None,
+                royalty_ata_b: None,
+                maker_receive_account: None,
+                sol_unwrap: None,
+                oracle: None,
+                maker_index: client::maker_index_pda(&maker.pubkey()).0,
+                paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: client::paused_mint_pda(&mint_b).0,
+                config,
+                fee_authority: admin.pubkey(),
+                fee_vault: ata(&admin.pubkey(), &mint_b),
+                fee_vault_a: ata(&admin.pubkey(), &mint_a),
+                receipt: client::receipt_pda(&escrow, &taker.pubkey()).0,
+                associated_token_program,
+                token_program,
+                system_program: SYSTEM_PROGRAM_ID,
+            }.to_account_metas(None),
+            data: crate::instruction::Take { take_amount: 990, max_receive: u64::MAX }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[take_ix],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Take of the full deposit failed");
+
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after a full take");
+        // 990 sent out of the vault, the mint withholds 10 on the way to the taker
+        assert_eq!(get_token_balance(&svm, &taker_ata_a), 980);
+        assert_eq!(get_token_balance(&svm, &maker_ata_b), 500);
+    }
+    #[test]
+    fn test_paused_mint_blocks_make_basket_and_take_with_sol() {
+        use crate::client;
+
+        let mut svm = setup();
+        ensure_native_mint(&mut svm);
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        let admin = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+        let config = init_config(&mut svm, &admin, 0);
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_a2 = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let maker_ata_a2 = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a2)
+            .owner(&maker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a2, &maker_ata_a2, 1_000).send().unwrap();
+
+        let set_mint_paused = |svm: &mut LiteSVM, mint: Pubkey, paused: bool| {
+            let ix = Instruction {
+                program_id: PROGRAM_ID,
+                accounts: crate::accounts::SetMintPaused {
+                    authority: admin.pubkey(),
+                    config,
+                    mint,
+                    paused_mint: client::paused_mint_pda(&mint).0,
+                    system_program: SYSTEM_PROGRAM_ID,
+                }.to_account_metas(None),
+                data: crate::instruction::SetMintPaused { paused }.data(),
+            };
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&admin.pubkey()),
+                &[&admin],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx).expect("SetMintPaused failed");
+        };
+
+        let make_basket = |svm: &mut LiteSVM, seed: u64| {
+            let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+            let ix = Instruction {
+                program_id: PROGRAM_ID,
+                accounts: crate::accounts::MakeBasket {
+                    maker: maker.pubkey(),
+                    mint_a, mint_a2, mint_b,
+                    maker_ata_a, maker_ata_a2,
+                    escrow,
+                    vault: client::vault_address(&escrow, &mint_a),
+                    vault_a2: client::vault_address(&escrow, &mint_a2),
+                    maker_index: client::maker_index_pda(&maker.pubkey()).0,
+                    config,
+                    paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                    paused_mint_a2: client::paused_mint_pda(&mint_a2).0,
+                    paused_mint_b: client::paused_mint_pda(&mint_b).0,
+                    associated_token_program: spl_associated_token_account::ID,
+                    token_program: TOKEN_PROGRAM_ID,
+                    system_program: SYSTEM_PROGRAM_ID,
+                }.to_account_metas(None),
+                data: crate::instruction::MakeBasket {
+                    args: crate::instructions::MakeBasketArgs { seed, deposit: 100, deposit_a2: 100, receive: 10, ..Default::default() },
+                }.data(),
+            };
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&maker.pubkey()),
+                &[&maker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        // Every leg of a basket is checked, not just mint_a
+        for mint in [mint_a, mint_a2, mint_b] {
+            set_mint_paused(&mut svm, mint, true);
+            assert_error(make_basket(&mut svm, 1), crate::error::EscrowError::MintPaused);
+            set_mint_paused(&mut svm, mint, false);
+        }
+        make_basket(&mut svm, 1).expect("Make basket with no paused mint failed");
+
+        // A wSOL escrow made before the pause can't be taken through TakeWithSol either
+        let seed = 2;
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &native_mint::ID, MakeArgs { seed, deposit: 100, receive: LAMPORTS_PER_SOL, ..Default::default() })],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let take_with_sol = |svm: &mut LiteSVM| {
+            let ix = Instruction {
+                program_id: PROGRAM_ID,
+                accounts: crate::accounts::TakeWithSol {
+                    taker: taker.pubkey(),
+                    maker: maker.pubkey(),
+                    maker_index: client::maker_index_pda(&maker.pubkey()).0,
+                    mint_a,
+                    mint_b: native_mint::ID,
+                    taker_ata_a: associated_token::get_associated_token_address(&taker.pubkey(), &mint_a),
+                    escrow,
+                    vault: client::vault_address(&escrow, &mint_a),
+                    config,
+                    paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                    paused_mint_b: client::paused_mint_pda(&native_mint::ID).0,
+                    fee_authority: admin.pubkey(),
+                    fee_vault_a: associated_token::get_associated_token_address(&admin.pubkey(), &mint_a),
+                    receipt: client::receipt_pda(&escrow, &taker.pubkey()).0,
+                    associated_token_program: spl_associated_token_account::ID,
+                    token_program: TOKEN_PROGRAM_ID,
+                    system_program: SYSTEM_PROGRAM_ID,
+                }.to_account_metas(None),
+                data: crate::instruction::TakeWithSol { take_amount: 100, max_receive: u64::MAX }.data(),
+            };
+            let tx = Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        for mint in [mint_a, native_mint::ID] {
+            set_mint_paused(&mut svm, mint, true);
+            assert_error(take_with_sol(&mut svm), crate::error::EscrowError::MintPaused);
+            set_mint_paused(&mut svm, mint, false);
+        }
+        take_with_sol(&mut svm).expect("Take with SOL after unpausing failed");
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after take");
+    }
 }
\ No newline at end of file
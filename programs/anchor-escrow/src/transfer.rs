@@ -0,0 +1,69 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{token_2022::{self, spl_token_2022::{extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions}, state::Mint as MintState}}, token_interface::{Mint, TokenAccount}};
+
+use crate::error::EscrowError;
+
+//Token-2022 transfer fee math and post-transfer balance checks, shared by the instructions that fund or pay out of a vault
+
+//Mint to send so the destination nets `net` after any Token-2022 transfer fee
+pub fn gross_for(mint: &InterfaceAccount<Mint>, net: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != token_2022::ID {
+        return Ok(net);
+    }
+
+    let data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<MintState>::unpack(&data)?;
+    let Ok(fee_config) = mint.get_extension::<TransferFeeConfig>() else {
+        return Ok(net);
+    };
+
+    let epoch = Clock::get()?.epoch;
+    let transfer_fee = fee_config
+        .get_epoch_fee(epoch)
+        .calculate_inverse_fee(net)
+        .ok_or(EscrowError::MathOverflow)?;
+
+    let gross = net.checked_add(transfer_fee).ok_or(EscrowError::MathOverflow)?;
+
+    Ok(gross)
+}
+
+//What the destination nets from `amount` after any Token-2022 transfer fee
+pub fn net_of(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != token_2022::ID {
+        return Ok(amount);
+    }
+
+    let data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<MintState>::unpack(&data)?;
+    let Ok(fee_config) = mint.get_extension::<TransferFeeConfig>() else {
+        return Ok(amount);
+    };
+
+    let epoch = Clock::get()?.epoch;
+    let transfer_fee = fee_config
+        .calculate_epoch_fee(epoch, amount)
+        .ok_or(EscrowError::MathOverflow)?;
+
+    Ok(amount.saturating_sub(transfer_fee))
+}
+
+//What `account` gained since it held `before`, a Token-2022 transfer fee lands less than was sent
+pub fn received_since(account: &mut InterfaceAccount<TokenAccount>, before: u64) -> Result<u64> {
+    account.reload()?;
+
+    account.amount.checked_sub(before).ok_or(error!(EscrowError::MathOverflow))
+}
+
+//Guards against a token program that reports success without moving the full amount
+//Checked right after the transfer, before any fee transfer could land in the same account
+pub fn verify_received(account: &mut InterfaceAccount<TokenAccount>, before: u64, expected: u64) -> Result<()> {
+    account.reload()?;
+
+    let received = account.amount.checked_sub(before);
+    require!(received.is_some_and(|received| received >= expected), EscrowError::TransferVerificationFailed);
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+#![allow(unexpected_cfgs)]
+#![allow(deprecated)]
+
+use anchor_lang::{prelude::*, solana_program::{instruction::Instruction, program::invoke_signed}, InstructionData};
+use anchor_escrow::{client::{make_account_metas, MakeArgs}, program::AnchorEscrow, settlement_hook::SettlementHookArgs};
+
+mod tests;
+
+declare_id!("7Bc1bcrLLA7jb23z8BedqpPRVhUB8VQ7h6n57tgmW8HE");
+
+//Makes escrows for users through CPI into anchor-escrow
+//The maker is this program's PDA [b"maker", user], so the escrow can only be refunded or updated through this program
+#[program]
+pub mod escrow_cpi_example {
+    use super::*;
+
+    pub fn make_for_user(ctx: Context<MakeForUser>, args: MakeArgs) -> Result<()> {
+        ctx.accounts.make_escrow(args, ctx.bumps.maker)
+    }
+
+    //Settlement hook for escrows that name this program, anchor-escrow's Take calls it after every fill
+    //Demo only: it trusts the args without checking that anchor-escrow is the caller
+    pub fn settlement_hook(ctx: Context<RecordSettlement>, args: SettlementHookArgs) -> Result<()> {
+        ctx.accounts.record(args, ctx.bumps.record)
+    }
+}
+
+//Fills the hook was told about, one record per escrow
+#[account]
+#[derive(InitSpace)]
+pub struct SettlementRecord {
+    pub escrow: Pubkey,
+    pub last_taker: Pubkey,
+    pub calls: u64,
+    pub total_take_amount: u64,
+    pub total_receive_amount: u64,
+    pub bump: u8,
+}
+
+#[derive(Accounts)]
+#[instruction(args: SettlementHookArgs)]
+pub struct RecordSettlement<'info> {
+    //The taker, passed on as a signer by Take
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [b"settlement", args.escrow.as_ref()],
+        bump,
+        space = 8 + SettlementRecord::INIT_SPACE,
+    )]
+    pub record: Account<'info, SettlementRecord>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RecordSettlement<'info> {
+    pub fn record(&mut self, args: SettlementHookArgs, bump: u8) -> Result<()> {
+        self.record.escrow = args.escrow;
+        self.record.last_taker = args.taker;
+        self.record.calls += 1;
+        self.record.total_take_amount += args.take_amount;
+        self.record.total_receive_amount += args.receive_amount;
+        self.record.bump = bump;
+
+        Ok(())
+    }
+}
+
+//The escrow side accounts are checked by anchor-escrow itself
+#[derive(Accounts)]
+pub struct MakeForUser<'info> {
+    pub user: Signer<'info>,
+    //Holds the deposit in its ATA and pays the escrow rent, so it has to be funded first
+    #[account(
+        mut,
+        seeds = [b"maker", user.key().as_ref()],
+        bump,
+    )]
+    pub maker: SystemAccount<'info>,
+    /// CHECK: checked by anchor-escrow
+    pub mint_a: UncheckedAccount<'info>,
+    /// CHECK: checked by anchor-escrow
+    pub mint_b: UncheckedAccount<'info>,
+    /// CHECK: checked by anchor-escrow
+    #[account(mut)]
+    pub maker_ata_a: UncheckedAccount<'info>,
+    /// CHECK: checked by anchor-escrow
+    #[account(mut)]
+    pub escrow: UncheckedAccount<'info>,
+    /// CHECK: checked by anchor-escrow
+    #[account(mut)]
+    pub vault: UncheckedAccount<'info>,
+    /// CHECK: checked by anchor-escrow
+    #[account(mut)]
+    pub maker_index: UncheckedAccount<'info>,
+    /// CHECK: checked by anchor-escrow
+    #[account(mut)]
+    pub config: UncheckedAccount<'info>,
+    /// CHECK: checked by anchor-escrow
+    pub paused_mint_a: UncheckedAccount<'info>,
+    /// CHECK: checked by anchor-escrow
+    pub paused_mint_b: UncheckedAccount<'info>,
+    pub escrow_program: Program<'info, AnchorEscrow>,
+    /// CHECK: checked by anchor-escrow
+    pub associated_token_program: UncheckedAccount<'info>,
+    /// CHECK: checked by anchor-escrow
+    pub token_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MakeForUser<'info> {
+    pub fn make_escrow(&self, args: MakeArgs, maker_bump: u8) -> Result<()> {
+        let make_ix = Instruction {
+            program_id: self.escrow_program.key(),
+            accounts: make_account_metas(&self.maker.key(), &self.mint_a.key(), &self.mint_b.key(), args.seed, args.nonce, &args.namespace),
+            data: anchor_escrow::instruction::Make { args }.data(),
+        };
+
+        let user = self.user.key();
+        let signer_seeds: [&[&[u8]]; 1] = [&[
+            b"maker",
+            user.as_ref(),
+            &[maker_bump]
+        ]];
+
+        invoke_signed(
+            &make_ix,
+            &[
+                self.maker.to_account_info(),
+                self.mint_a.to_account_info(),
+                self.mint_b.to_account_info(),
+                self.maker_ata_a.to_account_info(),
+                self.escrow.to_account_info(),
+                self.vault.to_account_info(),
+                self.maker_index.to_account_info(),
+                self.config.to_account_info(),
+                self.paused_mint_a.to_account_info(),
+                self.paused_mint_b.to_account_info(),
+                self.associated_token_program.to_account_info(),
+                self.token_program.to_account_info(),
+                self.system_program.to_account_info(),
+            ],
+            &signer_seeds,
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+#[cfg(test)]
+#[allow(clippy::module_inception, clippy::result_large_err)]
+mod tests {
+    use {
+        anchor_escrow::client::{self, MakeArgs},
+        anchor_lang::{
+            solana_program::program_pack::Pack,
+            system_program, AccountDeserialize, InstructionData, ToAccountMetas,
+        },
+        litesvm::LiteSVM,
+        litesvm_token::{spl_token, CreateAssociatedTokenAccount, CreateMint, MintTo},
+        solana_instruction::{AccountMeta, Instruction},
+        solana_keypair::Keypair,
+        solana_native_token::LAMPORTS_PER_SOL,
+        solana_pubkey::Pubkey,
+        solana_signer::Signer,
+        solana_transaction::Transaction,
+        std::path::PathBuf,
+    };
+
+    // Both programs have to be built first, the example only works against a deployed escrow
+    fn setup() -> LiteSVM {
+        let mut svm = LiteSVM::new();
+        let deploy = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../target/deploy");
+        for (program_id, so_name) in [(anchor_escrow::ID, "anchor_escrow.so"), (crate::ID, "escrow_cpi_example.so")] {
+            let program_data = std::fs::read(deploy.join(so_name)).expect("Failed to read program SO file");
+            svm.add_program(program_id, &program_data);
+        }
+        svm
+    }
+
+    fn get_token_balance(svm: &LiteSVM, ata: &Pubkey) -> u64 {
+        spl_token::state::Account::unpack(&svm.get_account(ata).unwrap().data).unwrap().amount
+    }
+
+    // Zero fee config, returns its authority
+    fn init_config(svm: &mut LiteSVM) -> Keypair {
+        let admin = Keypair::new();
+        svm.airdrop(&admin.pubkey(), LAMPORTS_PER_SOL).unwrap();
+
+        let init_config_ix = Instruction {
+            program_id: anchor_escrow::ID,
+            accounts: anchor_escrow::accounts::InitConfig {
+                authority: admin.pubkey(),
+                config: client::config_pda().0,
+                system_program: system_program::ID,
+            }.to_account_metas(None),
+            data: anchor_escrow::instruction::InitConfig { fee_bps: 0 }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[init_config_ix],
+            Some(&admin.pubkey()),
+            &[&admin],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Init config failed");
+
+        admin
+    }
+
+    #[test]
+    fn test_make_through_cpi() {
+        let mut svm = setup();
+
+        let user = Keypair::new();
+        svm.airdrop(&user.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        init_config(&mut svm);
+
+        // The maker PDA needs lamports for the escrow rent and mint_a in its ATA
+        let maker = Pubkey::find_program_address(&[b"maker", user.pubkey().as_ref()], &crate::ID).0;
+        svm.airdrop(&maker, LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &user)
+            .authority(&user.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &user)
+            .authority(&user.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &user, &mint_a)
+            .owner(&maker).send().unwrap();
+        MintTo::new(&mut svm, &user, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+
+        let args = MakeArgs { seed: 1, deposit: 400, receive: 100, ..Default::default() };
+        let escrow = client::escrow_pda(&maker, 1).0;
+        let vault = client::vault_address(&escrow, &mint_a);
+
+        let make_for_user_ix = Instruction {
+            program_id: crate::ID,
+            accounts: crate::accounts::MakeForUser {
+                user: user.pubkey(),
+                maker,
+                mint_a,
+                mint_b,
+                maker_ata_a,
+                escrow,
+                vault,
+                maker_index: client::maker_index_pda(&maker).0,
+                config: client::config_pda().0,
+                paused_mint_a: client::paused_mint_pda(&mint_a).0,
+                paused_mint_b: client::paused_mint_pda(&mint_b).0,
+                escrow_program: anchor_escrow::ID,
+                associated_token_program: anchor_lang::solana_program::pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL"),
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            }.to_account_metas(None),
+            data: crate::instruction::MakeForUser { args }.data(),
+        };
+        let tx = Transaction::new_signed_with_payer(
+            &[make_for_user_ix],
+            Some(&user.pubkey()),
+            &[&user],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make through CPI failed");
+
+        let escrow_data = client::load_escrow(&svm.get_account(&escrow).unwrap().data).unwrap();
+        assert_eq!(escrow_data.maker, maker);
+        assert_eq!(escrow_data.deposit, 400);
+        assert_eq!(escrow_data.receive, 100);
+        assert_eq!(get_token_balance(&svm, &vault), 400);
+        assert_eq!(get_token_balance(&svm, &maker_ata_a), 600);
+    }
+
+    #[test]
+    fn test_settlement_hook_fires_on_take() {
+        let mut svm = setup();
+        let admin = init_config(&mut svm);
+
+        let maker = Keypair::new();
+        let taker = Keypair::new();
+        svm.airdrop(&maker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+        svm.airdrop(&taker.pubkey(), 10 * LAMPORTS_PER_SOL).unwrap();
+
+        let mint_a = CreateMint::new(&mut svm, &maker)
+            .authority(&maker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let mint_b = CreateMint::new(&mut svm, &taker)
+            .authority(&taker.pubkey())
+            .decimals(6)
+            .send()
+            .unwrap();
+        let maker_ata_a = CreateAssociatedTokenAccount::new(&mut svm, &maker, &mint_a)
+            .owner(&maker.pubkey()).send().unwrap();
+        let taker_ata_b = CreateAssociatedTokenAccount::new(&mut svm, &taker, &mint_b)
+            .owner(&taker.pubkey()).send().unwrap();
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 1_000).send().unwrap();
+        MintTo::new(&mut svm, &taker, &mint_b, &taker_ata_b, 1_000).send().unwrap();
+
+        // This program is the hook
+        let seed = 7;
+        let args = MakeArgs { seed, deposit: 400, receive: 100, settlement_hook: crate::ID, ..Default::default() };
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, args)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Make failed");
+
+        let escrow = client::escrow_pda(&maker.pubkey(), seed).0;
+        let record = Pubkey::find_program_address(&[b"settlement", escrow.as_ref()], &crate::ID).0;
+
+        // The hook program first, then the accounts its settlement_hook instruction takes
+        let take = |svm: &mut LiteSVM, take_amount: u64| {
+            let mut take_ix = client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed, &admin.pubkey(), take_amount, u64::MAX);
+            take_ix.accounts.push(AccountMeta::new_readonly(crate::ID, false));
+            take_ix.accounts.extend(crate::accounts::RecordSettlement {
+                payer: taker.pubkey(),
+                record,
+                system_program: system_program::ID,
+            }.to_account_metas(None));
+            let tx = Transaction::new_signed_with_payer(
+                &[take_ix],
+                Some(&taker.pubkey()),
+                &[&taker],
+                svm.latest_blockhash(),
+            );
+            svm.send_transaction(tx)
+        };
+
+        take(&mut svm, 100).expect("Partial take failed");
+        take(&mut svm, 300).expect("Final take failed");
+        assert!(svm.get_account(&escrow).is_none(), "Escrow should be closed after the final take");
+
+        let record = crate::SettlementRecord::try_deserialize(&mut svm.get_account(&record).unwrap().data.as_slice()).unwrap();
+        assert_eq!(record.escrow, escrow);
+        assert_eq!(record.last_taker, taker.pubkey());
+        assert_eq!(record.calls, 2);
+        assert_eq!(record.total_take_amount, 400);
+        assert_eq!(record.total_receive_amount, 100);
+
+        // Leaving the hook out fails the take
+        MintTo::new(&mut svm, &maker, &mint_a, &maker_ata_a, 400).send().unwrap();
+        let args = MakeArgs { seed: seed + 1, deposit: 400, receive: 100, settlement_hook: crate::ID, ..Default::default() };
+        let tx = Transaction::new_signed_with_payer(
+            &[client::make_ix(&maker.pubkey(), &mint_a, &mint_b, args)],
+            Some(&maker.pubkey()),
+            &[&maker],
+            svm.latest_blockhash(),
+        );
+        svm.send_transaction(tx).expect("Second make failed");
+        let tx = Transaction::new_signed_with_payer(
+            &[client::take_ix(&taker.pubkey(), &maker.pubkey(), &mint_a, &mint_b, seed + 1, &admin.pubkey(), 400, u64::MAX)],
+            Some(&taker.pubkey()),
+            &[&taker],
+            svm.latest_blockhash(),
+        );
+        assert!(svm.send_transaction(tx).is_err(), "Take without the hook accounts should fail");
+    }
+}